@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pulldown_latex::{push_mathml, Parser, RenderConfig, Storage};
+
+/// Number of times the input is nested within itself.
+const DEPTH: usize = 1000;
+
+fuzz_target!(|data: &str| {
+    // Nest the input within groups, scripts and arguments to look for stack overflows.
+    let input = format!(
+        "{}{data}{}",
+        format!("{{{data}^{{\\sqrt").repeat(DEPTH),
+        "}}".repeat(DEPTH)
+    );
+    let storage = Storage::new();
+    let parser = Parser::new(&input, &storage);
+    let mut output = String::new();
+    let _ = push_mathml(&mut output, parser, RenderConfig::default());
+});
@@ -0,0 +1,538 @@
+//! Find and render math embedded in text or `HTML`.
+//!
+//! This is the offline equivalent of `KaTeX`'s [auto-render extension][auto-render]. The
+//! [`Segments`] iterator splits its input into [`Segment::Text`] and [`Segment::Math`] segments,
+//! where math is delimited by one of the following:
+//!
+//! | Delimiters                                | Display Mode            |
+//! |-------------------------------------------|-------------------------|
+//! | `$...$`                                   | [`DisplayMode::Inline`] |
+//! | `\(...\)`                                 | [`DisplayMode::Inline`] |
+//! | `$$...$$`                                 | [`DisplayMode::Block`]  |
+//! | `\[...\]`                                 | [`DisplayMode::Block`]  |
+//! | `\begin{equation}...\end{equation}`, etc. | [`DisplayMode::Block`]  |
+//!
+//! The environments that are recognized are `equation`, `align`, `alignat`, `gather`, their
+//! starred variants, and `multline`. Math is not searched for in the following places:
+//! - After a backslash, so `\$` is a literal dollar sign. The backslash is removed from the text.
+//! - Inside of code spans delimited by backticks, e.g., `` `$x$` ``.
+//! - Inside of the `code`, `pre`, `script`, `noscript`, `style`, `textarea`, and `option`
+//!   `HTML` elements.
+//!
+//! To avoid mistaking amounts of money for math, the content of `$...$` must neither start nor end
+//! with whitespace, and the closing `$` must not be followed by a digit (e.g., `$5 and $10`).
+//! Delimiters that are not closed are left in the text. As in `KaTeX`, so are the following
+//! delimiters of the same kind, since their closing delimiter is known to be missing.
+//!
+//! The [`push_auto_rendered`] and [`write_auto_rendered`] functions render the math segments of
+//! their input using the `mathml` renderer, and output the text segments as is.
+//!
+//! [auto-render]: https://katex.org/docs/autorender
+//!
+//! ## Example
+//!
+//! ```rust
+//! use pulldown_latex::{
+//!     auto_render::{Segment, Segments},
+//!     config::DisplayMode,
+//! };
+//!
+//! let segments = Segments::new(r"It costs \$5 if $x > 0$.").collect::<Vec<_>>();
+//! assert_eq!(
+//!     segments,
+//!     [
+//!         Segment::Text("It costs "),
+//!         Segment::Text("$5 if "),
+//!         Segment::Math {
+//!             content: "x > 0".into(),
+//!             display_mode: DisplayMode::Inline,
+//!         },
+//!         Segment::Text("."),
+//!     ]
+//! );
+//! ```
+
+use std::{borrow::Cow, io};
+
+use crate::{
+    config::{DisplayMode, RenderConfig},
+    mathml::write_mathml,
+    parser::{storage::Storage, Parser},
+};
+
+/// The environments that are rendered as block math when found in text.
+const ENVIRONMENTS: [&str; 9] = [
+    "equation",
+    "equation*",
+    "align",
+    "align*",
+    "alignat",
+    "alignat*",
+    "gather",
+    "gather*",
+    "multline",
+];
+
+/// The `HTML` elements whose content is never searched for math.
+const IGNORED_ELEMENTS: [&str; 7] = [
+    "script", "noscript", "style", "textarea", "pre", "code", "option",
+];
+
+/// A closing delimiter, which is an index into the bits of [`Unclosed`].
+#[derive(Debug, Clone, Copy)]
+enum Closing {
+    Dollar,
+    DoubleDollar,
+    Parenthesis,
+    Bracket,
+    /// The closing delimiter of the environment at the given index of [`ENVIRONMENTS`].
+    Environment(usize),
+}
+
+/// The closing delimiters that were not found in the rest of the input.
+///
+/// Opening delimiters whose closing delimiter is known to be missing are left in the text without
+/// searching for it again, which would take quadratic time for many unclosed delimiters.
+#[derive(Debug, Clone, Copy, Default)]
+struct Unclosed(u16);
+
+impl Unclosed {
+    fn bit(closing: Closing) -> u16 {
+        1 << match closing {
+            Closing::Dollar => 0,
+            Closing::DoubleDollar => 1,
+            Closing::Parenthesis => 2,
+            Closing::Bracket => 3,
+            Closing::Environment(index) => 4 + index,
+        }
+    }
+
+    /// Find the index of the closing delimiter in the input, as [`closing_delimiter`] does,
+    /// unless it is known to be missing.
+    fn find(&mut self, closing: Closing, input: &str, delimiter: &str) -> Option<usize> {
+        let bit = Self::bit(closing);
+        if self.0 & bit != 0 {
+            return None;
+        }
+        let index = closing_delimiter(input, delimiter);
+        if index.is_none() {
+            self.0 |= bit;
+        }
+        index
+    }
+}
+
+/// A part of the input of [`Segments`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment<'a> {
+    /// Text that does not contain math, which is a substring of the input.
+    Text(&'a str),
+    /// Math found in the input.
+    Math {
+        /// The `LaTeX` source of the math, without the surrounding delimiters.
+        ///
+        /// Math delimited by an environment includes the `\begin{...}` and `\end{...}`
+        /// commands, since they are part of the source. The `&lt;`, `&gt;`, `&amp;`, `&quot;`,
+        /// `&#39;`, and `&nbsp;` `HTML` entities are decoded.
+        content: Cow<'a, str>,
+        /// The display mode implied by the delimiters.
+        display_mode: DisplayMode,
+    },
+}
+
+/// An iterator over the [`Segment`]s of text or `HTML` containing math.
+///
+/// See the [module documentation](self) for the delimiters that are recognized.
+#[derive(Debug, Clone)]
+pub struct Segments<'a> {
+    rest: &'a str,
+    /// The number of bytes at the start of `rest` that are known to be text.
+    skip: usize,
+    /// Math that directly follows the text segment that was last returned.
+    pending: Option<(Segment<'a>, &'a str)>,
+    unclosed: Unclosed,
+}
+
+impl<'a> Segments<'a> {
+    /// Create an iterator over the segments of the given input.
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            rest: input,
+            skip: 0,
+            pending: None,
+            unclosed: Unclosed::default(),
+        }
+    }
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = Segment<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((segment, rest)) = self.pending.take() {
+            self.rest = rest;
+            return Some(segment);
+        }
+        if self.rest.is_empty() {
+            return None;
+        }
+
+        let mut index = std::mem::take(&mut self.skip);
+        while index < self.rest.len() {
+            let bytes = self.rest.as_bytes();
+            match bytes[index] {
+                b'\\' if bytes.get(index + 1) == Some(&b'$') => {
+                    // Remove the backslash, and keep the dollar sign as text.
+                    let text = &self.rest[..index];
+                    self.rest = &self.rest[index + 1..];
+                    if text.is_empty() {
+                        index = 1;
+                        continue;
+                    }
+                    self.skip = 1;
+                    return Some(Segment::Text(text));
+                }
+                b'\\' | b'$' => {
+                    let Some((segment, rest)) = math(&self.rest[index..], &mut self.unclosed)
+                    else {
+                        // A backslash always escapes the following character.
+                        index += if bytes[index] == b'\\' { 2 } else { 1 };
+                        continue;
+                    };
+                    if index == 0 {
+                        self.rest = rest;
+                        return Some(segment);
+                    }
+                    let text = &self.rest[..index];
+                    self.pending = Some((segment, rest));
+                    return Some(Segment::Text(text));
+                }
+                b'`' => index += code_span_length(&self.rest[index..]),
+                b'<' => index += ignored_element_length(&self.rest[index..]),
+                _ => index += 1,
+            }
+        }
+
+        let text = self.rest;
+        self.rest = &self.rest[self.rest.len()..];
+        Some(Segment::Text(text))
+    }
+}
+
+/// Parse math at the start of the input, and return it along with the rest of the input.
+fn math<'a>(input: &'a str, unclosed: &mut Unclosed) -> Option<(Segment<'a>, &'a str)> {
+    let (content, rest, display_mode) = if let Some(rest) = input.strip_prefix("$$") {
+        let end = unclosed.find(Closing::DoubleDollar, rest, "$$")?;
+        (&rest[..end], &rest[end + 2..], DisplayMode::Block)
+    } else if let Some(rest) = input.strip_prefix('$') {
+        if rest.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let mut searched = rest;
+        let end = loop {
+            let end =
+                unclosed.find(Closing::Dollar, searched, "$")? + (rest.len() - searched.len());
+            // `$$` never closes inline math.
+            if rest[end + 1..].starts_with('$') {
+                searched = &rest[end + 2..];
+                continue;
+            }
+            if !rest[..end].ends_with(char::is_whitespace)
+                && !rest[end + 1..].starts_with(|c: char| c.is_ascii_digit())
+            {
+                break end;
+            }
+            searched = &rest[end + 1..];
+        };
+        if end == 0 {
+            return None;
+        }
+        (&rest[..end], &rest[end + 1..], DisplayMode::Inline)
+    } else if let Some(rest) = input.strip_prefix(r"\(") {
+        let end = unclosed.find(Closing::Parenthesis, rest, r"\)")?;
+        (&rest[..end], &rest[end + 2..], DisplayMode::Inline)
+    } else if let Some(rest) = input.strip_prefix(r"\[") {
+        let end = unclosed.find(Closing::Bracket, rest, r"\]")?;
+        (&rest[..end], &rest[end + 2..], DisplayMode::Block)
+    } else {
+        let rest = input.strip_prefix(r"\begin{")?;
+        let index = ENVIRONMENTS.iter().position(|environment| {
+            rest.strip_prefix(environment)
+                .is_some_and(|rest| rest.starts_with('}'))
+        })?;
+        let environment = ENVIRONMENTS[index];
+        let rest = &rest[environment.len() + 1..];
+        let closing = format!(r"\end{{{environment}}}");
+        let end = unclosed.find(Closing::Environment(index), rest, &closing)?;
+        let length = input.len() - rest.len() + end + closing.len();
+        (&input[..length], &input[length..], DisplayMode::Block)
+    };
+
+    Some((
+        Segment::Math {
+            content: decode_entities(content),
+            display_mode,
+        },
+        rest,
+    ))
+}
+
+/// Find the index of the closing delimiter in the input, skipping over escaped characters and
+/// groups.
+fn closing_delimiter(input: &str, closing: &str) -> Option<usize> {
+    let bytes = input.as_bytes();
+    let mut depth = 0usize;
+    let mut index = 0;
+    while index < bytes.len() {
+        if depth == 0 && bytes[index..].starts_with(closing.as_bytes()) {
+            return Some(index);
+        }
+        match bytes[index] {
+            b'\\' => index += 1,
+            b'{' => depth += 1,
+            b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        index += 1;
+    }
+    None
+}
+
+/// Returns the length of the code span at the start of the input, or the length of the opening
+/// backticks if the code span is not closed.
+fn code_span_length(input: &str) -> usize {
+    let backticks = input.bytes().take_while(|&b| b == b'`').count();
+    let mut rest = &input[backticks..];
+    while let Some(start) = rest.find('`') {
+        let count = rest[start..].bytes().take_while(|&b| b == b'`').count();
+        rest = &rest[start + count..];
+        if count == backticks {
+            return input.len() - rest.len();
+        }
+    }
+    backticks
+}
+
+/// Returns the length of the ignored `HTML` element at the start of the input, or 1 if the input
+/// does not start with an ignored element.
+fn ignored_element_length(input: &str) -> usize {
+    let name_length = input[1..]
+        .bytes()
+        .take_while(u8::is_ascii_alphabetic)
+        .count();
+    let name = &input[1..1 + name_length];
+    let ignored = IGNORED_ELEMENTS
+        .iter()
+        .any(|element| element.eq_ignore_ascii_case(name))
+        && matches!(
+            input.as_bytes().get(1 + name_length),
+            Some(b'>' | b'/' | b' ' | b'\t' | b'\n' | b'\r')
+        );
+    if !ignored {
+        return 1;
+    }
+
+    let closing = format!("</{name}");
+    let Some(end) = input
+        .as_bytes()
+        .windows(closing.len())
+        .position(|window| window.eq_ignore_ascii_case(closing.as_bytes()))
+    else {
+        return input.len();
+    };
+    input[end..]
+        .find('>')
+        .map_or(input.len(), |close| end + close + 1)
+}
+
+/// Decode the `HTML` entities commonly found in math.
+fn decode_entities(input: &str) -> Cow<'_, str> {
+    if !input.contains('&') {
+        return Cow::Borrowed(input);
+    }
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(index) = rest.find('&') {
+        output.push_str(&rest[..index]);
+        rest = &rest[index..];
+        let (decoded, length) = [
+            ("&lt;", '<'),
+            ("&gt;", '>'),
+            ("&amp;", '&'),
+            ("&quot;", '"'),
+            ("&#39;", '\''),
+            ("&nbsp;", ' '),
+        ]
+        .into_iter()
+        .find_map(|(entity, c)| rest.starts_with(entity).then_some((c, entity.len())))
+        .unwrap_or(('&', 1));
+        output.push(decoded);
+        rest = &rest[length..];
+    }
+    output.push_str(rest);
+    Cow::Owned(output)
+}
+
+/// Renders the math found in the input into the given string, and copies the text as is.
+///
+/// The display mode of the `config` is overridden by the one implied by the delimiters of each
+/// math segment. See the [module documentation](self) for more details.
+///
+/// ## Example
+///
+/// ```rust
+/// use pulldown_latex::{auto_render::push_auto_rendered, RenderConfig};
+///
+/// let mut output = String::new();
+/// push_auto_rendered(&mut output, r"<p>Let \(x = 1\).</p>", RenderConfig::default()).unwrap();
+/// assert!(output.starts_with("<p>Let <math"));
+/// assert!(output.ends_with("</math>.</p>"));
+/// ```
+pub fn push_auto_rendered(
+    string: &mut String,
+    input: &str,
+    config: RenderConfig,
+) -> io::Result<()> {
+    // SAFETY: Text segments are substrings of the input, and the `mathml` renderer only writes
+    // valid utf-8.
+    write_auto_rendered(unsafe { string.as_mut_vec() }, input, config)
+}
+
+/// Renders the math found in the input into the given writer, and copies the text as is.
+///
+/// The display mode of the `config` is overridden by the one implied by the delimiters of each
+/// math segment. See the [module documentation](self) for more details.
+pub fn write_auto_rendered<W: io::Write>(
+    mut writer: W,
+    input: &str,
+    config: RenderConfig,
+) -> io::Result<()> {
+    let mut storage = Storage::new();
+    for segment in Segments::new(input) {
+        match segment {
+            Segment::Text(text) => writer.write_all(text.as_bytes())?,
+            Segment::Math {
+                content,
+                display_mode,
+            } => {
+                let parser = Parser::new(&content, &storage);
+                write_mathml(
+                    &mut writer,
+                    parser,
+                    RenderConfig {
+                        display_mode,
+                        ..config
+                    },
+                )?;
+                storage.reset();
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn math(content: &str, display_mode: DisplayMode) -> Segment<'_> {
+        Segment::Math {
+            content: content.into(),
+            display_mode,
+        }
+    }
+
+    #[test]
+    fn delimiters() {
+        let segments =
+            Segments::new(r"a $x$ b $$y$$ c \(z\) d \[w\] e \begin{align*}v &= u\end{align*} f")
+                .collect::<Vec<_>>();
+        assert_eq!(
+            segments,
+            [
+                Segment::Text("a "),
+                math("x", DisplayMode::Inline),
+                Segment::Text(" b "),
+                math("y", DisplayMode::Block),
+                Segment::Text(" c "),
+                math("z", DisplayMode::Inline),
+                Segment::Text(" d "),
+                math("w", DisplayMode::Block),
+                Segment::Text(" e "),
+                math(r"\begin{align*}v &= u\end{align*}", DisplayMode::Block),
+                Segment::Text(" f"),
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes_and_money() {
+        let segments = Segments::new(r"\$5 or $ x $ and $\$$ \$").collect::<Vec<_>>();
+        assert_eq!(
+            segments,
+            [
+                Segment::Text("$5 or $ x $ and "),
+                math(r"\$", DisplayMode::Inline),
+                Segment::Text(" "),
+                Segment::Text("$"),
+            ]
+        );
+        let segments = Segments::new("costs $5 and $10").collect::<Vec<_>>();
+        assert_eq!(segments, [Segment::Text("costs $5 and $10")]);
+    }
+
+    #[test]
+    fn nested_delimiters() {
+        let segments = Segments::new(r"$\text{$x$}$ and \(\{\)").collect::<Vec<_>>();
+        assert_eq!(
+            segments,
+            [
+                math(r"\text{$x$}", DisplayMode::Inline),
+                Segment::Text(" and "),
+                math(r"\{", DisplayMode::Inline),
+            ]
+        );
+    }
+
+    #[test]
+    fn code_and_ignored_elements() {
+        let input = "`$x$` ``a ` $y$`` <CODE>$z$</code> <pre class=\"a\">$w$</pre> <p>$v$</p>";
+        let segments = Segments::new(input).collect::<Vec<_>>();
+        assert_eq!(
+            segments,
+            [
+                Segment::Text("`$x$` ``a ` $y$`` <CODE>$z$</code> <pre class=\"a\">$w$</pre> <p>"),
+                math("v", DisplayMode::Inline),
+                Segment::Text("</p>"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unclosed_delimiters() {
+        let segments = Segments::new(r"$x \(y $$z `w").collect::<Vec<_>>();
+        assert_eq!(segments, [Segment::Text(r"$x \(y $$z `w")]);
+    }
+
+    #[test]
+    fn many_unclosed_delimiters() {
+        // Searching for the missing closing delimiter of every opening delimiter would take
+        // quadratic time.
+        let input = format!("$$ {}", r"$5 \( \[ \begin{align} ".repeat(50_000));
+        let segments = Segments::new(&input).collect::<Vec<_>>();
+        assert_eq!(segments, [Segment::Text(&input)]);
+
+        // A delimiter closed later is still found after an unclosed one of another kind.
+        let segments = Segments::new(r"\( $x$").collect::<Vec<_>>();
+        assert_eq!(
+            segments,
+            [Segment::Text(r"\( "), math("x", DisplayMode::Inline)]
+        );
+    }
+
+    #[test]
+    fn entities() {
+        let segments = Segments::new("$a &lt; b &amp;&amp; c$").collect::<Vec<_>>();
+        assert_eq!(segments, [math("a < b && c", DisplayMode::Inline)]);
+    }
+}
@@ -1,17 +1,23 @@
-//! Configuration options for the renderer.
+//! Configuration options for the parser and the renderer.
 //!
-//! The configuration of the `mathml` renderer is done through the [`RenderConfig`] struct.
+//! The configuration of the parser is done through the [`ParserConfig`] struct, and the configuration
+//! of the `mathml` renderer is done through the [`RenderConfig`] struct.
 use std::fmt::Display;
 
 /// Configuration for the `mathml` renderer.
 ///
 /// The default value is: [`RenderConfig::default`].
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RenderConfig<'a> {
     /// See [`DisplayMode`].
     pub display_mode: DisplayMode,
     /// If Some, the `mathml` generated includes an `<annotation>` element that contains the
     /// provided string. It is commonly used to include the LaTeX source code in the generated `mathml`.
+    ///
+    /// With the `serde` feature, the annotation is borrowed from the deserializer's input, which
+    /// fails if the string contains escape sequences in that format (e.g., `\\` in JSON).
+    #[cfg_attr(feature = "serde", serde(borrow))]
     pub annotation: Option<&'a str>,
     /// A RGB color. This option determines the color in which errors and invalid LaTeX commands are rendered in.
     pub error_color: (u8, u8, u8),
@@ -59,6 +65,76 @@ impl<'a> Default for RenderConfig<'a> {
     }
 }
 
+/// Configuration for the [`Parser`](crate::Parser).
+///
+/// Besides the error recovery mode, this configures the limits imposed on the parser, which are
+/// useful when parsing untrusted input. When a limit is exceeded, the parser returns an error of
+/// the corresponding [`ErrorKind`]. Exceeding the limits on the group nesting depth, the number of
+/// events, the input length, or the step budget stops the parser altogether, even when error
+/// recovery is enabled.
+///
+/// The default value is: [`ParserConfig::default`].
+///
+/// [`ErrorKind`]: crate::parser::error::ErrorKind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParserConfig {
+    /// If true, the parser recovers from errors instead of returning them.
+    ///
+    /// Every construct that fails to parse is replaced by a [`Content::Error`] event containing
+    /// the kind of error and the source text of the construct, so that the event stream remains
+    /// well-formed and the rest of the input is still parsed.
+    ///
+    /// [`Content::Error`]: crate::event::Content::Error
+    pub error_recovery: bool,
+    /// The maximum depth of nested macro expansions, which guards against infinite recursion.
+    pub max_expansion_depth: usize,
+    /// The maximum number of bytes that macro expansions can allocate in the
+    /// [`Storage`](crate::Storage).
+    pub max_expansion_bytes: usize,
+    /// If Some, the maximum nesting depth of groups (i.e., of [`Event::Begin`] events).
+    ///
+    /// [`Event::Begin`]: crate::event::Event::Begin
+    pub max_group_depth: Option<usize>,
+    /// If Some, the maximum number of events the parser can produce.
+    pub max_events: Option<usize>,
+    /// If Some, the maximum length of the input in bytes.
+    pub max_input_length: Option<usize>,
+    /// If Some, the maximum number of parsing steps the parser can take, where a step is the
+    /// parsing of a single token along with its arguments and scripts.
+    ///
+    /// This can be used to bound the time spent parsing an input.
+    pub step_budget: Option<usize>,
+}
+
+impl Default for ParserConfig {
+    /// # Default Value
+    /// ```rust
+    /// # use pulldown_latex::config::ParserConfig;
+    /// const DEFAULT: ParserConfig = ParserConfig {
+    ///     error_recovery: false,
+    ///     max_expansion_depth: 64,
+    ///     max_expansion_bytes: 1024 * 1024,
+    ///     max_group_depth: None,
+    ///     max_events: None,
+    ///     max_input_length: None,
+    ///     step_budget: None,
+    /// };
+    /// assert_eq!(ParserConfig::default(), DEFAULT);
+    /// ```
+    fn default() -> Self {
+        Self {
+            error_recovery: false,
+            max_expansion_depth: 64,
+            max_expansion_bytes: 1024 * 1024,
+            max_group_depth: None,
+            max_events: None,
+            max_input_length: None,
+            step_budget: None,
+        }
+    }
+}
+
 /// The way in which math variables are displayed.
 ///
 /// This is used to determine how single-letter variables are displayed. This affects lowercase and
@@ -74,6 +150,7 @@ impl<'a> Default for RenderConfig<'a> {
 /// | `French`  | _italic_   | upright    | upright    | upright    |
 /// | `Upright` | upright    | upright    | upright    | upright    |
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MathStyle {
     /// The default style used in TeX.
     ///
@@ -119,6 +196,7 @@ impl MathStyle {
 /// [`<math>`]: https://developer.mozilla.org/en-US/docs/Web/MathML/Element/math
 /// [`display`]: https://developer.mozilla.org/en-US/docs/Web/MathML/Element/math#display
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DisplayMode {
     /// The math is rendered inline.
     ///
@@ -13,7 +13,14 @@
 //! [`push_mathml`]: crate::mathml::push_mathml
 //! [`write_mathml`]: crate::mathml::write_mathml
 
-use std::fmt::Display;
+use std::{borrow::Cow, fmt::Display};
+
+use crate::parser::error::ErrorKind;
+
+mod validate;
+
+pub(crate) use validate::{allows_tags, max_alignments};
+pub use validate::{validate, Validate, ValidationError, Violation};
 
 /// All events that can be produced by the parser.
 ///
@@ -22,7 +29,7 @@ use std::fmt::Display;
 /// When an [`Event`] is referreing to an "_element_", it is referring to the next logical unit of
 /// content in the stream. This can be a single [`Event::Content`] element, a group marked
 /// by [`Event::Begin`] and [`Event::End`], an [`Event::Visual`] or an [`Event::Script`] element,
-/// an [`Event::Space`], or an [`Event::StateChange`].
+/// an [`Event::Space`], an [`Event::Rule`], or an [`Event::StateChange`].
 ///
 /// [`Event::Alignment`]s, and [`Event::NewLine`]s are not considered elements, and must never
 /// occur when an element is expected.
@@ -34,7 +41,7 @@ use std::fmt::Display;
 /// __Input__: `\text{Hello, world!}`
 /// ```
 /// # use pulldown_latex::event::{Event, Content};
-/// [Event::Content(Content::Text("Hello, world!"))];
+/// [Event::Content(Content::Text("Hello, world!".into()))];
 /// ```
 ///
 /// __Input__: `x^2_{\text{max}}`
@@ -46,7 +53,7 @@ use std::fmt::Display;
 ///         position: ScriptPosition::Right,
 ///     },
 ///     Event::Begin(Grouping::Normal),
-///     Event::Content(Content::Text("max")),
+///     Event::Content(Content::Text("max".into())),
 ///     Event::End,
 ///     Event::Content(Content::Ordinary {
 ///         content: 'x',
@@ -55,6 +62,7 @@ use std::fmt::Display;
 /// ];
 /// ```
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Event<'a> {
     /// The event is a [`Content`] element.
     Content(Content<'a>),
@@ -87,6 +95,18 @@ pub enum Event<'a> {
         /// The amount of depth (space below the baseline) to add.
         depth: Option<Dimension>,
     },
+    /// A filled rectangle, produced by `\rule`.
+    ///
+    /// The rectangle is drawn in the current text color, from `depth` below the baseline to
+    /// `height` above it.
+    Rule {
+        /// The width of the rectangle.
+        width: Dimension,
+        /// The height of the rectangle above the baseline.
+        height: Dimension,
+        /// The depth of the rectangle below the baseline.
+        depth: Dimension,
+    },
     /// This event specifies a state change in the renderer.
     ///
     /// This state change only applies to the current group nesting and deeper groups.
@@ -94,19 +114,20 @@ pub enum Event<'a> {
 
     /// This is a flow event that is emitted in mathematical environments such as `align`,
     /// `cases`, `array`, etc.
-    EnvironmentFlow(EnvironmentFlow),
+    EnvironmentFlow(EnvironmentFlow<'a>),
 }
 
 /// Base events that produce `mathml` nodes
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Content<'a> {
     /// Text content that should be typeset following the rules of `LaTeX`'s `text` mode.
-    Text(&'a str),
+    Text(Cow<'a, str>),
     /// A number, which can include decimal points and commas.
-    Number(&'a str),
+    Number(Cow<'a, str>),
     /// A function identifier, such as `sin`, `lim`, or a custom function with
     /// `\operatorname{arccotan}`.
-    Function(&'a str),
+    Function(Cow<'a, str>),
     /// A variable identifier, such as `x`, `\theta`, `\aleph`, etc., and other stuff that do not have
     /// any spacing around them. This includes things that normally go in under and overscripts
     /// which may be stretchy, e.g., `→`, `‾`, etc.
@@ -150,10 +171,107 @@ pub enum Content<'a> {
     },
     /// A punctuation character, such as `,`, `.`, or `;`.
     Punctuation(char),
+    /// A reference to a labeled row of an environment, as given by `\ref{...}` or `\eqref{...}`.
+    ///
+    /// The parser does not know the number of the labeled row, which is filled in by
+    /// [`Numbering::resolve`](crate::numbering::Numbering::resolve).
+    Reference {
+        /// The label being referred to.
+        label: Cow<'a, str>,
+        /// The number of the labeled row, or `None` if the reference is not resolved.
+        number: Option<Cow<'a, str>>,
+        /// Whether the number is surrounded by parentheses, as is the case for `\eqref`.
+        parenthesized: bool,
+    },
+    /// A placeholder for a construct that could not be parsed.
+    ///
+    /// This is only produced by the parser when [`ParserConfig::error_recovery`] is enabled, in
+    /// which case it replaces the faulty construct in the event stream.
+    ///
+    /// [`ParserConfig::error_recovery`]: crate::config::ParserConfig::error_recovery
+    Error {
+        /// The kind of error that occurred.
+        kind: ErrorKind,
+        /// The source text of the faulty construct.
+        source: Cow<'a, str>,
+    },
+}
+
+impl<'a> Event<'a> {
+    /// Convert the event into an owned event, which does not borrow from the input or the
+    /// [`Storage`].
+    ///
+    /// This allows for events to be cached, sent across threads, or kept after the storage is
+    /// reset.
+    ///
+    /// [`Storage`]: crate::Storage
+    pub fn into_owned(self) -> Event<'static> {
+        match self {
+            Event::Content(content) => Event::Content(content.into_owned()),
+            Event::Begin(grouping) => Event::Begin(grouping),
+            Event::End => Event::End,
+            Event::Visual(visual) => Event::Visual(visual),
+            Event::Script { ty, position } => Event::Script { ty, position },
+            Event::Space {
+                width,
+                height,
+                depth,
+            } => Event::Space {
+                width,
+                height,
+                depth,
+            },
+            Event::Rule {
+                width,
+                height,
+                depth,
+            } => Event::Rule {
+                width,
+                height,
+                depth,
+            },
+            Event::StateChange(state_change) => Event::StateChange(state_change),
+            Event::EnvironmentFlow(flow) => Event::EnvironmentFlow(flow.into_owned()),
+        }
+    }
+}
+
+impl<'a> Content<'a> {
+    /// Convert the content into an owned content, which does not borrow from the input or the
+    /// [`Storage`].
+    ///
+    /// [`Storage`]: crate::Storage
+    pub fn into_owned(self) -> Content<'static> {
+        match self {
+            Content::Text(text) => Content::Text(Cow::Owned(text.into_owned())),
+            Content::Number(number) => Content::Number(Cow::Owned(number.into_owned())),
+            Content::Function(function) => Content::Function(Cow::Owned(function.into_owned())),
+            Content::Ordinary { content, stretchy } => Content::Ordinary { content, stretchy },
+            Content::LargeOp { content, small } => Content::LargeOp { content, small },
+            Content::BinaryOp { content, small } => Content::BinaryOp { content, small },
+            Content::Relation { content, small } => Content::Relation { content, small },
+            Content::Delimiter { content, size, ty } => Content::Delimiter { content, size, ty },
+            Content::Punctuation(content) => Content::Punctuation(content),
+            Content::Reference {
+                label,
+                number,
+                parenthesized,
+            } => Content::Reference {
+                label: Cow::Owned(label.into_owned()),
+                number: number.map(|number| Cow::Owned(number.into_owned())),
+                parenthesized,
+            },
+            Content::Error { kind, source } => Content::Error {
+                kind,
+                source: Cow::Owned(source.into_owned()),
+            },
+        }
+    }
 }
 
 /// Modifies the visual representation of the following element(s)
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Visual {
     /// The following element is the content of the root.
     SquareRoot,
@@ -172,6 +290,67 @@ pub enum Visual {
     /// generates an akward looking negation across the next element, when it does not correspond
     /// to a commonly negated element.
     Negation,
+    /// The following element is surrounded by a frame, as produced by `\boxed`, `\fbox`, and
+    /// `\framebox`.
+    Framed(Framing),
+    /// The following element is invisible, but still takes up space.
+    ///
+    /// Only the dimensions whose fields are `true` are kept: `\phantom` keeps both, `\hphantom`
+    /// keeps only the width, and `\vphantom` keeps only the height and depth.
+    Phantom {
+        /// Whether the width of the element is kept.
+        horizontal: bool,
+        /// Whether the height and depth of the element are kept.
+        vertical: bool,
+    },
+    /// The following element is displayed, but its height, depth, or both are treated as zero, as
+    /// produced by `\smash`, `\smash[t]`, and `\smash[b]`.
+    Smash {
+        /// Whether the height of the element is treated as zero.
+        top: bool,
+        /// Whether the depth of the element is treated as zero.
+        bottom: bool,
+    },
+    /// The following element is displayed with a width of zero, so that it overlaps the content
+    /// on its left (`\llap`), on its right (`\rlap`), or on both sides (`\clap`).
+    Overlap(ColumnAlignment),
+    /// The following element is shifted up by the given distance, or down if it is negative, as
+    /// produced by `\raise`, `\lower`, and `\raisebox`.
+    Raise(Dimension),
+    /// The 4 following elements are the content to display in display style, text style, script
+    /// style, and scriptscript style respectively, as given to `\mathchoice`.
+    ///
+    /// Only one of them is rendered, depending on the style in which this element is rendered.
+    MathChoice,
+    /// The following element is set in a horizontal box, as produced by `\hbox` and `\mbox`.
+    ///
+    /// The content of the box is in text mode, and is never broken across lines.
+    HorizontalBox,
+    /// The following element is vertically centered on the math axis, as produced by `\vcenter`.
+    VerticalCenter,
+}
+
+/// The appearance of a frame drawn around an element.
+///
+/// See [`Visual::Framed`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Framing {
+    /// The width of the box, or `None` for the natural width of its content.
+    pub width: Option<Dimension>,
+    /// The alignment of the content in the box, which matters when the box is wider than its
+    /// content.
+    pub alignment: ColumnAlignment,
+}
+
+impl Default for Framing {
+    /// A frame with the natural width of its content.
+    fn default() -> Self {
+        Self {
+            width: None,
+            alignment: ColumnAlignment::Center,
+        }
+    }
 }
 
 /// Logical type of the script. This is used to determine how to render the scripts.
@@ -179,6 +358,7 @@ pub enum Visual {
 /// Things like subscripts, underscripts, and movable scripts can be represented when using this
 /// `enum` in conjunction with the [`ScriptPosition`] `enum`.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ScriptType {
     /// The 2 following elements are the base and and the subscript
     Subscript,
@@ -190,6 +370,7 @@ pub enum ScriptType {
 
 /// Position of the script. This is used to determine how to render the scripts.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ScriptPosition {
     /// The scripts are rendered to the (bottom and top) right of the operator.
     Right,
@@ -209,6 +390,7 @@ pub enum ScriptPosition {
 /// when entering a new group that is not a `Grouping::Normal`, or a `Grouping::LeftRight`. The
 /// exception to the latter is `StateChange::Style`, which is maintained across all groups.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StateChange {
     /// Changes the font of the content.
     ///
@@ -225,6 +407,7 @@ pub enum StateChange {
 
 /// Available font styles from LaTeX.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Font {
     /// The bold and calligraphic font-face.
     BoldScript,
@@ -268,6 +451,7 @@ pub enum Font {
 /// This is analogous to the different "modes" in `LaTeX`, such as `display`, `text`, etc., which
 /// are set by commands like `\displaystyle`, `\textstyle`, etc.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Style {
     /// Set by the `\displaystyle` command.
     Display,
@@ -281,6 +465,7 @@ pub enum Style {
 
 /// Represents a color change.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ColorChange {
     /// The color to change to.
     ///
@@ -295,6 +480,7 @@ pub struct ColorChange {
 
 /// The target of the color change.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColorTarget {
     /// The text of the content.
     Text,
@@ -309,6 +495,7 @@ pub enum ColorTarget {
 /// This can be created by a lot of different `LaTeX` commands, such as `{}`, `\left`, `\right`,
 /// `\begin{...}`, `\end{...}`, etc.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Grouping {
     /// A normal form of grouping, usually induced by `{}` or `\begingroup` and `\endgroup` in `LaTeX`.
     Normal,
@@ -396,7 +583,8 @@ impl Grouping {
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum EnvironmentFlow {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EnvironmentFlow<'a> {
     /// This event specifies an alignment mark in a mathematical environment.
     ///
     /// This event is only emitted when inside a `Grouping` that allows it.
@@ -484,32 +672,134 @@ pub enum EnvironmentFlow {
     /// );
     /// ```
     StartLines { lines: Box<[Line]> },
+
+    /// This event replaces the equation number of the current row with a custom tag, as
+    /// specified by `\tag{...}` or `\tag*{...}`.
+    ///
+    /// This event is only emitted once per row, when inside of a `Grouping` that allows equation
+    /// numbers, i.e., `Equation`, `Align`, `Alignat`, `Gather` and `Multline`, whether they are
+    /// numbered or not.
+    Tag {
+        /// The text of the tag.
+        tag: Cow<'a, str>,
+        /// Whether the tag is surrounded by parentheses, which is not the case for `\tag*`.
+        parenthesized: bool,
+    },
+    /// This event specifies that the current row is not numbered, as specified by `\notag` or
+    /// `\nonumber`.
+    ///
+    /// This event is only emitted when inside of a `Grouping` that allows equation numbers.
+    NoNumber,
+    /// This event attaches a label to the current row, as specified by `\label{...}`, so that it
+    /// can be referred to.
+    ///
+    /// This event is only emitted when inside of a `Grouping` that allows equation numbers.
+    Label(Cow<'a, str>),
 }
 
-#[derive(Debug, Clone, Copy)]
-pub(crate) enum GroupingKind {
+impl<'a> EnvironmentFlow<'a> {
+    /// Convert the flow event into an owned one, which does not borrow from the input or the
+    /// [`Storage`].
+    ///
+    /// [`Storage`]: crate::Storage
+    pub fn into_owned(self) -> EnvironmentFlow<'static> {
+        match self {
+            EnvironmentFlow::Alignment => EnvironmentFlow::Alignment,
+            EnvironmentFlow::NewLine {
+                spacing,
+                horizontal_lines,
+            } => EnvironmentFlow::NewLine {
+                spacing,
+                horizontal_lines,
+            },
+            EnvironmentFlow::StartLines { lines } => EnvironmentFlow::StartLines { lines },
+            EnvironmentFlow::Tag { tag, parenthesized } => EnvironmentFlow::Tag {
+                tag: Cow::Owned(tag.into_owned()),
+                parenthesized,
+            },
+            EnvironmentFlow::NoNumber => EnvironmentFlow::NoNumber,
+            EnvironmentFlow::Label(label) => EnvironmentFlow::Label(Cow::Owned(label.into_owned())),
+        }
+    }
+}
+
+/// The kind of a group in the input, as reported by [`ErrorKind::UnbalancedGroup`] when it is
+/// not closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum GroupingKind {
+    /// A group delimited by `{` and `}`.
     Normal,
+    /// An optional argument delimited by `[` and `]`.
     OptionalArgument,
+    /// Any environment, delimited by `\begin` and `\end`.
     BeginEnd,
+    /// A group delimited by `\left` and `\right`.
     LeftRight,
-    Array { display: bool },
-    Matrix { ty: MatrixType, column_spec: bool },
-    Cases { left: bool, display: bool },
-    Equation { eq_numbers: bool },
-    Align { eq_numbers: bool },
+    /// The `array` environment, or `darray` if `display` is `true`.
+    Array {
+        /// Whether the environment is `darray`.
+        display: bool,
+    },
+    /// One of the matrix environments.
+    Matrix {
+        /// The delimiters and size of the matrix.
+        ty: MatrixType,
+        /// Whether the environment is the starred variant, which takes a column alignment.
+        column_spec: bool,
+    },
+    /// One of the `cases` environments.
+    Cases {
+        /// Whether the brace is on the left, i.e., the environment is not `rcases`.
+        left: bool,
+        /// Whether the environment is in display style, e.g., `dcases`.
+        display: bool,
+    },
+    /// The `equation` environment, or `equation*` if `eq_numbers` is `false`.
+    Equation {
+        /// Whether the equation is numbered.
+        eq_numbers: bool,
+    },
+    /// The `align` environment, or `align*` if `eq_numbers` is `false`.
+    Align {
+        /// Whether the equations are numbered.
+        eq_numbers: bool,
+    },
+    /// The `aligned` environment.
     Aligned,
+    /// The `subarray` environment.
     SubArray,
-    Alignat { eq_numbers: bool },
+    /// The `alignat` environment, or `alignat*` if `eq_numbers` is `false`.
+    Alignat {
+        /// Whether the equations are numbered.
+        eq_numbers: bool,
+    },
+    /// The `alignedat` environment.
     Alignedat,
-    Gather { eq_numbers: bool },
+    /// The `gather` environment, or `gather*` if `eq_numbers` is `false`.
+    Gather {
+        /// Whether the equations are numbered.
+        eq_numbers: bool,
+    },
+    /// The `gathered` environment.
     Gathered,
+    /// The `multline` environment.
     Multline,
+    /// The `split` environment.
     Split,
+    /// Math nested in text mode, delimited by `$`.
+    InlineMath,
+    /// Math nested in text mode, delimited by `\(` and `\)`.
+    ParenthesizedMath,
+    /// A user-defined environment, with the given name.
+    Environment(Cow<'static, str>),
 }
 
 impl GroupingKind {
-    pub fn opening_str(&self) -> &'static str {
-        match self {
+    /// The string opening the group.
+    pub(crate) fn opening_str(&self) -> Cow<'static, str> {
+        Cow::Borrowed(match self {
             Self::Normal => "{",
             Self::OptionalArgument => "[",
             Self::BeginEnd => "\\begin",
@@ -552,11 +842,15 @@ impl GroupingKind {
             Self::Gathered => "\\begin{gathered}",
             Self::Multline => "\\begin{multline}",
             Self::Split => "\\begin{split}",
-        }
+            Self::InlineMath => "$",
+            Self::ParenthesizedMath => "\\(",
+            Self::Environment(name) => return Cow::Owned(format!("\\begin{{{name}}}")),
+        })
     }
 
-    pub fn closing_str(&self) -> &'static str {
-        match self {
+    /// The string closing the group.
+    pub(crate) fn closing_str(&self) -> Cow<'static, str> {
+        Cow::Borrowed(match self {
             Self::Normal => "}",
             Self::OptionalArgument => "]",
             Self::BeginEnd => "\\end",
@@ -599,23 +893,38 @@ impl GroupingKind {
             Self::Gathered => "\\end{gathered}",
             Self::Multline => "\\end{multline}",
             Self::Split => "\\end{split}",
-        }
+            Self::InlineMath => "$",
+            Self::ParenthesizedMath => "\\)",
+            Self::Environment(name) => return Cow::Owned(format!("\\end{{{name}}}")),
+        })
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub(crate) enum MatrixType {
+/// The kind of a matrix environment.
+///
+/// See [`GroupingKind::Matrix`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MatrixType {
+    /// `matrix`, without delimiters.
     Normal,
+    /// `smallmatrix`, without delimiters and in text style.
     Small,
+    /// `pmatrix`, delimited by parentheses.
     Parens,
+    /// `bmatrix`, delimited by brackets.
     Brackets,
+    /// `Bmatrix`, delimited by braces.
     Braces,
+    /// `vmatrix`, delimited by vertical bars.
     Vertical,
+    /// `Vmatrix`, delimited by double vertical bars.
     DoubleVertical,
 }
 
 /// Represents a column in a matrix or array environment.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColumnAlignment {
     /// Content in the column is left-aligned.
     Left,
@@ -629,6 +938,7 @@ pub enum ColumnAlignment {
 ///
 /// It can either be a column specification or a vertical separator specification.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ArrayColumn {
     /// A column specification.
     Column(ColumnAlignment),
@@ -638,6 +948,7 @@ pub enum ArrayColumn {
 
 /// Represents a delimiter size.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DelimiterSize {
     /// Corresponds to `\bigl`, `\bigr`, etc.
     Big,
@@ -662,6 +973,7 @@ impl DelimiterSize {
 
 /// Whether the delimiter is an opening, closing, or fence delimiter.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DelimiterType {
     /// Corresponds to the left delimiter.
     Open,
@@ -673,6 +985,7 @@ pub enum DelimiterType {
 
 /// Represents a line in a `LaTeX` environment.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Line {
     /// A solid line.
     Solid,
@@ -683,6 +996,7 @@ pub enum Line {
 /// Sometimes mathematical relations can be made of more than one character, so we need a way to
 /// represent them when one character is not enough.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RelationContent {
     content: (char, Option<char>),
 }
@@ -700,6 +1014,10 @@ impl RelationContent {
         }
     }
 
+    pub(crate) fn chars(&self) -> (char, Option<char>) {
+        self.content
+    }
+
     /// Write the content of the relation to a buffer, and output the filled slice of that
     /// buffer.
     ///
@@ -718,6 +1036,7 @@ pub type Glue = (Dimension, Option<Dimension>, Option<Dimension>);
 
 /// Represents a LaTeX dimension.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Dimension {
     /// The value of the dimension.
     pub value: f32,
@@ -796,6 +1115,7 @@ impl Display for Dimension {
 // From the TeXbook, p. 57, 60, 167.
 /// Represents a dimension unit in LaTeX.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DimensionUnit {
     /// The `em` unit.
     Em,
@@ -0,0 +1,628 @@
+use std::fmt::Display;
+
+use super::{ArrayColumn, EnvironmentFlow, Event, Grouping, ScriptType, Visual};
+
+/// Check that a stream of events is structurally valid.
+///
+/// The returned iterator yields the events of the stream unchanged, until it finds the first
+/// structural violation, which is returned as a [`ValidationError::Violation`] along with the
+/// index of the event at fault. The iterator ends after a violation is reported.
+///
+/// The rules are the ones documented for [`Event`], and upheld by the [`Parser`]:
+/// - every [`Event::Begin`] is matched by an [`Event::End`],
+/// - [`Event::Script`]s and [`Event::Visual`]s are followed by the number of elements they
+///   expect,
+/// - [`EnvironmentFlow`] events only occur directly inside of a mathematical environment that
+///   allows them, [`EnvironmentFlow::StartLines`] only occurs as the first event of the
+///   environment, and rows contain at most one [`EnvironmentFlow::Tag`].
+///
+/// Errors from the underlying stream are forwarded as [`ValidationError::Event`]. Since the
+/// stream is usually cut short by an error, validation stops after the first error is
+/// forwarded, and the remaining events are forwarded unchecked.
+///
+/// This is useful to check the output of code which produces or transforms events, before
+/// giving them to a renderer such as [`push_mathml`].
+///
+/// [`Parser`]: crate::Parser
+/// [`push_mathml`]: crate::push_mathml
+///
+/// # Example
+///
+/// ```rust
+/// use pulldown_latex::event::{self, Content, Event, ValidationError, Violation, Visual};
+///
+/// let events = [
+///     Event::Visual(Visual::Fraction(None)),
+///     Event::Content(Content::Number("1".into())),
+/// ];
+/// let result = event::validate(events.into_iter().map(Ok::<_, std::convert::Infallible>))
+///     .collect::<Result<Vec<_>, _>>();
+///
+/// assert!(matches!(
+///     result,
+///     Err(ValidationError::Violation {
+///         index: 0,
+///         violation: Violation::MissingArguments,
+///     })
+/// ));
+/// ```
+pub fn validate<'a, I, E>(events: I) -> Validate<I::IntoIter>
+where
+    I: IntoIterator<Item = Result<Event<'a>, E>>,
+{
+    Validate {
+        inner: events.into_iter(),
+        index: 0,
+        stack: Vec::new(),
+        state: ValidationState::Checking {
+            environment_start: false,
+        },
+    }
+}
+
+/// The iterator returned by [`validate`].
+#[derive(Debug)]
+pub struct Validate<I> {
+    inner: I,
+    /// The index of the next event.
+    index: usize,
+    stack: Vec<Frame>,
+    state: ValidationState,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ValidationState {
+    Checking {
+        /// Whether the previous event began a mathematical environment.
+        environment_start: bool,
+    },
+    /// An error was forwarded, the rest of the stream is not checked.
+    Forwarding,
+    /// A violation was reported.
+    Done,
+}
+
+#[derive(Debug)]
+struct Frame {
+    /// The index of the event that opened the frame.
+    index: usize,
+    kind: FrameKind,
+}
+
+#[derive(Debug)]
+enum FrameKind {
+    Group,
+    Environment {
+        /// The number of alignments allowed in a row.
+        max_alignments: usize,
+        alignments: usize,
+        /// Whether the environment allows tags, labels and `\notag`.
+        tags: bool,
+        /// Whether the current row has a tag.
+        tagged: bool,
+    },
+    /// Elements are expected after a script or a visual.
+    Arguments {
+        remaining: usize,
+    },
+}
+
+impl<'a, I, E> Iterator for Validate<I>
+where
+    I: Iterator<Item = Result<Event<'a>, E>>,
+{
+    type Item = Result<Event<'a>, ValidationError<E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let environment_start = match self.state {
+            ValidationState::Checking { environment_start } => environment_start,
+            ValidationState::Forwarding => {
+                return self
+                    .inner
+                    .next()
+                    .map(|event| event.map_err(ValidationError::Event))
+            }
+            ValidationState::Done => return None,
+        };
+        let index = self.index;
+
+        let event = match self.inner.next() {
+            Some(Ok(event)) => event,
+            Some(Err(e)) => {
+                self.state = ValidationState::Forwarding;
+                return Some(Err(ValidationError::Event(e)));
+            }
+            None => {
+                let frame = self.stack.pop()?;
+                let violation = match frame.kind {
+                    FrameKind::Arguments { .. } => Violation::MissingArguments,
+                    _ => Violation::UnclosedGroup,
+                };
+                return Some(Err(self.violation(frame.index, violation)));
+            }
+        };
+        self.index += 1;
+        self.state = ValidationState::Checking {
+            environment_start: false,
+        };
+
+        if let Some(Frame {
+            index: arguments_index,
+            kind: FrameKind::Arguments { .. },
+        }) = self.stack.last()
+        {
+            if matches!(event, Event::End | Event::EnvironmentFlow(_)) {
+                return Some(Err(
+                    self.violation(*arguments_index, Violation::MissingArguments)
+                ));
+            }
+        }
+
+        match &event {
+            Event::Content(_)
+            | Event::Space { .. }
+            | Event::Rule { .. }
+            | Event::StateChange(_) => self.complete_element(),
+            Event::Begin(grouping) => {
+                let kind = match max_alignments(grouping) {
+                    Some(max_alignments) => {
+                        self.state = ValidationState::Checking {
+                            environment_start: true,
+                        };
+                        FrameKind::Environment {
+                            max_alignments,
+                            alignments: 0,
+                            tags: allows_tags(grouping),
+                            tagged: false,
+                        }
+                    }
+                    None => FrameKind::Group,
+                };
+                self.stack.push(Frame { index, kind });
+            }
+            Event::End => match self.stack.pop() {
+                Some(Frame {
+                    kind: FrameKind::Group | FrameKind::Environment { .. },
+                    ..
+                }) => self.complete_element(),
+                _ => return Some(Err(self.violation(index, Violation::UnexpectedEnd))),
+            },
+            Event::Visual(visual) => {
+                let remaining = match visual {
+                    Visual::SquareRoot
+                    | Visual::Negation
+                    | Visual::Framed(_)
+                    | Visual::Phantom { .. }
+                    | Visual::Smash { .. }
+                    | Visual::Overlap(_)
+                    | Visual::Raise(_)
+                    | Visual::HorizontalBox
+                    | Visual::VerticalCenter => 1,
+                    Visual::Root | Visual::Fraction(_) => 2,
+                    Visual::MathChoice => 4,
+                };
+                self.stack.push(Frame {
+                    index,
+                    kind: FrameKind::Arguments { remaining },
+                });
+            }
+            Event::Script { ty, .. } => {
+                let remaining = match ty {
+                    ScriptType::Subscript | ScriptType::Superscript => 2,
+                    ScriptType::SubSuperscript => 3,
+                };
+                self.stack.push(Frame {
+                    index,
+                    kind: FrameKind::Arguments { remaining },
+                });
+            }
+            Event::EnvironmentFlow(flow) => {
+                let Some(Frame {
+                    kind:
+                        FrameKind::Environment {
+                            max_alignments,
+                            alignments,
+                            tags,
+                            tagged,
+                        },
+                    ..
+                }) = self.stack.last_mut()
+                else {
+                    return Some(Err(self.violation(index, Violation::MisplacedFlow)));
+                };
+                match flow {
+                    EnvironmentFlow::Alignment if *alignments < *max_alignments => *alignments += 1,
+                    EnvironmentFlow::Alignment => {
+                        return Some(Err(self.violation(index, Violation::TooManyAlignments)))
+                    }
+                    EnvironmentFlow::NewLine { .. } => {
+                        *alignments = 0;
+                        *tagged = false;
+                    }
+                    EnvironmentFlow::StartLines { .. } if environment_start => {}
+                    EnvironmentFlow::StartLines { .. } => {
+                        return Some(Err(self.violation(index, Violation::MisplacedStartLines)))
+                    }
+                    EnvironmentFlow::Tag { .. }
+                    | EnvironmentFlow::NoNumber
+                    | EnvironmentFlow::Label(_)
+                        if !*tags =>
+                    {
+                        return Some(Err(self.violation(index, Violation::MisplacedTag)))
+                    }
+                    EnvironmentFlow::Tag { .. } if *tagged => {
+                        return Some(Err(self.violation(index, Violation::MultipleTags)))
+                    }
+                    EnvironmentFlow::Tag { .. } => *tagged = true,
+                    EnvironmentFlow::NoNumber | EnvironmentFlow::Label(_) => {}
+                }
+            }
+        }
+
+        Some(Ok(event))
+    }
+}
+
+impl<I> Validate<I> {
+    /// Mark the end of an element, completing the scripts and visuals that were waiting for it.
+    fn complete_element(&mut self) {
+        while let Some(Frame {
+            kind: FrameKind::Arguments { remaining },
+            ..
+        }) = self.stack.last_mut()
+        {
+            *remaining -= 1;
+            if *remaining > 0 {
+                break;
+            }
+            self.stack.pop();
+        }
+    }
+
+    fn violation<E>(&mut self, index: usize, violation: Violation) -> ValidationError<E> {
+        self.state = ValidationState::Done;
+        ValidationError::Violation { index, violation }
+    }
+}
+
+/// The number of alignments allowed in a row of the grouping, or `None` if the grouping is not a
+/// mathematical environment.
+pub(crate) fn max_alignments(grouping: &Grouping) -> Option<usize> {
+    Some(match grouping {
+        Grouping::Normal | Grouping::LeftRight(..) => return None,
+        Grouping::Array(columns) => columns
+            .iter()
+            .filter(|column| matches!(column, ArrayColumn::Column(_)))
+            .count()
+            .saturating_sub(1),
+        Grouping::Matrix { .. } | Grouping::Align { .. } | Grouping::Aligned => usize::MAX,
+        Grouping::Cases { .. } | Grouping::Split => 1,
+        Grouping::Alignat { pairs, .. } | Grouping::Alignedat { pairs } => {
+            (*pairs as usize * 2).saturating_sub(1)
+        }
+        Grouping::Equation { .. }
+        | Grouping::SubArray { .. }
+        | Grouping::Gather { .. }
+        | Grouping::Gathered
+        | Grouping::Multline => 0,
+    })
+}
+
+/// Whether the rows of the grouping can be tagged, labeled and unnumbered.
+pub(crate) fn allows_tags(grouping: &Grouping) -> bool {
+    matches!(
+        grouping,
+        Grouping::Equation { .. }
+            | Grouping::Align { .. }
+            | Grouping::Alignat { .. }
+            | Grouping::Gather { .. }
+            | Grouping::Multline
+    )
+}
+
+/// An error returned by the iterator of [`validate`].
+#[derive(Debug)]
+pub enum ValidationError<E> {
+    /// An error from the underlying stream of events.
+    Event(E),
+    /// The stream of events is not structurally valid.
+    Violation {
+        /// The index of the event at fault in the stream, starting at 0.
+        ///
+        /// For [`Violation::UnclosedGroup`] and [`Violation::MissingArguments`], this is the index
+        /// of the event that expected the closing of the group or the arguments.
+        index: usize,
+        /// The rule that was violated.
+        violation: Violation,
+    },
+}
+
+/// A structural rule of the event stream, which was violated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Violation {
+    /// An [`Event::End`] was found while no group was open.
+    UnexpectedEnd,
+    /// An [`Event::Begin`] was never closed by an [`Event::End`].
+    UnclosedGroup,
+    /// An [`Event::Script`] or [`Event::Visual`] is not followed by enough elements.
+    MissingArguments,
+    /// An [`Event::EnvironmentFlow`] was found outside of a mathematical environment.
+    MisplacedFlow,
+    /// An [`EnvironmentFlow::StartLines`] was found after the start of the environment.
+    MisplacedStartLines,
+    /// An [`EnvironmentFlow::Alignment`] was found in a row that already has as many alignments
+    /// as the environment allows.
+    TooManyAlignments,
+    /// An [`EnvironmentFlow::Tag`], [`EnvironmentFlow::NoNumber`] or [`EnvironmentFlow::Label`]
+    /// was found in an environment that does not allow equation numbers.
+    MisplacedTag,
+    /// An [`EnvironmentFlow::Tag`] was found in a row that is already tagged.
+    MultipleTags,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Violation::UnexpectedEnd => "end of group found while no group is open",
+            Violation::UnclosedGroup => "group is never closed",
+            Violation::MissingArguments => "script or visual is not followed by enough elements",
+            Violation::MisplacedFlow => {
+                "alignment or line break found outside of a mathematical environment"
+            }
+            Violation::MisplacedStartLines => {
+                "horizontal lines found after the start of the environment"
+            }
+            Violation::TooManyAlignments => "too many alignments in a row of the environment",
+            Violation::MisplacedTag => {
+                "tag, label or `\\notag` found in an environment without equation numbers"
+            }
+            Violation::MultipleTags => "multiple tags in a row of the environment",
+        })
+    }
+}
+
+impl<E: Display> Display for ValidationError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::Event(e) => e.fmt(f),
+            ValidationError::Violation { index, violation } => {
+                write!(f, "invalid event stream at event {index}: {violation}")
+            }
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for ValidationError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ValidationError::Event(e) => Some(e),
+            ValidationError::Violation { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use crate::{
+        event::{ColumnAlignment, Content, ScriptPosition},
+        Parser, Storage,
+    };
+
+    use super::*;
+
+    fn violation(events: Vec<Event<'static>>) -> Option<(usize, Violation)> {
+        validate(events.into_iter().map(Ok::<_, Infallible>)).find_map(|event| match event {
+            Err(ValidationError::Violation { index, violation }) => Some((index, violation)),
+            _ => None,
+        })
+    }
+
+    fn x() -> Event<'static> {
+        Event::Content(Content::Ordinary {
+            content: 'x',
+            stretchy: false,
+        })
+    }
+
+    #[test]
+    fn parser_output_is_valid() {
+        let storage = Storage::new();
+        for input in [
+            r"x^2_{\text{max}} + \frac{1}{\sqrt[3]{y}} \not= \sum_{n=0}^\infty a_n",
+            r"\left( \mathbb{R} \middle| \boldsymbol{\alpha} \right] \Bigl\{ \sin\theta \Bigr\}",
+            r"\begin{array}{|l:c|r} \hline 1 & 2 & 3 \\[2pt] \hline 4 & 5 & 6 \\ \end{array}",
+            r"\begin{darray}{cc} \hline a & b \end{darray}",
+            r"\begin{pmatrix} a & -b \\ c & d \end{pmatrix} \begin{dcases} 0 & x < 0 \end{dcases}",
+            r"\begin{alignat}{2} a &= b & c &= d \\ e &= f \end{alignat}",
+            r"\begin{equation} \begin{split} a &= b \\ &= c \end{split} \end{equation}",
+            r"\begin{align*} a &= b \tag{1} \label{eq} \\ c &= d \notag \tag*{$*$} \end{align*}",
+            r"\sum_{\substack{0 \le i \le m \\ 0 < j < n}} \overbrace{a + b}^{n} \kern1em \color{red} x",
+        ] {
+            let events = Parser::new(input, &storage);
+            if let Some(error) = validate(events).find_map(Result::err) {
+                panic!("input `{input}` is invalid: {error}");
+            }
+        }
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(
+            violation(vec![
+                Event::Begin(Grouping::Normal),
+                x(),
+                Event::End,
+                Event::End
+            ]),
+            Some((3, Violation::UnexpectedEnd))
+        );
+        assert_eq!(
+            violation(vec![
+                Event::Begin(Grouping::Normal),
+                Event::Begin(Grouping::Normal),
+                Event::End,
+            ]),
+            Some((0, Violation::UnclosedGroup))
+        );
+    }
+
+    #[test]
+    fn arguments() {
+        let script = Event::Script {
+            ty: ScriptType::SubSuperscript,
+            position: ScriptPosition::Right,
+        };
+        assert_eq!(
+            violation(vec![
+                script.clone(),
+                Event::Visual(Visual::Negation),
+                x(),
+                Event::Begin(Grouping::Normal),
+                Event::End,
+                x(),
+            ]),
+            None
+        );
+        assert_eq!(
+            violation(vec![x(), script.clone(), x(), x()]),
+            Some((1, Violation::MissingArguments))
+        );
+        assert_eq!(
+            violation(vec![
+                Event::Begin(Grouping::Normal),
+                Event::Visual(Visual::Fraction(None)),
+                x(),
+                Event::End,
+            ]),
+            Some((1, Violation::MissingArguments))
+        );
+        assert_eq!(
+            violation(vec![
+                Event::Begin(Grouping::Matrix {
+                    alignment: ColumnAlignment::Center
+                }),
+                script,
+                x(),
+                Event::EnvironmentFlow(EnvironmentFlow::Alignment),
+            ]),
+            Some((1, Violation::MissingArguments))
+        );
+    }
+
+    #[test]
+    fn environment_flow() {
+        let alignment = || Event::EnvironmentFlow(EnvironmentFlow::Alignment);
+        let new_line = || {
+            Event::EnvironmentFlow(EnvironmentFlow::NewLine {
+                spacing: None,
+                horizontal_lines: Box::new([]),
+            })
+        };
+        let start_lines = || {
+            Event::EnvironmentFlow(EnvironmentFlow::StartLines {
+                lines: Box::new([]),
+            })
+        };
+
+        assert_eq!(
+            violation(vec![x(), alignment()]),
+            Some((1, Violation::MisplacedFlow))
+        );
+        assert_eq!(
+            violation(vec![
+                Event::Begin(Grouping::Cases { left: true }),
+                Event::Begin(Grouping::Normal),
+                new_line(),
+            ]),
+            Some((2, Violation::MisplacedFlow))
+        );
+        assert_eq!(
+            violation(vec![
+                Event::Begin(Grouping::Cases { left: true }),
+                alignment(),
+                new_line(),
+                alignment(),
+                alignment(),
+                Event::End,
+            ]),
+            Some((4, Violation::TooManyAlignments))
+        );
+        assert_eq!(
+            violation(vec![
+                Event::Begin(Grouping::Array(Box::new([
+                    ArrayColumn::Column(ColumnAlignment::Left),
+                    ArrayColumn::Separator(crate::event::Line::Solid),
+                    ArrayColumn::Column(ColumnAlignment::Right),
+                ]))),
+                start_lines(),
+                alignment(),
+                alignment(),
+            ]),
+            Some((3, Violation::TooManyAlignments))
+        );
+        assert_eq!(
+            violation(vec![Event::Begin(Grouping::Gathered), x(), start_lines()]),
+            Some((2, Violation::MisplacedStartLines))
+        );
+    }
+
+    #[test]
+    fn tags() {
+        let tag = || {
+            Event::EnvironmentFlow(EnvironmentFlow::Tag {
+                tag: "1".into(),
+                parenthesized: true,
+            })
+        };
+        let new_line = Event::EnvironmentFlow(EnvironmentFlow::NewLine {
+            spacing: None,
+            horizontal_lines: Box::new([]),
+        });
+
+        assert_eq!(
+            violation(vec![
+                Event::Begin(Grouping::Gather { eq_numbers: false }),
+                tag(),
+                Event::EnvironmentFlow(EnvironmentFlow::Label("eq".into())),
+                new_line,
+                tag(),
+                Event::EnvironmentFlow(EnvironmentFlow::NoNumber),
+                Event::End,
+            ]),
+            None
+        );
+        assert_eq!(
+            violation(vec![
+                Event::Begin(Grouping::Equation { eq_numbers: true }),
+                tag(),
+                x(),
+                tag(),
+            ]),
+            Some((3, Violation::MultipleTags))
+        );
+        assert_eq!(
+            violation(vec![
+                Event::Begin(Grouping::Gathered),
+                Event::EnvironmentFlow(EnvironmentFlow::NoNumber),
+            ]),
+            Some((1, Violation::MisplacedTag))
+        );
+    }
+
+    #[test]
+    fn errors_stop_validation() {
+        let events: Vec<Result<Event<'static>, &str>> = vec![
+            Ok(Event::Begin(Grouping::Normal)),
+            Err("error"),
+            Ok(Event::End),
+            Ok(Event::End),
+        ];
+        let validated = validate(events).collect::<Vec<_>>();
+        assert_eq!(validated.len(), 4);
+        assert!(matches!(validated[1], Err(ValidationError::Event("error"))));
+        assert!(matches!(validated[3], Ok(Event::End)));
+    }
+}
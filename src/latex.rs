@@ -0,0 +1,1719 @@
+//! A `LaTeX` writer, serializing a stream of [`Event`]s back into `LaTeX` source.
+//!
+//! This is the inverse of the [`Parser`], available through the [`push_latex`] and
+//! [`write_latex`] functions. The output parses back into the same events, and is canonical:
+//! inputs producing the same events are written the same way. This makes it suitable to normalize
+//! user input before storing or comparing it, or to get `LaTeX` back after transforming events.
+//!
+//! Some events cannot be produced by the parser in the way they are arranged, e.g., a font
+//! without a corresponding switch command, or a text font that cannot be reached from the
+//! surrounding one. The writer approximates those as best it can, and the output then does not
+//! parse back into the exact same events.
+//!
+//! # Example
+//!
+//! ```rust
+//! use pulldown_latex::{push_latex, Parser, Storage};
+//!
+//! let storage = Storage::new();
+//! let parser = Parser::new(r"\frac {1} {2}+\alpha^{ 2 } \mathbf x", &storage);
+//! let mut latex = String::new();
+//! push_latex(&mut latex, parser).unwrap();
+//!
+//! assert_eq!(latex, r"\frac{1}{2}+\alpha^{2}\mathbf{x}");
+//! ```
+//!
+//! [`Parser`]: crate::Parser
+
+mod tables;
+
+use std::{borrow::Cow, io};
+
+use crate::{
+    event::{
+        self, ArrayColumn, ColorChange, ColorTarget, ColumnAlignment, Content, DelimiterSize,
+        DelimiterType, Dimension, DimensionUnit, EnvironmentFlow, Event, Font, Grouping,
+        GroupingKind, Line, MatrixType, ScriptPosition, ScriptType, StateChange, Style, Visual,
+    },
+    parser::{bold, char_event, italic, upright},
+};
+
+/// What was last written, to know whether a separator is needed before the next output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Last {
+    /// A control word, which would absorb a following letter.
+    ControlWord,
+    /// A number, which would absorb a following digit or decimal separator.
+    Number,
+    /// A new line without spacing, which would take a following `[` as its spacing.
+    NewLine,
+    Other,
+}
+
+/// A unit of work of the writer, which works with an explicit stack so that stack usage does not
+/// depend on the nesting of the input.
+#[derive(Debug, Clone)]
+enum Task {
+    /// Write the elements in the range, in math mode.
+    Math(usize, usize),
+    /// Write the elements in the range, in text mode with the given font.
+    Text(usize, usize, Option<Font>),
+    /// Write the element at the index.
+    Element(usize),
+    /// Write the element at the index so that it is parsed as a single argument.
+    Argument(usize),
+    /// Write the delimiter of a `\left` or `\right` command.
+    Delimiter(Option<char>),
+    /// Write a control sequence.
+    ControlSequence(&'static str),
+    /// Write a string as is.
+    Str(Cow<'static, str>),
+}
+
+/// How a script is written.
+#[derive(Debug, Clone, Copy)]
+enum ScriptForm {
+    /// An accent command taking the base as argument, e.g., `\hat{x}`.
+    Accent(&'static str),
+    /// `\overset{superscript}{base}`.
+    Overset,
+    /// `\underset{subscript}{base}`.
+    Underset,
+    /// An extensible arrow, e.g., `\xrightarrow[subscript]{superscript}`.
+    Arrow(&'static str),
+    /// The base followed by `_` and `^`.
+    Attached,
+}
+
+struct LatexWriter<'e, 'a, W> {
+    writer: W,
+    events: &'e [Event<'a>],
+    /// The index one past the end of the element starting at each index.
+    ends: Vec<usize>,
+    stack: Vec<Task>,
+    last: Last,
+}
+
+impl<'e, 'a, W: io::Write> LatexWriter<'e, 'a, W> {
+    fn new(writer: W, events: &'e [Event<'a>]) -> Self {
+        Self {
+            writer,
+            events,
+            ends: element_ends(events),
+            stack: Vec::new(),
+            last: Last::Other,
+        }
+    }
+
+    fn write(mut self) -> io::Result<()> {
+        self.stack.push(Task::Math(0, self.events.len()));
+        while let Some(task) = self.stack.pop() {
+            match task {
+                Task::Math(start, end) => self.math(start, end)?,
+                Task::Text(start, end, font) => {
+                    if start < end {
+                        self.stack.push(Task::Text(self.ends[start], end, font));
+                        self.text_element(start, font)?;
+                    }
+                }
+                Task::Element(index) => self.element(index)?,
+                Task::Argument(index) => self.argument(index)?,
+                Task::Delimiter(delimiter) => self.fence(delimiter)?,
+                Task::ControlSequence(cs) => self.control_sequence(cs)?,
+                Task::Str(s) => self.str(&s)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Schedule the tasks to run in order, before the tasks already scheduled.
+    fn then<const N: usize>(&mut self, tasks: [Task; N]) {
+        self.stack.extend(tasks.into_iter().rev());
+    }
+
+    fn str(&mut self, s: &str) -> io::Result<()> {
+        let separate = match (self.last, s.chars().next()) {
+            (Last::ControlWord, Some(c)) => c.is_ascii_alphabetic(),
+            (Last::Number, Some(c)) => matches!(c, '0'..='9' | '.' | ','),
+            (Last::NewLine, Some('[')) => {
+                self.writer.write_all(b"\\relax")?;
+                false
+            }
+            _ => false,
+        };
+        if separate {
+            self.writer.write_all(b" ")?;
+        }
+        if !s.is_empty() {
+            self.last = Last::Other;
+        }
+        self.writer.write_all(s.as_bytes())
+    }
+
+    fn control_sequence(&mut self, cs: &str) -> io::Result<()> {
+        self.str("\\")?;
+        self.writer.write_all(cs.as_bytes())?;
+        if cs.bytes().all(|b| b.is_ascii_alphabetic()) {
+            self.last = Last::ControlWord;
+        }
+        Ok(())
+    }
+
+    fn number(&mut self, number: &str) -> io::Result<()> {
+        self.str(number)?;
+        self.last = Last::Number;
+        Ok(())
+    }
+
+    fn char(&mut self, c: char) -> io::Result<()> {
+        self.str(c.encode_utf8(&mut [0; 4]))
+    }
+
+    /// Write a braced argument, e.g., a color or a dimension.
+    fn braced(&mut self, s: &str) -> io::Result<()> {
+        self.str("{")?;
+        self.str(s)?;
+        self.str("}")
+    }
+
+    fn math(&mut self, start: usize, end: usize) -> io::Result<()> {
+        if start >= end {
+            return Ok(());
+        }
+        // Fonts without a switch command are applied to the rest of the group instead.
+        if let Event::StateChange(StateChange::Font(font)) = self.events[start] {
+            if font.and_then(font_switch).is_none() {
+                self.control_sequence(math_font_command(font))?;
+                self.str("{")?;
+                self.then([Task::Math(start + 1, end), Task::Str("}".into())]);
+                return Ok(());
+            }
+        }
+        self.stack.push(Task::Math(self.ends[start], end));
+        self.element(start)
+    }
+
+    fn element(&mut self, index: usize) -> io::Result<()> {
+        let events = self.events;
+        match &events[index] {
+            Event::Content(content) => self.content(content),
+            Event::Begin(grouping) => self.group(index, grouping),
+            Event::End => Ok(()),
+            Event::Script { ty, position } => self.script(index, *ty, *position),
+            Event::Visual(visual) => self.visual(index, *visual),
+            Event::Space {
+                width,
+                height,
+                depth,
+            } => self.space(*width, *height, *depth),
+            Event::Rule {
+                width,
+                height,
+                depth,
+            } => self.rule(None, *width, *height, *depth),
+            Event::StateChange(state_change) => self.state_change(*state_change),
+            Event::EnvironmentFlow(flow) => self.environment_flow(flow),
+        }
+    }
+
+    fn argument(&mut self, index: usize) -> io::Result<()> {
+        let atomic = match &self.events[index] {
+            Event::Content(Content::Number(number)) => number.chars().count() == 1,
+            Event::Content(Content::Error { .. }) => false,
+            Event::Script { .. } => !matches!(self.script_form(index), ScriptForm::Attached),
+            _ => true,
+        };
+        if atomic {
+            self.element(index)
+        } else {
+            self.str("{")?;
+            self.then([Task::Element(index), Task::Str("}".into())]);
+            Ok(())
+        }
+    }
+
+    fn content(&mut self, content: &Content) -> io::Result<()> {
+        match content {
+            Content::Text(text) if text == "&nbsp;" => self.str("~"),
+            Content::Text(text) => {
+                self.control_sequence("text")?;
+                self.str("{")?;
+                self.text(text, None)?;
+                self.str("}")
+            }
+            Content::Number(number) => self.number(number),
+            Content::Function(name) => match tables::function(name) {
+                Some((cs, _)) => self.control_sequence(cs),
+                None => {
+                    self.control_sequence("operatorname")?;
+                    self.braced(&operator_name(name))
+                }
+            },
+            Content::LargeOp {
+                content: '∫',
+                small: true,
+            } => self.control_sequence("smallint"),
+            Content::LargeOp { content, .. } => match tables::large_op(*content, false)
+                .or_else(|| tables::large_op(*content, true))
+            {
+                Some(cs) => self.control_sequence(cs),
+                None => self.atom("mathop", *content),
+            },
+            Content::BinaryOp { content: c, .. } => {
+                self.symbol(content, *c, tables::binary(*c), "mathbin")
+            }
+            Content::Relation {
+                content: relation,
+                small,
+            } => match relation.chars() {
+                (c, None) if *small && tables::small_relation(c).is_some() => {
+                    self.control_sequence(tables::small_relation(c).unwrap())
+                }
+                (c, None) => self.symbol(content, c, tables::relation(c), "mathrel"),
+                (first, Some(second)) => match tables::multirelation(first, second) {
+                    Some(cs) => self.control_sequence(cs),
+                    None => {
+                        self.control_sequence("mathrel")?;
+                        self.str("{")?;
+                        self.char(first)?;
+                        self.char(second)?;
+                        self.str("}")
+                    }
+                },
+            },
+            Content::Ordinary { content: c, .. } => {
+                self.symbol(content, *c, tables::ordinary(*c), "mathord")
+            }
+            Content::Delimiter {
+                content: c,
+                size: None,
+                ty,
+            } => {
+                let class = match ty {
+                    DelimiterType::Open => "mathopen",
+                    DelimiterType::Close => "mathclose",
+                    DelimiterType::Fence => "mathord",
+                };
+                self.symbol(content, *c, tables::math_delimiter(*c, *ty), class)
+            }
+            Content::Delimiter {
+                content,
+                size: Some(size),
+                ty,
+            } => self.sized_delimiter(*content, *size, *ty),
+            Content::Punctuation(c) => self.symbol(content, *c, None, "mathpunct"),
+            Content::Reference {
+                label,
+                parenthesized,
+                ..
+            } => {
+                self.control_sequence(if *parenthesized { "eqref" } else { "ref" })?;
+                self.braced(label)
+            }
+            Content::Error { source, .. } => self.str(source),
+        }
+    }
+
+    /// Write a character in the way that is most natural to type: the character itself if it is
+    /// ASCII, then the control sequence producing it, then the character itself, and finally the
+    /// character in an atom command of the given class.
+    fn symbol(
+        &mut self,
+        content: &Content,
+        c: char,
+        control_sequence: Option<&str>,
+        class: &str,
+    ) -> io::Result<()> {
+        let produces =
+            |c: char| matches!(char_event(c), Event::Content(ref produced) if produced == content);
+        let ascii = match c {
+            '−' => '-',
+            '∗' => '*',
+            c => c,
+        };
+        if is_plain_char(ascii) && ascii.is_ascii() && produces(ascii) {
+            return self.char(ascii);
+        }
+        if let Some(cs) = control_sequence {
+            return self.control_sequence(cs);
+        }
+        if is_plain_char(c) && produces(c) {
+            return self.char(c);
+        }
+        match content {
+            Content::Ordinary { .. } if !is_plain_char(c) && (c as u32) < 256 => {
+                self.control_sequence("char")?;
+                self.number(&(c as u32).to_string())
+            }
+            _ => self.atom(class, c),
+        }
+    }
+
+    fn atom(&mut self, class: &str, c: char) -> io::Result<()> {
+        self.control_sequence(class)?;
+        self.str("{")?;
+        self.char(c)?;
+        self.str("}")
+    }
+
+    fn sized_delimiter(
+        &mut self,
+        c: char,
+        size: DelimiterSize,
+        ty: DelimiterType,
+    ) -> io::Result<()> {
+        let cs = match (size, ty) {
+            (DelimiterSize::Big, DelimiterType::Open) => "bigl",
+            (DelimiterSize::Big, DelimiterType::Close) => "bigr",
+            (DelimiterSize::Big, DelimiterType::Fence) => "bigm",
+            (DelimiterSize::BIG, DelimiterType::Open) => "Bigl",
+            (DelimiterSize::BIG, DelimiterType::Close) => "Bigr",
+            (DelimiterSize::BIG, DelimiterType::Fence) => "Bigm",
+            (DelimiterSize::Bigg, DelimiterType::Open) => "biggl",
+            (DelimiterSize::Bigg, DelimiterType::Close) => "biggr",
+            (DelimiterSize::Bigg, DelimiterType::Fence) => "biggm",
+            (DelimiterSize::BIGG, DelimiterType::Open) => "Biggl",
+            (DelimiterSize::BIGG, DelimiterType::Close) => "Biggr",
+            (DelimiterSize::BIGG, DelimiterType::Fence) => "Biggm",
+        };
+        // The type of the delimiter is given by the delimiter token, except for `\middle` which
+        // always makes a fence.
+        if delimiter_token(c, ty).is_none()
+            && size == DelimiterSize::Big
+            && ty == DelimiterType::Fence
+        {
+            self.control_sequence("middle")?;
+        } else {
+            self.control_sequence(cs)?;
+        }
+        self.delimiter(c, ty)
+    }
+
+    /// Write a delimiter token, preferably of the given type.
+    fn delimiter(&mut self, c: char, ty: DelimiterType) -> io::Result<()> {
+        let token = delimiter_token(c, ty)
+            .or_else(|| delimiter_token(c, DelimiterType::Open))
+            .or_else(|| delimiter_token(c, DelimiterType::Close))
+            .or_else(|| delimiter_token(c, DelimiterType::Fence));
+        match token {
+            Some(DelimiterToken::Char) => self.char(c),
+            Some(DelimiterToken::ControlSequence(cs)) => self.control_sequence(cs),
+            // Not a delimiter, the closest is a null delimiter.
+            None => self.str("."),
+        }
+    }
+
+    fn fence(&mut self, delimiter: Option<char>) -> io::Result<()> {
+        match delimiter {
+            Some(c) => self.delimiter(c, DelimiterType::Fence),
+            None => self.str("."),
+        }
+    }
+
+    fn group(&mut self, index: usize, grouping: &Grouping) -> io::Result<()> {
+        let events = self.events;
+        let end = self.ends[index] - 1;
+        match grouping {
+            Grouping::Normal => self.normal_group(index, end),
+            Grouping::LeftRight(left, right) => {
+                if let (Some(left), Some(right)) = (left, right) {
+                    let matrix = matches!(events[index + 1], Event::Begin(Grouping::Matrix { .. }))
+                        && self.ends[index + 1] == end;
+                    if let (true, Some(ty)) = (matrix, matrix_type(*left, *right)) {
+                        return self.environment(index + 1, Some(ty));
+                    }
+                }
+                if left.is_some() || right.is_some() {
+                    if let Some(fraction) = self.fraction_in(index + 1, end) {
+                        return self.fraction_like(fraction, *left, *right);
+                    }
+                }
+                self.control_sequence("left")?;
+                self.fence(*left)?;
+                self.then([
+                    Task::Math(index + 1, end),
+                    Task::ControlSequence("right"),
+                    Task::Delimiter(*right),
+                ]);
+                Ok(())
+            }
+            _ => self.environment(index, None),
+        }
+    }
+
+    fn normal_group(&mut self, index: usize, end: usize) -> io::Result<()> {
+        let events = self.events;
+        let start = index + 1;
+        let state_change = match events.get(start) {
+            Some(Event::StateChange(state_change)) if start < end => *state_change,
+            _ => {
+                self.str("{")?;
+                self.then([Task::Math(start, end), Task::Str("}".into())]);
+                return Ok(());
+            }
+        };
+
+        match state_change {
+            StateChange::Font(font) => {
+                match math_text_font_command(font) {
+                    Some(cs) if self.is_text(start + 1, end) => {
+                        self.control_sequence(cs)?;
+                        self.str("{")?;
+                        self.then([Task::Text(start + 1, end, font), Task::Str("}".into())]);
+                    }
+                    _ => {
+                        self.control_sequence(math_font_command(font))?;
+                        self.str("{")?;
+                        self.then([Task::Math(start + 1, end), Task::Str("}".into())]);
+                    }
+                }
+                return Ok(());
+            }
+            StateChange::Color(ColorChange {
+                color,
+                target: ColorTarget::Text,
+            }) if self.ends[start + 1] == end => {
+                self.control_sequence("textcolor")?;
+                self.braced(&hex_color(color))?;
+                self.then([Task::Argument(start + 1)]);
+                return Ok(());
+            }
+            StateChange::Color(ColorChange {
+                color,
+                target: ColorTarget::Background,
+            }) => {
+                self.control_sequence("colorbox")?;
+                self.braced(&hex_color(color))?;
+                self.str("{")?;
+                self.then([Task::Text(start + 1, end, None), Task::Str("}".into())]);
+                return Ok(());
+            }
+            StateChange::Color(ColorChange {
+                color,
+                target: ColorTarget::Border,
+            }) => {
+                if let Some(Event::StateChange(StateChange::Color(ColorChange {
+                    color: background,
+                    target: ColorTarget::Background,
+                }))) = events.get(start + 1)
+                {
+                    self.control_sequence("fcolorbox")?;
+                    self.braced(&hex_color(color))?;
+                    self.braced(&hex_color(*background))?;
+                    self.str("{")?;
+                    self.then([Task::Text(start + 2, end, None), Task::Str("}".into())]);
+                    return Ok(());
+                }
+            }
+            StateChange::Style(_) => {
+                if let Some(fraction) = self.fraction_in(start, end) {
+                    return self.fraction_like(fraction, None, None);
+                }
+            }
+            StateChange::Color(_) => {}
+        }
+
+        self.str("{")?;
+        self.then([Task::Math(start, end), Task::Str("}".into())]);
+        Ok(())
+    }
+
+    /// Returns the style and the index of the fraction, if the range only contains a fraction
+    /// optionally preceded by a style change.
+    fn fraction_in(&self, start: usize, end: usize) -> Option<(Option<Style>, usize)> {
+        let (style, fraction) = match self.events.get(start) {
+            Some(Event::StateChange(StateChange::Style(style))) => (Some(*style), start + 1),
+            _ => (None, start),
+        };
+        (fraction < end
+            && matches!(self.events[fraction], Event::Visual(Visual::Fraction(_)))
+            && self.ends[fraction] == end)
+            .then_some((style, fraction))
+    }
+
+    /// Write a fraction with a style or delimiters, as produced by `\dfrac`, `\binom`, or
+    /// `\genfrac`.
+    fn fraction_like(
+        &mut self,
+        (style, fraction): (Option<Style>, usize),
+        left: Option<char>,
+        right: Option<char>,
+    ) -> io::Result<()> {
+        let Event::Visual(Visual::Fraction(bar)) = self.events[fraction] else {
+            unreachable!("the index points to a fraction");
+        };
+        let binom = left == Some('(')
+            && right == Some(')')
+            && bar == Some(Dimension::new(0., DimensionUnit::Em));
+        let cs = match (left.is_some() || right.is_some(), binom, style, bar) {
+            (false, _, Some(Style::Display), None) => Some("dfrac"),
+            (false, _, Some(Style::Text), None) => Some("tfrac"),
+            (true, true, None, _) => Some("binom"),
+            (true, true, Some(Style::Display), _) => Some("dbinom"),
+            (true, true, Some(Style::Text), _) => Some("tbinom"),
+            _ => None,
+        };
+        match cs {
+            Some(cs) => self.control_sequence(cs)?,
+            None => {
+                self.control_sequence("genfrac")?;
+                for delimiter in [left, right] {
+                    match delimiter {
+                        Some(c) => self.delimiter(c, DelimiterType::Fence)?,
+                        None => self.str("{}")?,
+                    }
+                }
+                self.braced(&bar.map(dimension).unwrap_or_default())?;
+                self.braced(match style {
+                    Some(Style::Display) => "0",
+                    Some(Style::Text) => "1",
+                    Some(Style::Script) => "2",
+                    Some(Style::ScriptScript) => "3",
+                    None => "",
+                })?;
+            }
+        }
+        let denominator = self.ends[fraction + 1];
+        self.then([Task::Argument(fraction + 1), Task::Argument(denominator)]);
+        Ok(())
+    }
+
+    /// Write a mathematical environment, possibly wrapped in delimiters.
+    fn environment(&mut self, index: usize, wrap: Option<MatrixType>) -> io::Result<()> {
+        let events = self.events;
+        let Event::Begin(grouping) = &events[index] else {
+            unreachable!("the index points to an environment");
+        };
+        let end = self.ends[index] - 1;
+        let mut start = index + 1;
+
+        let mut lines: &[Line] = &[];
+        if let Some(Event::EnvironmentFlow(EnvironmentFlow::StartLines { lines: start_lines })) =
+            events.get(start).filter(|_| start < end)
+        {
+            lines = start_lines;
+            start += 1;
+        }
+        let style = match events.get(start) {
+            Some(Event::StateChange(StateChange::Style(style))) if start < end => Some(*style),
+            _ => None,
+        };
+        let display = style == Some(Style::Display);
+        let kind = match grouping {
+            Grouping::Array(_) => GroupingKind::Array { display },
+            Grouping::Matrix { alignment } => GroupingKind::Matrix {
+                ty: wrap.unwrap_or(if style == Some(Style::Text) {
+                    MatrixType::Small
+                } else {
+                    MatrixType::Normal
+                }),
+                column_spec: *alignment != ColumnAlignment::Center,
+            },
+            Grouping::Cases { left } => GroupingKind::Cases {
+                left: *left,
+                display,
+            },
+            Grouping::Equation { eq_numbers } => GroupingKind::Equation {
+                eq_numbers: *eq_numbers,
+            },
+            Grouping::Align { eq_numbers } => GroupingKind::Align {
+                eq_numbers: *eq_numbers,
+            },
+            Grouping::Aligned => GroupingKind::Aligned,
+            Grouping::SubArray { .. } => GroupingKind::SubArray,
+            Grouping::Alignat { eq_numbers, .. } => GroupingKind::Alignat {
+                eq_numbers: *eq_numbers,
+            },
+            Grouping::Alignedat { .. } => GroupingKind::Alignedat,
+            Grouping::Gather { eq_numbers } => GroupingKind::Gather {
+                eq_numbers: *eq_numbers,
+            },
+            Grouping::Gathered => GroupingKind::Gathered,
+            Grouping::Multline => GroupingKind::Multline,
+            Grouping::Split => GroupingKind::Split,
+            Grouping::Normal | Grouping::LeftRight(..) => {
+                unreachable!("the index points to an environment")
+            }
+        };
+        // The style change is part of the environment.
+        if matches!(
+            kind,
+            GroupingKind::Array { display: true }
+                | GroupingKind::Cases { display: true, .. }
+                | GroupingKind::Matrix {
+                    ty: MatrixType::Small,
+                    ..
+                }
+        ) {
+            start += 1;
+        }
+
+        self.str(&kind.opening_str())?;
+        match grouping {
+            Grouping::Array(columns) => {
+                let columns: String = columns
+                    .iter()
+                    .map(|column| match column {
+                        ArrayColumn::Column(alignment) => alignment_char(*alignment),
+                        ArrayColumn::Separator(Line::Solid) => '|',
+                        ArrayColumn::Separator(Line::Dashed) => ':',
+                    })
+                    .collect();
+                self.braced(&columns)?;
+            }
+            Grouping::Matrix { alignment } if *alignment != ColumnAlignment::Center => {
+                self.str("[")?;
+                self.char(alignment_char(*alignment))?;
+                self.str("]")?;
+            }
+            Grouping::SubArray { alignment } => {
+                self.braced(alignment_char(*alignment).encode_utf8(&mut [0; 4]))?;
+            }
+            Grouping::Alignat { pairs, .. } | Grouping::Alignedat { pairs } => {
+                self.braced(&pairs.to_string())?;
+            }
+            _ => {}
+        }
+        self.lines(lines)?;
+
+        self.then([Task::Math(start, end), Task::Str(kind.closing_str())]);
+        Ok(())
+    }
+
+    fn lines(&mut self, lines: &[Line]) -> io::Result<()> {
+        for line in lines {
+            self.control_sequence(match line {
+                Line::Solid => "hline",
+                Line::Dashed => "hdashline",
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Returns how the script at the index is written.
+    fn script_form(&self, index: usize) -> ScriptForm {
+        let events = self.events;
+        let Event::Script { ty, position } = events[index] else {
+            unreachable!("the index points to a script");
+        };
+        let base = index + 1;
+        let script = self.ends[base];
+        if position != ScriptPosition::AboveBelow {
+            return ScriptForm::Attached;
+        }
+        let accent = match (ty, &events[script]) {
+            (ScriptType::Superscript, Event::Content(Content::Ordinary { content, stretchy })) => {
+                tables::accent(*content, *stretchy)
+            }
+            (
+                ScriptType::Subscript,
+                Event::Content(Content::Ordinary {
+                    content,
+                    stretchy: true,
+                }),
+            ) => tables::underscript(*content),
+            _ => None,
+        };
+        if let Some((cs, _)) = accent {
+            return ScriptForm::Accent(cs);
+        }
+        if self.takes_limits(base) || self.is_above_below_accent(base) {
+            return ScriptForm::Attached;
+        }
+        match ty {
+            ScriptType::Superscript => ScriptForm::Overset,
+            ScriptType::Subscript => ScriptForm::Underset,
+            ScriptType::SubSuperscript => match (&events[base], &events[script]) {
+                (
+                    Event::Content(Content::Relation {
+                        content,
+                        small: false,
+                    }),
+                    Event::Begin(Grouping::Normal),
+                ) => match content.chars() {
+                    ('→', None) => ScriptForm::Arrow("xrightarrow"),
+                    ('←', None) => ScriptForm::Arrow("xleftarrow"),
+                    _ => ScriptForm::Attached,
+                },
+                _ => ScriptForm::Attached,
+            },
+        }
+    }
+
+    /// Whether the element at the index accepts `\limits` and `\nolimits`.
+    fn takes_limits(&self, index: usize) -> bool {
+        matches!(
+            self.events[index],
+            Event::Content(
+                Content::LargeOp { small: false, .. }
+                    | Content::LargeOp {
+                        content: '∫',
+                        small: true
+                    }
+                    | Content::Function(_)
+            )
+        )
+    }
+
+    /// Whether the element at the index is an accent which places scripts above and below it,
+    /// e.g., `\overbrace`.
+    fn is_above_below_accent(&self, index: usize) -> bool {
+        let ScriptForm::Accent(cs) = (match self.events[index] {
+            Event::Script { .. } => self.script_form(index),
+            _ => return false,
+        }) else {
+            return false;
+        };
+        matches!(
+            cs,
+            "overgroup"
+                | "undergroup"
+                | "overbrace"
+                | "underbrace"
+                | "underparen"
+                | "overbracket"
+                | "underbracket"
+        )
+    }
+
+    fn script(&mut self, index: usize, ty: ScriptType, position: ScriptPosition) -> io::Result<()> {
+        let base = index + 1;
+        let first = self.ends[base];
+        let (subscript, superscript) = match ty {
+            ScriptType::Subscript => (Some(first), None),
+            ScriptType::Superscript => (None, Some(first)),
+            ScriptType::SubSuperscript => (Some(first), Some(self.ends[first])),
+        };
+
+        match self.script_form(index) {
+            ScriptForm::Accent(cs) => {
+                self.control_sequence(cs)?;
+                self.then([Task::Argument(base)]);
+                return Ok(());
+            }
+            ScriptForm::Overset => {
+                self.control_sequence("overset")?;
+                self.then([Task::Argument(first), Task::Argument(base)]);
+                return Ok(());
+            }
+            ScriptForm::Underset => {
+                self.control_sequence("underset")?;
+                self.then([Task::Argument(first), Task::Argument(base)]);
+                return Ok(());
+            }
+            ScriptForm::Arrow(cs) => {
+                let end = self.ends[first] - 1;
+                self.control_sequence(cs)?;
+                self.str("[")?;
+                self.then([
+                    Task::Math(first + 1, end),
+                    Task::Str("]".into()),
+                    Task::Argument(end + 1),
+                ]);
+                return Ok(());
+            }
+            ScriptForm::Attached => {}
+        }
+
+        let tasks = [
+            subscript.map(|_| Task::Str("_".into())),
+            subscript.map(Task::Argument),
+            superscript.map(|_| Task::Str("^".into())),
+            superscript.map(Task::Argument),
+        ];
+        self.stack.extend(tasks.into_iter().rev().flatten());
+
+        if self.takes_limits(base) {
+            return self.operator(base, position);
+        }
+        match self.events[base] {
+            Event::Script { .. } if matches!(self.script_form(base), ScriptForm::Attached) => {
+                self.str("{")?;
+                self.then([Task::Element(base), Task::Str("}".into())]);
+                Ok(())
+            }
+            _ => self.element(base),
+        }
+    }
+
+    /// Write an operator that is the base of a script, along with the limit modifier needed for
+    /// the scripts to be placed in the given position.
+    fn operator(&mut self, index: usize, position: ScriptPosition) -> io::Result<()> {
+        // The natural position of the scripts of the written operator.
+        let natural = match &self.events[index] {
+            Event::Content(Content::LargeOp {
+                content: '∫',
+                small: true,
+            }) => {
+                self.control_sequence("smallint")?;
+                ScriptPosition::Right
+            }
+            Event::Content(Content::LargeOp { content, .. }) => {
+                let movable = tables::large_op(*content, true);
+                let fixed = tables::large_op(*content, false);
+                match (position, fixed, movable) {
+                    (ScriptPosition::Right, Some(cs), _) | (_, None, Some(cs)) => {
+                        self.control_sequence(cs)?;
+                        if movable.is_some() {
+                            ScriptPosition::Movable
+                        } else {
+                            ScriptPosition::Right
+                        }
+                    }
+                    (_, Some(cs), _) => {
+                        self.control_sequence(cs)?;
+                        ScriptPosition::Right
+                    }
+                    (_, None, None) => {
+                        self.atom("mathop", *content)?;
+                        ScriptPosition::Movable
+                    }
+                }
+            }
+            Event::Content(Content::Function(name)) => match tables::function(name) {
+                Some((cs, movable)) if movable || position == ScriptPosition::Right => {
+                    self.control_sequence(cs)?;
+                    if movable {
+                        ScriptPosition::Movable
+                    } else {
+                        return Ok(());
+                    }
+                }
+                _ if position == ScriptPosition::Movable => {
+                    self.control_sequence("operatorname")?;
+                    self.str("*")?;
+                    self.braced(&operator_name(name))?;
+                    ScriptPosition::Movable
+                }
+                _ => {
+                    self.control_sequence("operatorname")?;
+                    self.braced(&operator_name(name))?;
+                    ScriptPosition::Right
+                }
+            },
+            _ => unreachable!("the index points to an operator"),
+        };
+
+        match (position, natural) {
+            (ScriptPosition::AboveBelow, _) => self.control_sequence("limits"),
+            (ScriptPosition::Right, ScriptPosition::Movable) => self.control_sequence("nolimits"),
+            (ScriptPosition::Movable, ScriptPosition::Right) => {
+                self.control_sequence("displaylimits")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn visual(&mut self, index: usize, visual: Visual) -> io::Result<()> {
+        let events = self.events;
+        let first = index + 1;
+        match visual {
+            Visual::SquareRoot => {
+                if events[first]
+                    == (Event::Space {
+                        width: Some(Dimension::new(0., DimensionUnit::Em)),
+                        height: Some(Dimension::new(0.7, DimensionUnit::Em)),
+                        depth: None,
+                    })
+                {
+                    return self.control_sequence("surd");
+                }
+                self.control_sequence("sqrt")?;
+                self.then([Task::Argument(first)]);
+            }
+            Visual::Root => {
+                // The index comes after the radicand in the events, but is written first.
+                let index = self.ends[first];
+                let index_task = match events[index] {
+                    Event::Begin(Grouping::Normal) => Task::Math(index + 1, self.ends[index] - 1),
+                    _ => Task::Element(index),
+                };
+                self.control_sequence("sqrt")?;
+                self.str("[")?;
+                self.then([index_task, Task::Str("]".into()), Task::Argument(first)]);
+            }
+            Visual::Fraction(bar) => {
+                match bar {
+                    None => self.control_sequence("frac")?,
+                    Some(bar) => {
+                        self.control_sequence("genfrac")?;
+                        self.str("{}{}")?;
+                        self.braced(&dimension(bar))?;
+                        self.str("{}")?;
+                    }
+                }
+                self.then([Task::Argument(first), Task::Argument(self.ends[first])]);
+            }
+            Visual::Negation => {
+                self.control_sequence("not")?;
+                self.then([Task::Argument(first)]);
+            }
+            Visual::Framed(framing) => {
+                let end = self.ends[first];
+                if framing.width.is_none()
+                    && events[first] == Event::Begin(Grouping::Normal)
+                    && events[first + 1] == Event::StateChange(StateChange::Style(Style::Display))
+                {
+                    self.control_sequence("boxed")?;
+                    self.str("{")?;
+                    self.then([Task::Math(first + 2, end - 1), Task::Str("}".into())]);
+                    return Ok(());
+                }
+                match framing.width {
+                    None => self.control_sequence("fbox")?,
+                    Some(width) => {
+                        self.control_sequence("framebox")?;
+                        self.str("[")?;
+                        self.str(&dimension(width))?;
+                        self.str("]")?;
+                        match framing.alignment {
+                            ColumnAlignment::Left => self.str("[l]")?,
+                            ColumnAlignment::Center => {}
+                            ColumnAlignment::Right => self.str("[r]")?,
+                        }
+                    }
+                }
+                self.str("{")?;
+                self.then([Task::Text(first, end, None), Task::Str("}".into())]);
+            }
+            Visual::Phantom {
+                horizontal,
+                vertical,
+            } => {
+                self.control_sequence(match (horizontal, vertical) {
+                    (true, true) => "phantom",
+                    (true, false) => "hphantom",
+                    (false, true) => "vphantom",
+                    (false, false) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "a phantom must keep at least one of its dimensions",
+                        ))
+                    }
+                })?;
+                self.then([Task::Argument(first)]);
+            }
+            Visual::Smash { top, bottom } => {
+                self.control_sequence("smash")?;
+                match (top, bottom) {
+                    (true, true) => {}
+                    (true, false) => self.str("[t]")?,
+                    (false, true) => self.str("[b]")?,
+                    (false, false) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "a smash must remove at least one of the dimensions",
+                        ))
+                    }
+                }
+                self.then([Task::Argument(first)]);
+            }
+            Visual::Overlap(alignment) => {
+                let end = self.ends[first];
+                let text = matches!(events[first], Event::Content(Content::Text(_)))
+                    || self.is_text(first, end);
+                self.control_sequence(match (alignment, text) {
+                    (ColumnAlignment::Left, true) => "llap",
+                    (ColumnAlignment::Center, true) => "clap",
+                    (ColumnAlignment::Right, true) => "rlap",
+                    (ColumnAlignment::Left, false) => "mathllap",
+                    (ColumnAlignment::Center, false) => "mathclap",
+                    (ColumnAlignment::Right, false) => "mathrlap",
+                })?;
+                if text {
+                    self.str("{")?;
+                    self.then([Task::Text(first, end, None), Task::Str("}".into())]);
+                } else {
+                    self.then([Task::Argument(first)]);
+                }
+            }
+            Visual::MathChoice => {
+                let text = self.ends[first];
+                let script = self.ends[text];
+                let script_script = self.ends[script];
+                self.control_sequence("mathchoice")?;
+                self.then([
+                    Task::Argument(first),
+                    Task::Argument(text),
+                    Task::Argument(script),
+                    Task::Argument(script_script),
+                ]);
+            }
+            Visual::HorizontalBox => {
+                self.control_sequence("mbox")?;
+                self.str("{")?;
+                self.then([
+                    Task::Text(first, self.ends[first], None),
+                    Task::Str("}".into()),
+                ]);
+            }
+            Visual::VerticalCenter => {
+                self.control_sequence("vcenter")?;
+                self.then([Task::Argument(first)]);
+            }
+            Visual::Raise(shift) => {
+                let end = self.ends[first];
+                if let Event::Rule {
+                    width,
+                    height,
+                    depth,
+                } = events[first]
+                {
+                    return self.rule(Some(shift), width, height, depth);
+                }
+                if matches!(events[first], Event::Content(Content::Text(_)))
+                    || self.is_text(first, end)
+                {
+                    self.control_sequence("raisebox")?;
+                    self.braced(&dimension(shift))?;
+                    self.str("{")?;
+                    self.then([Task::Text(first, end, None), Task::Str("}".into())]);
+                } else if shift.value < 0. {
+                    self.control_sequence("lower")?;
+                    self.braced(&dimension(Dimension::new(-shift.value, shift.unit)))?;
+                    self.then([Task::Argument(first)]);
+                } else {
+                    self.control_sequence("raise")?;
+                    self.braced(&dimension(shift))?;
+                    self.then([Task::Argument(first)]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn rule(
+        &mut self,
+        raise: Option<Dimension>,
+        width: Dimension,
+        height: Dimension,
+        depth: Dimension,
+    ) -> io::Result<()> {
+        if depth.value != 0. {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "rules extending below the baseline cannot be written",
+            ));
+        }
+        self.control_sequence("rule")?;
+        if let Some(raise) = raise {
+            self.str("[")?;
+            self.str(&dimension(raise))?;
+            self.str("]")?;
+        }
+        self.braced(&dimension(width))?;
+        self.braced(&dimension(height))
+    }
+
+    fn space(
+        &mut self,
+        width: Option<Dimension>,
+        height: Option<Dimension>,
+        depth: Option<Dimension>,
+    ) -> io::Result<()> {
+        let em = |value| Some(Dimension::new(value, DimensionUnit::Em));
+        let cs = match (width, height, depth) {
+            (Some(_), None, None) => match width {
+                w if w == em(3. / 18.) => Some(","),
+                w if w == em(4. / 18.) => Some(":"),
+                w if w == em(5. / 18.) => Some(";"),
+                w if w == em(0.5) => Some("enspace"),
+                w if w == em(1.) => Some("quad"),
+                w if w == em(2.) => Some("qquad"),
+                w if w == em(-3. / 18.) => Some("!"),
+                w if w == em(-4. / 18.) => Some("negmedspace"),
+                w if w == em(-5. / 18.) => Some("negthickspace"),
+                _ => None,
+            },
+            (None, h, None) if h == em(0.7) => Some("mathstrut"),
+            (None, h, None) if h == em(1.) => Some("strut"),
+            _ => None,
+        };
+        match (cs, width, height, depth) {
+            (Some(cs), ..) => self.control_sequence(cs),
+            (None, Some(width), None, None) => {
+                self.control_sequence("kern")?;
+                self.braced(&dimension(width))
+            }
+            (None, ..) => {
+                let zero = Dimension::new(0., DimensionUnit::Em);
+                self.control_sequence("Space")?;
+                for dim in [width, height, depth] {
+                    self.braced(&dimension(dim.unwrap_or(zero)))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn state_change(&mut self, state_change: StateChange) -> io::Result<()> {
+        match state_change {
+            // Fonts without a switch are handled along with the rest of the group.
+            StateChange::Font(font) => {
+                self.control_sequence(font.and_then(font_switch).unwrap_or("rm"))
+            }
+            StateChange::Color(ColorChange {
+                color,
+                target: ColorTarget::Text,
+            }) => {
+                self.control_sequence("color")?;
+                self.braced(&hex_color(color))
+            }
+            StateChange::Color(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "background and border colors can only be written at the start of a group",
+            )),
+            StateChange::Style(style) => self.control_sequence(match style {
+                Style::Display => "displaystyle",
+                Style::Text => "textstyle",
+                Style::Script => "scriptstyle",
+                Style::ScriptScript => "scriptscriptstyle",
+            }),
+        }
+    }
+
+    fn environment_flow(&mut self, flow: &EnvironmentFlow) -> io::Result<()> {
+        match flow {
+            EnvironmentFlow::Alignment => self.str("&"),
+            EnvironmentFlow::NewLine {
+                spacing,
+                horizontal_lines,
+            } => {
+                self.str("\\\\")?;
+                if let Some(spacing) = spacing {
+                    self.str("[")?;
+                    self.str(&dimension(*spacing))?;
+                    self.str("]")?;
+                }
+                self.lines(horizontal_lines)?;
+                if spacing.is_none() && horizontal_lines.is_empty() {
+                    self.last = Last::NewLine;
+                }
+                Ok(())
+            }
+            EnvironmentFlow::StartLines { lines } => self.lines(lines),
+            EnvironmentFlow::Tag { tag, parenthesized } => {
+                self.control_sequence("tag")?;
+                if !parenthesized {
+                    self.str("*")?;
+                }
+                self.str("{")?;
+                self.text(tag, None)?;
+                self.str("}")
+            }
+            EnvironmentFlow::NoNumber => self.control_sequence("notag"),
+            EnvironmentFlow::Label(label) => {
+                self.control_sequence("label")?;
+                self.braced(label)
+            }
+        }
+    }
+
+    /// Whether the elements in the range can all be written in text mode.
+    fn is_text(&self, start: usize, end: usize) -> bool {
+        let events = self.events;
+        let mut index = start;
+        let mut previous_text = false;
+        while index < end {
+            match &events[index] {
+                // Adjacent texts would be merged, and empty texts would disappear.
+                Event::Content(Content::Text(text)) if !previous_text && !text.is_empty() => {
+                    previous_text = true;
+                    index += 1;
+                    continue;
+                }
+                Event::Begin(Grouping::Normal) => match events.get(index + 1) {
+                    Some(Event::StateChange(StateChange::Font(None))) => index = self.ends[index],
+                    Some(Event::StateChange(
+                        StateChange::Font(Some(_))
+                        | StateChange::Color(ColorChange {
+                            target: ColorTarget::Text,
+                            ..
+                        }),
+                    )) => index += 2,
+                    _ => return false,
+                },
+                Event::End => index += 1,
+                _ => return false,
+            }
+            previous_text = false;
+        }
+        true
+    }
+
+    fn text_element(&mut self, index: usize, font: Option<Font>) -> io::Result<()> {
+        let events = self.events;
+        match &events[index] {
+            Event::Content(Content::Text(text)) => self.text(text, font),
+            Event::Begin(Grouping::Normal) => {
+                let end = self.ends[index] - 1;
+                let start = index + 1;
+                match events.get(start).filter(|_| start < end) {
+                    Some(Event::StateChange(StateChange::Font(None)))
+                        if !self.is_text(start + 1, end) =>
+                    {
+                        self.str("$")?;
+                        self.then([Task::Math(start + 1, end), Task::Str("$".into())]);
+                    }
+                    Some(Event::StateChange(StateChange::Font(group_font))) => {
+                        self.control_sequence(text_font_command(font, *group_font))?;
+                        self.str("{")?;
+                        self.then([
+                            Task::Text(start + 1, end, *group_font),
+                            Task::Str("}".into()),
+                        ]);
+                    }
+                    Some(Event::StateChange(StateChange::Color(ColorChange {
+                        color,
+                        target: ColorTarget::Text,
+                    }))) => {
+                        self.control_sequence("textcolor")?;
+                        self.braced(&hex_color(*color))?;
+                        self.str("{")?;
+                        self.then([Task::Text(start + 1, end, font), Task::Str("}".into())]);
+                    }
+                    // Groups are transparent in text mode.
+                    _ => self.then([Task::Text(start, end, font)]),
+                }
+                Ok(())
+            }
+            _ => {
+                self.str("$")?;
+                self.then([Task::Element(index), Task::Str("$".into())]);
+                Ok(())
+            }
+        }
+    }
+
+    /// Write text, escaping the characters that have a special meaning in text mode.
+    fn text(&mut self, text: &str, font: Option<Font>) -> io::Result<()> {
+        let ligatures = font != Some(Font::Monospace);
+        let mut escaped = String::with_capacity(text.len());
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => escaped.push_str("\\textbackslash{}"),
+                '~' => escaped.push_str("\\textasciitilde{}"),
+                '^' => escaped.push_str("\\textasciicircum{}"),
+                '$' | '%' | '&' | '#' | '_' | '{' | '}' => {
+                    escaped.push('\\');
+                    escaped.push(c);
+                }
+                '\u{A0}' => escaped.push('~'),
+                '-' if ligatures && chars.peek() == Some(&'-') => escaped.push_str("-{}"),
+                c => escaped.push(c),
+            }
+        }
+        self.str(&escaped)
+    }
+}
+
+/// Compute the index one past the end of the element starting at each index of the events.
+///
+/// The events must be valid, and the value for an `End` event is the index following it.
+fn element_ends(events: &[Event]) -> Vec<usize> {
+    let mut ends = vec![0; events.len()];
+    let mut open_groups = Vec::new();
+    for (index, event) in events.iter().enumerate() {
+        match event {
+            Event::Begin(_) => open_groups.push(index),
+            Event::End => {
+                let begin = open_groups.pop().expect("events are validated");
+                ends[begin] = index + 1;
+            }
+            _ => {}
+        }
+    }
+
+    for index in (0..events.len()).rev() {
+        let arguments = match &events[index] {
+            Event::Begin(_) => continue,
+            Event::Script {
+                ty: ScriptType::SubSuperscript,
+                ..
+            } => 3,
+            Event::Script { .. } | Event::Visual(Visual::Root | Visual::Fraction(_)) => 2,
+            Event::Visual(Visual::MathChoice) => 4,
+            Event::Visual(_) => 1,
+            _ => 0,
+        };
+        let mut end = index + 1;
+        for _ in 0..arguments {
+            end = ends[end];
+        }
+        ends[index] = end;
+    }
+    ends
+}
+
+/// Whether the character is written as is in math mode, i.e., it is not an active character, nor
+/// a digit or whitespace.
+fn is_plain_char(c: char) -> bool {
+    !matches!(
+        c,
+        '\\' | '{' | '}' | '%' | '$' | '#' | '&' | '_' | '^' | '~' | '0'..='9'
+    ) && !c.is_whitespace()
+        && !c.is_control()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum DelimiterToken {
+    Char,
+    ControlSequence(&'static str),
+}
+
+/// Returns the token producing the delimiter with the given type, after `\left`, `\right`, or a
+/// sizing command.
+fn delimiter_token(c: char, ty: DelimiterType) -> Option<DelimiterToken> {
+    match c {
+        '(' | ')' | '[' | ']' | '|' | '/' => {
+            let is_type = match ty {
+                DelimiterType::Open => matches!(c, '(' | '['),
+                DelimiterType::Close => matches!(c, ')' | ']'),
+                DelimiterType::Fence => matches!(c, '|' | '/'),
+            };
+            is_type.then_some(DelimiterToken::Char)
+        }
+        _ => tables::delimiter(c, ty).map(DelimiterToken::ControlSequence),
+    }
+}
+
+fn matrix_type(left: char, right: char) -> Option<MatrixType> {
+    Some(match (left, right) {
+        ('(', ')') => MatrixType::Parens,
+        ('[', ']') => MatrixType::Brackets,
+        ('{', '}') => MatrixType::Braces,
+        ('|', '|') => MatrixType::Vertical,
+        ('‖', '‖') => MatrixType::DoubleVertical,
+        _ => return None,
+    })
+}
+
+fn alignment_char(alignment: ColumnAlignment) -> char {
+    match alignment {
+        ColumnAlignment::Left => 'l',
+        ColumnAlignment::Center => 'c',
+        ColumnAlignment::Right => 'r',
+    }
+}
+
+/// Returns the switch command of the font, e.g., `\bf`.
+fn font_switch(font: Font) -> Option<&'static str> {
+    Some(match font {
+        Font::Bold => "bf",
+        Font::Script => "cal",
+        Font::Italic => "it",
+        Font::UpRight => "rm",
+        Font::SansSerif => "sf",
+        Font::Monospace => "tt",
+        _ => return None,
+    })
+}
+
+/// Returns the math mode command applying the font to its argument, e.g., `\mathbf`.
+fn math_font_command(font: Option<Font>) -> &'static str {
+    match font {
+        None => "mathnormal",
+        Some(Font::Bold) => "mathbf",
+        Some(Font::BoldSymbol) => "boldsymbol",
+        Some(Font::Script) => "mathcal",
+        Some(Font::Italic) => "mathit",
+        Some(Font::UpRight) => "mathrm",
+        Some(Font::SansSerif) => "mathsf",
+        Some(Font::Monospace) => "mathtt",
+        Some(Font::DoubleStruck) => "mathbb",
+        Some(Font::DoubleStruckItalic) => "mathbbit",
+        Some(Font::Fraktur) => "mathfrak",
+        Some(Font::BoldScript) => "mathbfcal",
+        Some(Font::SansSerifItalic) => "mathsfit",
+        Some(Font::BoldItalic) => "mathbfit",
+        Some(Font::BoldFraktur) => "mathbffrak",
+        Some(Font::BoldSansSerif) => "mathbfsfup",
+        Some(Font::SansSerifBoldItalic) => "mathbfsfit",
+    }
+}
+
+/// Returns the math mode command writing its argument as text in the font, e.g., `\textbf`.
+fn math_text_font_command(font: Option<Font>) -> Option<&'static str> {
+    Some(match font {
+        None => "textnormal",
+        Some(Font::UpRight) => "textrm",
+        Some(Font::Bold) => "textbf",
+        Some(Font::Italic) => "textit",
+        Some(Font::SansSerif) => "textsf",
+        Some(Font::Monospace) => "texttt",
+        _ => return None,
+    })
+}
+
+/// Returns the text mode command changing the current font to the given one.
+fn text_font_command(current: Option<Font>, font: Option<Font>) -> &'static str {
+    match font {
+        None => "textnormal",
+        Some(Font::UpRight) => "textrm",
+        Some(Font::SansSerif) => "textsf",
+        Some(Font::Monospace) => "texttt",
+        Some(font) if font == bold(current) => "textbf",
+        Some(font) if font == italic(current) => "textit",
+        Some(font) if font == upright(current) => "textup",
+        // Not reachable from the current font.
+        Some(_) => "textrm",
+    }
+}
+
+/// Escape the name of an operator, so that it is parsed back into the same name by
+/// `\operatorname`.
+fn operator_name(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            ' ' => escaped.push_str("\\ "),
+            '{' | '}' | '$' | '%' | '&' | '#' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\\' => escaped.push_str("\\backslash "),
+            '^' | '~' => escaped.push_str(&format!("{{\\char{}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn hex_color((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// Format a dimension in `LaTeX` units.
+///
+/// This differs from the `Display` implementation of `Dimension`, which converts to CSS units.
+fn dimension(dimension: Dimension) -> String {
+    let unit = match dimension.unit {
+        DimensionUnit::Em => "em",
+        DimensionUnit::Mu => "mu",
+        DimensionUnit::Ex => "ex",
+        DimensionUnit::Pt => "pt",
+        DimensionUnit::Pc => "pc",
+        DimensionUnit::In => "in",
+        DimensionUnit::Bp => "bp",
+        DimensionUnit::Cm => "cm",
+        DimensionUnit::Mm => "mm",
+        DimensionUnit::Dd => "dd",
+        DimensionUnit::Cc => "cc",
+        DimensionUnit::Sp => "sp",
+    };
+    format!("{}{}", dimension.value, unit)
+}
+
+fn collect<'a, I, E>(events: I) -> io::Result<Vec<Event<'a>>>
+where
+    I: Iterator<Item = Result<Event<'a>, E>>,
+    E: std::error::Error,
+{
+    event::validate(events)
+        .collect::<Result<_, _>>()
+        .map_err(|e| {
+            let kind = match e {
+                event::ValidationError::Event(_) => io::ErrorKind::InvalidData,
+                event::ValidationError::Violation { .. } => io::ErrorKind::InvalidInput,
+            };
+            io::Error::new(kind, e.to_string())
+        })
+}
+
+/// Takes a [`Parser`], or any `Iterator<Item = Result<Event<'_>, E>>` as input, and writes the
+/// `LaTeX` source producing the events into the given string.
+///
+/// An error is returned if the input contains an error, or if the events are not structurally
+/// valid, as checked by [`event::validate`].
+///
+/// [`Parser`]: crate::parser::Parser
+pub fn push_latex<'a, I, E>(string: &mut String, events: I) -> io::Result<()>
+where
+    I: Iterator<Item = Result<Event<'a>, E>>,
+    E: std::error::Error,
+{
+    let events = collect(events)?;
+    // SAFETY: The LatexWriter only writes valid utf-8 to the writer.
+    LatexWriter::new(unsafe { string.as_mut_vec() }, &events).write()
+}
+
+/// Takes a [`Parser`], or any `Iterator<Item = Result<Event<'_>, E>>`, as input and writes the
+/// `LaTeX` source producing the events into the given writer.
+///
+/// An error is returned if the input contains an error, or if the events are not structurally
+/// valid, as checked by [`event::validate`].
+///
+/// [`Parser`]: crate::parser::Parser
+pub fn write_latex<'a, I, W, E>(writer: W, events: I) -> io::Result<()>
+where
+    I: Iterator<Item = Result<Event<'a>, E>>,
+    W: io::Write,
+    E: std::error::Error,
+{
+    let events = collect(events)?;
+    LatexWriter::new(writer, &events).write()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser, Storage};
+
+    use super::*;
+
+    fn parse(input: &str) -> Vec<Event<'static>> {
+        let storage = Storage::new();
+        Parser::new(input, &storage)
+            .map(|event| event.map(Event::into_owned))
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|e| panic!("input `{input}` failed to parse: {e}"))
+    }
+
+    fn write(events: &[Event<'static>]) -> String {
+        let mut latex = String::new();
+        push_latex(
+            &mut latex,
+            events
+                .iter()
+                .cloned()
+                .map(Ok::<_, std::convert::Infallible>),
+        )
+        .unwrap();
+        latex
+    }
+
+    #[track_caller]
+    fn round_trip(input: &str) {
+        let events = parse(input);
+        let latex = write(&events);
+        assert_eq!(
+            parse(&latex),
+            events,
+            "input `{input}` was written as `{latex}`"
+        );
+        assert_eq!(write(&parse(&latex)), latex, "output is not canonical");
+    }
+
+    #[test]
+    fn symbols() {
+        round_trip(r"a + b - c * d = e < f > g , h ; i : j ! k ? l . m / n | o ' p");
+        round_trip(r"\alpha \beta \Gamma \infty \partial \nabla \ell \hbar \emptyset \#\%\&\$\_");
+        round_trip(r"\pm \times \div \cdot \cup \cap \wedge \vee \oplus \otimes \circ \setminus");
+        round_trip(r"\le \ge \neq \equiv \approx \sim \subset \supseteq \in \notin \to \mapsto");
+        round_trip(r"\coloneqq \eqqcolon \smallsmile \smallfrown");
+        round_trip(r"\langle \rangle \lceil \rfloor \lvert \rVert \{ \} ( ) [ ] \backslash \|");
+        round_trip(r"\char65 \char`\a \mathord{+} \mathrel{x} \mathbin{y} \mathpunct{z}");
+        round_trip(r"123 4.5 6,7 1 2 \not = \cancel{x}");
+    }
+
+    #[test]
+    fn operators_and_scripts() {
+        round_trip(r"\sum_{i=0}^n \int_a^b \iint \smallint_0^1 \prod\limits_i \int\limits_0");
+        round_trip(r"\sum\nolimits_i \lim_{x \to 0} \limsup_n \sin^2 x \log_2 \operatorname{sgn}");
+        round_trip(r"\operatorname{arg\,max}_x \mathop{op}_a^b \mathop{\star}\limits_a");
+        round_trip(r"\operatorname*{arg\,max}_x \operatorname{a\{b\}\_\#\backslash{\char94}}");
+        round_trip(
+            r"\sum\displaylimits_i \int\displaylimits_0^1 \lim\nolimits_x \mathop{op}\nolimits",
+        );
+        round_trip(r"x^2 x_1 x_1^2 x^{10} x^{-1} {x^2}^3 x'' f'_1 {}_a^b");
+        round_trip(r"\hat x \widehat{xy} \vec{v} \bar{z}^2 \overline{ab} \underline{c}_d");
+        round_trip(r"\overbrace{a + b}^{n} \underbrace{c}_{m} \overset{!}{=} \underset{x}{\max}");
+        round_trip(r"\xrightarrow{f} \xleftarrow[g]{h} \xrightarrow[a]{} \stackrel{?}{=}");
+    }
+
+    #[test]
+    fn visuals_and_spaces() {
+        round_trip(r"\frac{1}{2} \frac12 \dfrac{a}{b} \tfrac{c}{d} \cfrac{e}{f}");
+        round_trip(r"\binom{n}{k} \dbinom{n}{k} \tbinom{n}{k} \genfrac[]{1pt}{2}{a}{b}");
+        round_trip(r"\genfrac{}{}{0pt}{}{a}{b} \genfrac\langle{}{}{3}{a}{b}");
+        round_trip(r"\sqrt{x} \sqrt[3]{y} \sqrt[ab]{z} \surd \sqrt\frac12");
+        round_trip(r"a\,b\:c\;d\enspace e\quad f\qquad g\!h \negmedspace \negthickspace");
+        round_trip(r"\kern1em \kern{-2.5pt} \mathstrut \strut \Space{1em}{2ex}{3pt} a~b \ c");
+        round_trip(r"\phantom{x} \hphantom{y} \vphantom\frac12 \smash{z} \smash[t]{g} \smash[b]y");
+        round_trip(r"\llap{a b} \rlap{} \clap{$x$ y} \mathllap{x} \mathrlap{} \mathclap\frac12");
+        round_trip(r"\rule{0pt}{2em} \rule[-1ex]{1cm}{3pt} \raise{1pt}{x} \lower{2mu}\frac12");
+        round_trip(r"\raisebox{0.5ex}{$\cdot$} \raisebox{-1em}{a b} \raise{1pt}{\rule{1em}{1em}}");
+        round_trip(r"\mathchoice{a}{b}{c}{d} \mathchoice{\frac12}{x y}{}{\sqrt2}");
+        round_trip(r"\mbox{ if } \mbox{$x$ y} \mbox{} \vcenter{x} \vcenter{\frac12}");
+        round_trip(r"\boxed{E=mc^2} \fbox{a b} \fbox{$x$ y} \framebox[2cm][r]{z} \framebox[1em]{}");
+    }
+
+    #[test]
+    fn groups_and_delimiters() {
+        round_trip(r"{a} {} {{b}} \left( \frac{a}{b} \right) \left. x \middle| y \right\rangle");
+        round_trip(r"\bigl( \Bigr] \biggm| \Biggl\lbrace \big/ \bigm\uparrow \Big\|");
+        round_trip(r"\mathbf{x} \mathbb R \mathcal{A} \boldsymbol{\alpha} \mathbfsfit{v}");
+        round_trip(r"\bf x \it y \rm z \cal A \mathnormal{x y}");
+        round_trip(r"\displaystyle x \textstyle y \scriptstyle z \scriptscriptstyle w");
+        round_trip(r"\color{red} x \textcolor{blue}{y} \textcolor{green}{a + b}");
+        round_trip(r"{\color{#ff0000} a} b");
+    }
+
+    #[test]
+    fn text() {
+        round_trip(r"\text{hello world} \text{} \textbf{bold} \textit{a $x^2$ b}");
+        round_trip(r"\text{a \textbf{b \textit{c}} d} \texttt{--} \text{-- --- `` ''}");
+        round_trip(
+            r"\text{\$ \% \& \# \_ \{ \} \textbackslash \textasciitilde ~ \textasciicircum}",
+        );
+        round_trip(r"\textnormal{a} \textrm{b} \textsf{c} \text{a {b} c}");
+        round_trip(r"\colorbox{red}{text} \fcolorbox{red}{blue}{$x$ and y}");
+    }
+
+    #[test]
+    fn environments() {
+        round_trip(
+            r"\begin{array}{|l:c|r} \hline 1 & 2 & 3 \\[2pt] \hline 4 & 5 & 6 \\ \end{array}",
+        );
+        round_trip(r"\begin{darray}{cc} \hline a & b \end{darray}");
+        round_trip(
+            r"\begin{pmatrix} a & -b \\ c & d \end{pmatrix} \begin{dcases} 0 & x < 0 \end{dcases}",
+        );
+        round_trip(r"\begin{bmatrix*}[r] 1 \end{bmatrix*} \begin{smallmatrix} a \end{smallmatrix}");
+        round_trip(
+            r"\begin{Bmatrix} a \end{Bmatrix} \begin{vmatrix} a \end{vmatrix} \begin{Vmatrix} a \end{Vmatrix}",
+        );
+        round_trip(r"\begin{alignat}{2} a &= b & c &= d \\ e &= f \end{alignat}");
+        round_trip(r"\begin{equation} \begin{split} a &= b \\ &= c \end{split} \end{equation}");
+        round_trip(
+            r"\sum_{\substack{0 \le i \le m \\ 0 < j < n}} \begin{subarray}{l} a \end{subarray}",
+        );
+        round_trip(r"\begin{gather*} a \\ \relax [b] \end{gather*} \begin{rcases} a \end{rcases}");
+        round_trip(r"\begin{align} a \\ \end{align} \begin{multline} a \end{multline}");
+        round_trip(r"\begin{align*} a &= b \tag{1.2} \label{eq:a} \\ c &= d \notag \end{align*}");
+        round_trip(r"\begin{equation} x \tag*{$\ast$ --- \{a\}} \end{equation}");
+        round_trip(r"\ref{eq:a} = \eqref{b}^2");
+    }
+
+    #[test]
+    fn canonical_output() {
+        for (input, expected) in [
+            (r"\frac {1} {2}+\alpha^{ 2 }", r"\frac{1}{2}+\alpha^{2}"),
+            (r"x^2_{10}", r"x_{10}^2"),
+            (r"\alpha b \beta 1 2", r"\alpha b\beta1 2"),
+            (
+                r"\int \limits _0 \sum \nolimits_i",
+                r"\int\limits_0\sum\nolimits_i",
+            ),
+            (r"\bigl ( x \bigr )", r"\bigl(x\bigr)"),
+            (r"\text{a\textbackslash b}", r"\text{a\textbackslash{}b}"),
+            (
+                r"\begin{matrix}a\\\end{matrix}",
+                r"\begin{matrix}a\\\end{matrix}",
+            ),
+        ] {
+            assert_eq!(write(&parse(input)), expected);
+        }
+    }
+
+    #[test]
+    fn invalid_streams() {
+        let mut latex = String::new();
+        let error = push_latex(
+            &mut latex,
+            [Ok::<_, std::convert::Infallible>(Event::End)].into_iter(),
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
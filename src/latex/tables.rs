@@ -0,0 +1,785 @@
+//! Reverse lookup tables, mapping the content produced by the parser back to the control
+//! sequences producing it.
+//!
+//! When several control sequences produce the same content, the first one handled by the parser
+//! is used.
+
+use crate::event::DelimiterType;
+
+/// Returns the control sequence producing the given ordinary character.
+#[rustfmt::skip]
+pub fn ordinary(c: char) -> Option<&'static str> {
+    Some(match c {
+        'α' => "alpha",
+        'β' => "beta",
+        'γ' => "gamma",
+        'δ' => "delta",
+        'ϵ' => "epsilon",
+        'ζ' => "zeta",
+        'η' => "eta",
+        'θ' => "theta",
+        'ι' => "iota",
+        'κ' => "kappa",
+        'λ' => "lambda",
+        'µ' => "mu",
+        'ν' => "nu",
+        'ξ' => "xi",
+        'π' => "pi",
+        'ρ' => "rho",
+        'σ' => "sigma",
+        'τ' => "tau",
+        'υ' => "upsilon",
+        'ϕ' => "phi",
+        'χ' => "chi",
+        'ψ' => "psi",
+        'ω' => "omega",
+        'ο' => "omicron",
+        'Α' => "Alpha",
+        'Β' => "Beta",
+        'Γ' => "Gamma",
+        'Δ' => "Delta",
+        'Ε' => "Epsilon",
+        'Ζ' => "Zeta",
+        'Η' => "Eta",
+        'Θ' => "Theta",
+        'Ι' => "Iota",
+        'Κ' => "Kappa",
+        'Λ' => "Lambda",
+        'Μ' => "Mu",
+        'Ν' => "Nu",
+        'Ξ' => "Xi",
+        'Π' => "Pi",
+        'Ρ' => "Rho",
+        'Σ' => "Sigma",
+        'Τ' => "Tau",
+        'Υ' => "Upsilon",
+        'Φ' => "Phi",
+        'Χ' => "Chi",
+        'Ψ' => "Psi",
+        'Ω' => "Omega",
+        'Ο' => "Omicron",
+        'ε' => "varepsilon",
+        'ϑ' => "vartheta",
+        'ϰ' => "varkappa",
+        'ϱ' => "varrho",
+        'ς' => "varsigma",
+        'ϖ' => "varpi",
+        'φ' => "varphi",
+        '𝛤' => "varGamma",
+        '𝛥' => "varDelta",
+        '𝛩' => "varTheta",
+        '𝛬' => "varLambda",
+        '𝛯' => "varXi",
+        '𝛱' => "varPi",
+        '𝛴' => "varSigma",
+        '𝛶' => "varUpsilon",
+        '𝛷' => "varPhi",
+        '𝛹' => "varPsi",
+        '𝛺' => "varOmega",
+        'ℵ' => "aleph",
+        'ℶ' => "beth",
+        'ℷ' => "gimel",
+        'ℸ' => "daleth",
+        'ϝ' => "digamma",
+        'ð' => "eth",
+        'ℓ' => "ell",
+        '∇' => "nabla",
+        '∂' => "partial",
+        'Ⅎ' => "Finv",
+        'ℏ' => "hbar",
+        'ı' => "imath",
+        'ȷ' => "jmath",
+        'ℑ' => "Im",
+        'ℜ' => "Re",
+        '℘' => "wp",
+        '𝕜' => "Bbbk",
+        'Å' => "Angstrom",
+        '϶' => "backepsilon",
+        '…' => "ldots",
+        '⋯' => "cdots",
+        '⋱' => "ddots",
+        '⋰' => "iddots",
+        '⋮' => "vdots",
+        '∞' => "infty",
+        '✓' => "checkmark",
+        '✗' => "ballotx",
+        '†' => "dagger",
+        '‡' => "ddagger",
+        '∠' => "angle",
+        '∡' => "measuredangle",
+        '‘' => "lq",
+        '□' => "Box",
+        '∢' => "sphericalangle",
+        '⊤' => "top",
+        '′' => "rq",
+        '■' => "blacksquare",
+        '⊥' => "bot",
+        '▽' => "triangledown",
+        '⫫' => "Bot",
+        '◃' => "triangleleft",
+        '▹' => "triangleright",
+        '¢' => "cent",
+        ':' => "colon",
+        '£' => "pounds",
+        '△' => "bigtriangleup",
+        '▲' => "blacktriangle",
+        '▼' => "blacktriangledown",
+        '¥' => "yen",
+        '◀' => "blacktriangleleft",
+        '€' => "euro",
+        '▶' => "blacktriangleright",
+        '◊' => "Diamond",
+        '°' => "degree",
+        '⧫' => "blacklozenge",
+        '℧' => "mho",
+        '★' => "bigstar",
+        '╲' => "diagdown",
+        '✠' => "maltese",
+        '╱' => "diagup",
+        '¶' => "P",
+        '♣' => "clubsuit",
+        '♧' => "varclubsuit",
+        '§' => "S",
+        '♢' => "diamondsuit",
+        '♦' => "vardiamondsuit",
+        '©' => "copyright",
+        '♡' => "heartsuit",
+        '♥' => "varheartsuit",
+        '®' => "circledR",
+        '♠' => "spadesuit",
+        '♤' => "varspadesuit",
+        'Ⓢ' => "circledS",
+        '♀' => "female",
+        '♂' => "male",
+        '☉' => "astrosun",
+        '☼' => "sun",
+        '☾' => "leftmoon",
+        '☽' => "rightmoon",
+        '☺' => "smiley",
+        '⊕' => "Earth",
+        '♭' => "flat",
+        '⦵' => "standardstate",
+        '♮' => "natural",
+        '♯' => "sharp",
+        '‰' => "permil",
+        '∎' => "QED",
+        '↯' => "lightning",
+        '⌀' => "diameter",
+        '⟕' => "leftouterjoin",
+        '⟖' => "rightouterjoin",
+        '⟡' => "concavediamond",
+        '⟢' => "concavediamondtickleft",
+        '⟣' => "concavediamondtickright",
+        '⟗' => "fullouterjoin",
+        '⟤' => "whitesquaretickleft",
+        '⟥' => "whitesquaretickright",
+        '″' => "dprime",
+        '‴' => "trprime",
+        '⁗' => "qprime",
+        '‵' => "backprime",
+        '‶' => "backdprime",
+        '‷' => "backtrprime",
+        '∀' => "forall",
+        '∃' => "exists",
+        '∁' => "complement",
+        '∄' => "nexists",
+        '¬' => "neg",
+        '∅' => "emptyset",
+        '\\' => "backslash",
+        '∥' => "|",
+        '#' => "#",
+        '%' => "%",
+        '&' => "&",
+        '$' => "$",
+        '_' => "_",
+        _ => return None,
+    })
+}
+
+/// Returns the control sequence producing the given relation.
+#[rustfmt::skip]
+pub fn relation(c: char) -> Option<&'static str> {
+    Some(match c {
+        '∴' => "therefore",
+        '∵' => "because",
+        '⊂' => "subset",
+        '⊃' => "supset",
+        '⥽' => "strictif",
+        '⥼' => "strictfi",
+        '↦' => "mapsto",
+        '⟹' => "implies",
+        '∣' => "mid",
+        '→' => "to",
+        '⟸' => "impliedby",
+        '∈' => "in",
+        '∋' => "ni",
+        '←' => "gets",
+        '⟺' => "iff",
+        '∌' => "notni",
+        '≖' => "eqcirc",
+        '≶' => "lessgtr",
+        '⌣' => "smile",
+        '∹' => "eqcolon",
+        '≲' => "lesssim",
+        '⊏' => "sqsubset",
+        '≪' => "ll",
+        '⊑' => "sqsubseteq",
+        '≕' => "eqqcolon",
+        '⋘' => "lll",
+        '⊐' => "sqsupset",
+        '⊒' => "sqsupseteq",
+        '≈' => "approx",
+        '≝' => "eqdef",
+        '<' => "lt",
+        '≛' => "stareq",
+        '≊' => "approxeq",
+        '≂' => "eqsim",
+        '≞' => "measeq",
+        '⋐' => "Subset",
+        '≘' => "arceq",
+        '⪖' => "eqslantgtr",
+        '⪕' => "eqslantless",
+        '⊨' => "models",
+        '⊆' => "subseteq",
+        '≌' => "backcong",
+        '≡' => "equiv",
+        '⊸' => "multimap",
+        '⫅' => "subseteqq",
+        '≒' => "fallingdotseq",
+        '⧟' => "multimapboth",
+        '≻' => "succ",
+        '∽' => "backsim",
+        '⌢' => "frown",
+        '⟜' => "multimapinv",
+        '⪸' => "succapprox",
+        '⋍' => "backsimeq",
+        '≥' => "ge",
+        '⊶' => "origof",
+        '≽' => "succcurlyeq",
+        '≬' => "between",
+        '⪰' => "succeq",
+        '≏' => "bumpeq",
+        '≧' => "geqq",
+        '∥' => "parallel",
+        '≿' => "succsim",
+        '≎' => "Bumpeq",
+        '⩾' => "geqslant",
+        '⟂' => "perp",
+        '⋑' => "Supset",
+        '≗' => "circeq",
+        '≫' => "gg",
+        '⫫' => "Perp",
+        '⋙' => "ggg",
+        '⋔' => "pitchfork",
+        '⊇' => "supseteq",
+        '≺' => "prec",
+        '⫆' => "supseteqq",
+        '>' => "gt",
+        '⪷' => "precapprox",
+        '⪆' => "gtrapprox",
+        '≼' => "preccurlyeq",
+        '∼' => "thicksim",
+        '⋛' => "gtreqless",
+        '⪯' => "preceq",
+        '⊴' => "trianglelefteq",
+        '≔' => "coloneqq",
+        '⪌' => "gtreqqless",
+        '≾' => "precsim",
+        '≜' => "triangleq",
+        '⩴' => "Coloneqq",
+        '≷' => "gtrless",
+        '∝' => "propto",
+        '⊵' => "trianglerighteq",
+        '≳' => "gtrsim",
+        '≟' => "questeq",
+        '⊷' => "imageof",
+        '≅' => "cong",
+        '≓' => "risingdotseq",
+        '⊲' => "vartriangleleft",
+        '⋞' => "curlyeqprec",
+        '⊳' => "vartriangleright",
+        '⋟' => "curlyeqsucc",
+        '≤' => "le",
+        '⊢' => "vdash",
+        '⊣' => "dashv",
+        '∷' => "dblcolon",
+        '≦' => "leqq",
+        '⊩' => "Vdash",
+        '≐' => "doteq",
+        '⩽' => "leqslant",
+        '≃' => "simeq",
+        '⊫' => "Dash",
+        '≑' => "Doteq",
+        '⪅' => "lessapprox",
+        '⊪' => "Vvdash",
+        '⋚' => "lesseqgtr",
+        '≚' => "veeeq",
+        '⩵' => "eqeq",
+        '⪋' => "lesseqqgtr",
+        '≙' => "wedgeq",
+        '⋈' => "bowtie",
+        '⪊' => "gnapprox",
+        '≱' => "ngeqslant",
+        '⊄' => "nsubset",
+        '⊮' => "nVdash",
+        '⪈' => "gneq",
+        '≯' => "ngtr",
+        '⊈' => "nsubseteq",
+        '⪹' => "precnapprox",
+        '≩' => "gneqq",
+        '≰' => "nleq",
+        '⪵' => "precneqq",
+        '⋧' => "gnsim",
+        '⊁' => "nsucc",
+        '⋨' => "precnsim",
+        '⋡' => "nsucceq",
+        '⊊' => "subsetneq",
+        '⪉' => "lnapprox",
+        '≮' => "nless",
+        '⊅' => "nsupset",
+        '⫋' => "subsetneqq",
+        '⪇' => "lneq",
+        '∤' => "nmid",
+        '⊉' => "nsupseteq",
+        '⪺' => "succnapprox",
+        '≨' => "lneqq",
+        '∉' => "notin",
+        '⪶' => "succneqq",
+        '⋦' => "lnsim",
+        '⋪' => "ntriangleleft",
+        '⋩' => "succnsim",
+        '∦' => "nparallel",
+        '⋬' => "ntrianglelefteq",
+        '⊋' => "supsetneq",
+        '≆' => "ncong",
+        '⊀' => "nprec",
+        '⋫' => "ntriangleright",
+        '⫌' => "supsetneqq",
+        '≠' => "ne",
+        '⋠' => "npreceq",
+        '⋭' => "ntrianglerighteq",
+        '⊬' => "nvdash",
+        '⊭' => "nvDash",
+        '≁' => "nsim",
+        '⊯' => "nVDash",
+        '↺' => "circlearrowleft",
+        '⇔' => "Leftrightarrow",
+        '↾' => "restriction",
+        '↻' => "circlearrowright",
+        '⇆' => "leftrightarrows",
+        '↶' => "curvearrowleft",
+        '⇋' => "leftrightharpoons",
+        '⇒' => "Rightarrow",
+        '↷' => "curvearrowright",
+        '↭' => "leftrightsquigarrow",
+        '↣' => "rightarrowtail",
+        '⇠' => "dashleftarrow",
+        '⇚' => "Lleftarrow",
+        '⇁' => "rightharpoondown",
+        '⇢' => "dashrightarrow",
+        '⟵' => "longleftarrow",
+        '⇀' => "rightharpoonup",
+        '↓' => "downarrow",
+        '⇄' => "rightleftarrows",
+        '⇓' => "Downarrow",
+        '⟷' => "longleftrightarrow",
+        '⇌' => "rightleftharpoons",
+        '⇊' => "downdownarrows",
+        '⇉' => "rightrightarrows",
+        '⇃' => "downharpoonleft",
+        '⟼' => "longmapsto",
+        '⇝' => "rightsquigarrow",
+        '⇂' => "downharpoonright",
+        '⟶' => "longrightarrow",
+        '⇛' => "Rrightarrow",
+        '↱' => "Rsh",
+        '↩' => "hookleftarrow",
+        '↫' => "looparrowleft",
+        '↘' => "searrow",
+        '↪' => "hookrightarrow",
+        '↬' => "looparrowright",
+        '↙' => "swarrow",
+        '↰' => "Lsh",
+        '↤' => "mapsfrom",
+        '↞' => "twoheadleftarrow",
+        '↠' => "twoheadrightarrow",
+        '↗' => "nearrow",
+        '↑' => "uparrow",
+        '↚' => "nleftarrow",
+        '⇑' => "Uparrow",
+        '⇐' => "Leftarrow",
+        '⇍' => "nLeftarrow",
+        '↕' => "updownarrow",
+        '↢' => "leftarrowtail",
+        '↮' => "nleftrightarrow",
+        '⇕' => "Updownarrow",
+        '↽' => "leftharpoondown",
+        '⇎' => "nLeftrightarrow",
+        '↿' => "upharpoonleft",
+        '↼' => "leftharpoonup",
+        '↛' => "nrightarrow",
+        '⇇' => "leftleftarrows",
+        '⇏' => "nRightarrow",
+        '⇈' => "upuparrows",
+        '↔' => "leftrightarrow",
+        '↖' => "nwarrow",
+        _ => return None,
+    })
+}
+
+/// Returns the control sequence producing the given small relation.
+#[rustfmt::skip]
+pub fn small_relation(c: char) -> Option<&'static str> {
+    Some(match c {
+        '∣' => "shortmid",
+        '∥' => "shortparallel",
+        '⌣' => "smallsmile",
+        '∤' => "nshortmid",
+        '∦' => "nshortparallel",
+        _ => return None,
+    })
+}
+
+/// Returns the control sequence producing the given two-character relation.
+#[rustfmt::skip]
+pub fn multirelation(first: char, second: char) -> Option<&'static str> {
+    Some(match (first, second) {
+        ('⫌', '\u{FE00}') => "varsupsetneqq",
+        ('⫋', '\u{FE00}') => "varsubsetneqq",
+        ('⊊', '\u{FE00}') => "varsubsetneq",
+        ('⊋', '\u{FE00}') => "varsupsetneq",
+        ('≩', '\u{FE00}') => "gvertneqq",
+        ('≨', '\u{FE00}') => "lvertneqq",
+        ('−', '∷') => "Eqcolon",
+        ('=', '∷') => "Eqqcolon",
+        ('≈', ':') => "approxcolon",
+        (':', '≈') => "colonapprox",
+        ('≈', '∷') => "approxcoloncolon",
+        ('∷', '≈') => "Colonapprox",
+        (':', '−') => "coloneq",
+        ('∷', '−') => "Coloneq",
+        (':', '∼') => "colonsim",
+        ('∷', '∼') => "Colonsim",
+        _ => return None,
+    })
+}
+
+/// Returns the control sequence producing the given binary operator.
+#[rustfmt::skip]
+pub fn binary(c: char) -> Option<&'static str> {
+    Some(match c {
+        '∧' => "land",
+        '.' => "ldotp",
+        '·' => "cdotp",
+        '⋅' => "cdot",
+        '∘' => "circ",
+        '∙' => "bullet",
+        '⊛' => "circledast",
+        '⊚' => "circledcirc",
+        '⊝' => "circleddash",
+        '◯' => "bigcirc",
+        '⋋' => "leftthreetimes",
+        '⊳' => "rhd",
+        '⊲' => "lhd",
+        '⋌' => "rightthreetimes",
+        '⋊' => "rtimes",
+        '⋉' => "ltimes",
+        '⊨' => "leftmodels",
+        '⨿' => "amalg",
+        '*' => "ast",
+        '≍' => "asymp",
+        '&' => "And",
+        '∨' => "lor",
+        '∖' => "setminus",
+        '⋓' => "Cup",
+        '∪' => "cup",
+        '⊔' => "sqcup",
+        '⊓' => "sqcap",
+        '⋖' => "lessdot",
+        '⌅' => "barwedge",
+        '⋎' => "curlyvee",
+        '⋏' => "curlywedge",
+        '⫽' => "sslash",
+        '÷' => "div",
+        '∓' => "mp",
+        '×' => "times",
+        '⊡' => "boxdot",
+        '⋇' => "divideontimes",
+        '⊙' => "odot",
+        '⊴' => "unlhd",
+        '⊟' => "boxminus",
+        '∔' => "dotplus",
+        '⊖' => "ominus",
+        '⊵' => "unrhd",
+        '⊞' => "boxplus",
+        '⩞' => "doublebarwedge",
+        '⊕' => "oplus",
+        '⊎' => "uplus",
+        '⊠' => "boxtimes",
+        '⋒' => "doublecap",
+        '⊗' => "otimes",
+        '⊻' => "veebar",
+        '⅋' => "parr",
+        '∩' => "cap",
+        '⋗' => "gtrdot",
+        '±' => "pm",
+        '⊺' => "intercal",
+        '≀' => "wr",
+        '⦶' => "circledvert",
+        '⧗' => "blackhourglass",
+        '⦵' => "circlehbar",
+        '⦹' => "operp",
+        '⧆' => "boxast",
+        '⧈' => "boxbox",
+        '⊘' => "oslash",
+        '⧇' => "boxcircle",
+        '⋄' => "diamond",
+        '⨷' => "Otimes",
+        '⧖' => "hourglass",
+        '⨶' => "otimeshat",
+        '⨻' => "triangletimes",
+        '⟠' => "lozengeminus",
+        '⋆' => "star",
+        '⌽' => "obar",
+        '⦸' => "obslash",
+        '⨺' => "triangleminus",
+        '⨸' => "odiv",
+        '⨹' => "triangleplus",
+        '⊜' => "circledequal",
+        '⧁' => "ogreaterthan",
+        '⦷' => "circledparallel",
+        '⧀' => "olessthan",
+        _ => return None,
+    })
+}
+
+/// Returns the control sequence producing the given large operator, where `movable` is whether
+/// its limits are placed above and below in display style.
+#[rustfmt::skip]
+pub fn large_op(c: char, movable: bool) -> Option<&'static str> {
+    Some(match (c, movable) {
+        ('∑', true) => "sum",
+        ('∏', true) => "prod",
+        ('∐', true) => "coprod",
+        ('⋁', true) => "bigvee",
+        ('⋀', true) => "bigwedge",
+        ('⋃', true) => "bigcup",
+        ('⋂', true) => "bigcap",
+        ('⨄', true) => "biguplus",
+        ('⨁', true) => "bigoplus",
+        ('⨂', true) => "bigotimes",
+        ('⨀', true) => "bigodot",
+        ('⨆', true) => "bigsqcup",
+        ('⨅', true) => "bigsqcap",
+        ('⨉', true) => "bigtimes",
+        ('∫', true) => "intop",
+        ('∫', false) => "int",
+        ('∬', false) => "iint",
+        ('∭', false) => "iiint",
+        ('⨌', false) => "iiiint",
+        ('⨙', false) => "intcap",
+        ('⨚', false) => "intcup",
+        ('∮', false) => "oint",
+        ('∲', false) => "varointclockwise",
+        ('∱', false) => "intclockwise",
+        ('∯', false) => "oiint",
+        ('⨕', false) => "pointint",
+        ('⨒', false) => "rppolint",
+        ('⨓', false) => "scpolint",
+        ('∰', false) => "oiiint",
+        ('⨗', false) => "intlarhk",
+        ('⨖', false) => "sqint",
+        ('⨘', false) => "intx",
+        ('⨍', false) => "intbar",
+        ('⨎', false) => "intBar",
+        ('⨏', false) => "fint",
+        _ => return None,
+    })
+}
+
+/// Returns the accent command placing the given character over its argument, and whether the
+/// command makes scripts go above and below.
+#[rustfmt::skip]
+pub fn accent(c: char, stretchy: bool) -> Option<(&'static str, bool)> {
+    Some(match (c, stretchy) {
+        ('´', false) => ("acute", false),
+        ('‾', false) => ("bar", false),
+        ('˘', false) => ("breve", false),
+        ('ˇ', false) => ("check", false),
+        ('˙', false) => ("dot", false),
+        ('¨', false) => ("ddot", false),
+        ('`', false) => ("grave", false),
+        ('^', false) => ("hat", false),
+        ('~', false) => ("tilde", false),
+        ('→', false) => ("vec", false),
+        ('˚', false) => ("mathring", false),
+        ('←', true) => ("overleftarrow", false),
+        ('→', true) => ("overrightarrow", false),
+        ('⇒', true) => ("Overrightarrow", false),
+        ('↔', true) => ("overleftrightarrow", false),
+        ('↼', true) => ("overleftharpoon", false),
+        ('⇀', true) => ("overrightharpoon", false),
+        ('ˇ', true) => ("widecheck", false),
+        ('^', true) => ("widehat", false),
+        ('~', true) => ("widetilde", false),
+        ('⏜', true) => ("wideparen", false),
+        ('⏠', true) => ("overgroup", true),
+        ('⏞', true) => ("overbrace", true),
+        ('⎴', true) => ("overbracket", true),
+        _ => return None,
+    })
+}
+
+/// Returns the command placing the given stretchy character under its argument, and whether
+/// the command makes scripts go above and below.
+#[rustfmt::skip]
+pub fn underscript(c: char) -> Option<(&'static str, bool)> {
+    Some(match c {
+        '_' => ("underbar", false),
+        '←' => ("underleftarrow", false),
+        '→' => ("underrightarrow", false),
+        '↔' => ("underleftrightarrow", false),
+        '⏡' => ("undergroup", true),
+        '⏟' => ("underbrace", true),
+        '⏝' => ("underparen", true),
+        '⎵' => ("underbracket", true),
+        _ => return None,
+    })
+}
+
+/// Returns the control sequence producing the given delimiter after `\left`, `\right` or a
+/// sizing command.
+#[rustfmt::skip]
+pub fn delimiter(c: char, ty: DelimiterType) -> Option<&'static str> {
+    Some(match (c, ty) {
+        ('(', DelimiterType::Open) => "lparen",
+        (')', DelimiterType::Close) => "rparen",
+        ('⦇', DelimiterType::Open) => "llparenthesis",
+        ('⦈', DelimiterType::Close) => "rrparenthesis",
+        ('⟮', DelimiterType::Open) => "lgroup",
+        ('⟯', DelimiterType::Close) => "rgroup",
+        ('[', DelimiterType::Open) => "lbrack",
+        (']', DelimiterType::Close) => "rbrack",
+        ('⟦', DelimiterType::Open) => "lBrack",
+        ('⟧', DelimiterType::Close) => "rBrack",
+        ('{', DelimiterType::Open) => "{",
+        ('}', DelimiterType::Close) => "}",
+        ('⦃', DelimiterType::Open) => "lBrace",
+        ('⦄', DelimiterType::Close) => "rBrace",
+        ('⟨', DelimiterType::Open) => "langle",
+        ('⟩', DelimiterType::Close) => "rangle",
+        ('⟪', DelimiterType::Open) => "lAngle",
+        ('⟫', DelimiterType::Close) => "rAngle",
+        ('⦉', DelimiterType::Open) => "llangle",
+        ('⦊', DelimiterType::Close) => "rrangle",
+        ('⌊', DelimiterType::Open) => "lfloor",
+        ('⌋', DelimiterType::Close) => "rfloor",
+        ('⌈', DelimiterType::Open) => "lceil",
+        ('⌉', DelimiterType::Close) => "rceil",
+        ('┌', DelimiterType::Open) => "ulcorner",
+        ('┐', DelimiterType::Close) => "urcorner",
+        ('└', DelimiterType::Open) => "llcorner",
+        ('┘', DelimiterType::Close) => "lrcorner",
+        ('⎰', DelimiterType::Open) => "lmoustache",
+        ('⎱', DelimiterType::Close) => "rmoustache",
+        ('\\', DelimiterType::Fence) => "backslash",
+        ('|', DelimiterType::Fence) => "vert",
+        ('|', DelimiterType::Open) => "lvert",
+        ('|', DelimiterType::Close) => "rvert",
+        ('‖', DelimiterType::Fence) => "|",
+        ('‖', DelimiterType::Open) => "lVert",
+        ('‖', DelimiterType::Close) => "rVert",
+        ('↑', DelimiterType::Fence) => "uparrow",
+        ('⇑', DelimiterType::Fence) => "Uparrow",
+        ('↓', DelimiterType::Fence) => "downarrow",
+        ('⇓', DelimiterType::Fence) => "Downarrow",
+        ('↕', DelimiterType::Fence) => "updownarrow",
+        ('⇕', DelimiterType::Fence) => "Updownarrow",
+        _ => return None,
+    })
+}
+
+/// Returns the control sequence producing the given unsized delimiter in math mode.
+#[rustfmt::skip]
+pub fn math_delimiter(c: char, ty: DelimiterType) -> Option<&'static str> {
+    Some(match (c, ty) {
+        ('(', DelimiterType::Open) => "lparen",
+        (')', DelimiterType::Close) => "rparen",
+        ('⦇', DelimiterType::Open) => "llparenthesis",
+        ('⦈', DelimiterType::Close) => "rrparenthesis",
+        ('⟮', DelimiterType::Open) => "lgroup",
+        ('⟯', DelimiterType::Close) => "rgroup",
+        ('[', DelimiterType::Open) => "lbrack",
+        (']', DelimiterType::Close) => "rbrack",
+        ('⟦', DelimiterType::Open) => "lBrack",
+        ('⟧', DelimiterType::Close) => "rBrack",
+        ('{', DelimiterType::Open) => "{",
+        ('}', DelimiterType::Close) => "}",
+        ('⦃', DelimiterType::Open) => "lBrace",
+        ('⦄', DelimiterType::Close) => "rBrace",
+        ('⟨', DelimiterType::Open) => "langle",
+        ('⟩', DelimiterType::Close) => "rangle",
+        ('⟪', DelimiterType::Open) => "lAngle",
+        ('⟫', DelimiterType::Close) => "rAngle",
+        ('⦉', DelimiterType::Open) => "llangle",
+        ('⦊', DelimiterType::Close) => "rrangle",
+        ('⌊', DelimiterType::Open) => "lfloor",
+        ('⌋', DelimiterType::Close) => "rfloor",
+        ('⌈', DelimiterType::Open) => "lceil",
+        ('⌉', DelimiterType::Close) => "rceil",
+        ('┌', DelimiterType::Open) => "ulcorner",
+        ('┐', DelimiterType::Close) => "urcorner",
+        ('└', DelimiterType::Open) => "llcorner",
+        ('┘', DelimiterType::Close) => "lrcorner",
+        ('⎰', DelimiterType::Open) => "lmoustache",
+        ('⎱', DelimiterType::Close) => "rmoustache",
+        ('|', DelimiterType::Fence) => "vert",
+        ('|', DelimiterType::Open) => "lvert",
+        ('|', DelimiterType::Close) => "rvert",
+        ('‖', DelimiterType::Fence) => "Vert",
+        ('‖', DelimiterType::Open) => "lVert",
+        ('‖', DelimiterType::Close) => "rVert",
+        _ => return None,
+    })
+}
+
+/// Returns the control sequence producing the given function, and whether its limits are movable.
+pub fn function(name: &str) -> Option<(&'static str, bool)> {
+    Some(match name {
+        "arccos" => ("arccos", false),
+        "cos" => ("cos", false),
+        "csc" => ("csc", false),
+        "exp" => ("exp", false),
+        "ker" => ("ker", false),
+        "sinh" => ("sinh", false),
+        "arcsin" => ("arcsin", false),
+        "cosh" => ("cosh", false),
+        "deg" => ("deg", false),
+        "lg" => ("lg", false),
+        "ln" => ("ln", false),
+        "arctan" => ("arctan", false),
+        "cot" => ("cot", false),
+        "det" => ("det", false),
+        "hom" => ("hom", false),
+        "log" => ("log", false),
+        "sec" => ("sec", false),
+        "tan" => ("tan", false),
+        "arg" => ("arg", false),
+        "coth" => ("coth", false),
+        "dim" => ("dim", false),
+        "sin" => ("sin", false),
+        "tanh" => ("tanh", false),
+        "sgn" => ("sgn", false),
+        "mod" => ("bmod", false),
+        "lim" => ("lim", true),
+        "Pr" => ("Pr", true),
+        "sup" => ("sup", true),
+        "max" => ("max", true),
+        "inf" => ("inf", true),
+        "gcd" => ("gcd", true),
+        "min" => ("min", true),
+        "lim inf" => ("liminf", true),
+        "lim sup" => ("limsup", true),
+        _ => return None,
+    })
+}
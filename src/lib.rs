@@ -4,16 +4,22 @@
 
 #![doc = include_str!("../docs/usage.md")]
 
+pub mod auto_render;
 pub mod config;
 pub mod event;
+pub mod latex;
 pub mod mathml;
+pub mod numbering;
 pub mod parser;
+pub mod tree;
 
 #[doc(inline)]
-pub use config::RenderConfig;
+pub use config::{ParserConfig, RenderConfig};
 #[doc(inline)]
 pub use event::Event;
 #[doc(inline)]
+pub use latex::{push_latex, write_latex};
+#[doc(inline)]
 pub use mathml::{push_mathml, write_mathml};
 #[doc(inline)]
-pub use parser::{error::ParserError, storage::Storage, Parser};
+pub use parser::{error::ParserError, macros::MacroSet, storage::Storage, Parser};
@@ -4,6 +4,7 @@
 //! [`push_mathml`] and [`write_mathml`] functions.
 
 use std::{
+    borrow::Cow,
     collections::VecDeque,
     io::{self, Write},
 };
@@ -25,6 +26,8 @@ struct MathmlWriter<'a, I: Iterator, W> {
     state_stack: Vec<State>,
     previous_atom: Option<Atom>,
     error_recovery: bool,
+    /// The tag, labels and numbering of the current row of a numbered environment.
+    row_label: RowLabel<'a>,
 }
 
 impl<'a, I, W, E> MathmlWriter<'a, I, W>
@@ -42,6 +45,7 @@ where
             border_color: None,
             background_color: None,
             style: None,
+            group_style: None,
         });
         let env_stack = Vec::with_capacity(16);
         Self {
@@ -52,6 +56,7 @@ where
             state_stack,
             previous_atom: None,
             error_recovery: false,
+            row_label: RowLabel::default(),
         }
     }
 
@@ -62,6 +67,7 @@ where
             background_color,
             style,
             font: _,
+            group_style: _,
         } = *self.state();
         write!(self.writer, "<{}", tag)?;
         if let Some(style) = style {
@@ -149,7 +155,10 @@ where
                     self.state_stack.push(State::default())
                 } else {
                     let last_state = *self.state();
-                    self.state_stack.push(last_state);
+                    self.state_stack.push(State {
+                        group_style: None,
+                        ..last_state
+                    });
                     while let Some(Ok(Event::StateChange(state_change))) = self.input.peek_first() {
                         let state_change = *state_change;
                         self.handle_state_change(state_change);
@@ -161,6 +170,7 @@ where
                     // except font.
                     *self.state_stack.last_mut().expect("state stack is empty") = State {
                         font: self.state().font,
+                        group_style: self.state().group_style,
                         ..State::default()
                     };
                 }
@@ -355,15 +365,17 @@ where
                         self.previous_atom = Some(Atom::Close);
                         self.writer.write_all(b"</mrow>")
                     }
-                    EnvGrouping::Matrix
-                    | EnvGrouping::Align
-                    | EnvGrouping::SubArray
+                    EnvGrouping::Matrix | EnvGrouping::SubArray | EnvGrouping::Split { .. } => {
+                        self.writer.write_all(b"</mtd></mtr></mtable>")
+                    }
+                    EnvGrouping::Align
                     | EnvGrouping::Gather
                     | EnvGrouping::Multline
                     | EnvGrouping::Equation
-                    | EnvGrouping::Split { .. }
                     | EnvGrouping::Alignat { .. } => {
-                        self.writer.write_all(b"</mtd></mtr></mtable>")
+                        self.writer.write_all(b"</mtd>")?;
+                        self.write_row_label(self.env_stack.len() + 1)?;
+                        self.writer.write_all(b"</mtr></mtable>")
                     }
                     EnvGrouping::Array { cols, cols_index } => {
                         self.writer.write_all(b"</mtd>")?;
@@ -394,6 +406,33 @@ where
                     }
                 }
             }
+            Ok(Event::Visual(Visual::MathChoice)) => {
+                let style = self.current_style();
+                // Only the branch for the current style is kept, and the others are dropped from
+                // the input. If the branches cannot be delimited, e.g., because of an error, they
+                // are all written.
+                let env = match self.choice_branches() {
+                    Some(ends) => {
+                        let chosen = match style {
+                            Style::Display => 0,
+                            Style::Text => 1,
+                            Style::Script => 2,
+                            Style::ScriptScript => 3,
+                        };
+                        let start = if chosen == 0 { 0 } else { ends[chosen - 1] };
+                        self.input.peeked.drain(ends[chosen]..ends[3]);
+                        self.input.peeked.drain(..start);
+                        Environment::Visual {
+                            ty: Visual::MathChoice,
+                            count: 1,
+                        }
+                    }
+                    None => Environment::from(Visual::MathChoice),
+                };
+                self.env_stack.push(env);
+                self.open_tag("mrow", None)?;
+                self.writer.write_all(b">")
+            }
             Ok(Event::Visual(visual)) => {
                 if visual == Visual::Negation {
                     match self.input.peek_first() {
@@ -405,7 +444,7 @@ where
                             | content @ Content::Delimiter { .. }
                             | content @ Content::Punctuation(_),
                         ))) => {
-                            let content = *content;
+                            let content = content.clone();
                             self.write_content(content, true)?;
                             self.input.next();
                         }
@@ -418,14 +457,81 @@ where
                     return Ok(());
                 }
 
+                if let Visual::Framed(framing) = visual {
+                    self.open_tag("mtable", Some("mframed"))?;
+                    self.writer.write_all(b"><mtr><mtd")?;
+                    match framing.alignment {
+                        ColumnAlignment::Left => self.writer.write_all(b" class=\"cell-left\"")?,
+                        ColumnAlignment::Center => {}
+                        ColumnAlignment::Right => {
+                            self.writer.write_all(b" class=\"cell-right\"")?
+                        }
+                    }
+                    if let Some(width) = framing.width {
+                        write!(self.writer, " style=\"width: {width}\"")?;
+                    }
+                    self.env_stack.push(Environment::from(visual));
+                    return self.writer.write_all(b">");
+                }
+
+                if visual == Visual::VerticalCenter {
+                    // Tables are centered on the math axis, but their cells are not in display
+                    // style, so it is restored when needed.
+                    let display = self.current_style() == Style::Display;
+                    self.open_tag("mtable", Some("mvcenter"))?;
+                    self.writer.write_all(b"><mtr><mtd")?;
+                    if display {
+                        self.writer.write_all(b" displaystyle=\"true\"")?;
+                    }
+                    self.env_stack.push(Environment::from(visual));
+                    return self.writer.write_all(b">");
+                }
+
+                // Overlaps that extend to the left are shifted by the stylesheet.
+                let classes = match visual {
+                    Visual::Overlap(ColumnAlignment::Left) => Some("moverlap-left"),
+                    Visual::Overlap(ColumnAlignment::Center) => Some("moverlap-center"),
+                    _ => None,
+                };
                 let env = Environment::from(visual);
                 self.env_stack.push(env);
-                self.open_tag(visual_tag(visual), None)?;
-                if let Visual::Fraction(Some(dim)) = visual {
-                    write!(self.writer, " linethickness=\"{}\"", dim)?;
+                self.open_tag(visual_tag(visual), classes)?;
+                match visual {
+                    Visual::Fraction(Some(dim)) => {
+                        write!(self.writer, " linethickness=\"{}\"", dim)?;
+                    }
+                    Visual::Phantom {
+                        horizontal,
+                        vertical,
+                    } => {
+                        if !horizontal {
+                            self.writer.write_all(b" width=\"0\"")?;
+                        }
+                        if !vertical {
+                            self.writer.write_all(b" height=\"0\" depth=\"0\"")?;
+                        }
+                    }
+                    Visual::Smash { top, bottom } => {
+                        if top {
+                            self.writer.write_all(b" height=\"0\"")?;
+                        }
+                        if bottom {
+                            self.writer.write_all(b" depth=\"0\"")?;
+                        }
+                    }
+                    Visual::Raise(shift) => {
+                        write!(self.writer, " voffset=\"{shift}\"")?;
+                    }
+                    Visual::Overlap(_) => {
+                        self.writer.write_all(b" width=\"0\"")?;
+                    }
+                    _ => {}
                 }
-
-                self.writer.write_all(b">")
+                self.writer.write_all(b">")?;
+                for tag in inner_visual_tags(visual) {
+                    write!(self.writer, "<{tag}>")?;
+                }
+                Ok(())
             }
 
             Ok(Event::Script { ty, position }) => {
@@ -464,6 +570,20 @@ where
                 }
                 self.writer.write_all(b" />")
             }
+            Ok(Event::Rule {
+                width,
+                height,
+                depth,
+            }) => {
+                self.open_tag("mspace", None)?;
+                write!(
+                    self.writer,
+                    " width=\"{width}\" height=\"{height}\" depth=\"{depth}\" \
+                     mathbackground=\"currentColor\" />"
+                )?;
+                self.set_previous_atom(Atom::Ord);
+                Ok(())
+            }
             Ok(Event::StateChange(state_change)) => {
                 self.handle_state_change(state_change);
                 Ok(())
@@ -482,14 +602,16 @@ where
                     self.env_stack.last()
                 {
                     array_close_line(&mut self.writer, &cols[*cols_index..])?;
-                } else if let Some(Environment::Group(EnvGrouping::Equation { .. })) =
+                } else if let Some(Environment::Group(EnvGrouping::Equation)) =
                     self.env_stack.last()
                 {
                     // LaTeX does _nothing_ when a newline is encountered in an eqution, we do the
                     // same thing.
                     return Ok(());
                 } else {
-                    self.writer.write_all(b"</mtd></mtr><mtr")?;
+                    self.writer.write_all(b"</mtd>")?;
+                    self.write_row_label(self.env_stack.len())?;
+                    self.writer.write_all(b"</mtr><mtr")?;
                 }
 
                 if let Some(spacing) = spacing {
@@ -574,13 +696,25 @@ where
                 self.error_recovery = true;
                 Ok(())
             }
+            Ok(Event::EnvironmentFlow(flow)) => {
+                self.row_label.depth = self.env_stack.len();
+                match flow {
+                    EnvironmentFlow::Tag { tag, parenthesized } => {
+                        self.row_label.tag = Some((tag, parenthesized))
+                    }
+                    EnvironmentFlow::NoNumber => self.row_label.no_number = true,
+                    EnvironmentFlow::Label(label) => self.row_label.labels.push(label),
+                    _ => unreachable!("other flow events are handled above"),
+                }
+                Ok(())
+            }
 
             Err(e) => {
                 self.error_recovery = true;
                 let error_color = self.config.error_color;
                 write!(
                     self.writer,
-                    "<merror style=\"border-color: #{:x}{:x}{:x}\"><mtext>",
+                    "<merror style=\"border-color: #{:02x}{:02x}{:02x}\"><mtext>",
                     error_color.0, error_color.1, error_color.2
                 )?;
                 self.writer.write_all(e.to_string().as_bytes())?;
@@ -589,6 +723,49 @@ where
         }
     }
 
+    /// Write the cell holding the tag and label of the row that is ending, if the row belongs to
+    /// the environment at the given depth of the environment stack.
+    ///
+    /// The cell is absolutely positioned by the stylesheet, so that it does not shift the columns
+    /// of the environment. Each label of the row is written as an empty anchor in the cell.
+    fn write_row_label(&mut self, depth: usize) -> io::Result<()> {
+        if self.row_label.depth != depth {
+            return Ok(());
+        }
+        let RowLabel {
+            tag,
+            no_number,
+            labels,
+            ..
+        } = std::mem::take(&mut self.row_label);
+        if tag.is_none() && !no_number && labels.is_empty() {
+            return Ok(());
+        }
+
+        self.writer.write_all(b"<mtd class=\"menv-tag")?;
+        if tag.is_some() || no_number {
+            self.writer.write_all(b" menv-nonumber")?;
+        }
+        self.writer.write_all(b"\">")?;
+        for label in labels {
+            self.writer.write_all(b"<mrow id=\"")?;
+            write_escaped(&mut self.writer, &label)?;
+            self.writer.write_all(b"\"></mrow>")?;
+        }
+        if let Some((tag, parenthesized)) = tag {
+            self.writer.write_all(b"<mtext>")?;
+            if parenthesized {
+                self.writer.write_all(b"(")?;
+            }
+            write_escaped(&mut self.writer, &tag)?;
+            if parenthesized {
+                self.writer.write_all(b")")?;
+            }
+            self.writer.write_all(b"</mtext>")?;
+        }
+        self.writer.write_all(b"</mtd>")
+    }
+
     fn write_content(&mut self, content: Content<'a>, negate: bool) -> io::Result<()> {
         let mut buf = [0u8; 4];
         match content {
@@ -653,7 +830,7 @@ where
                 } else {
                     b">"
                 })?;
-                self.writer.write_all(str.as_bytes())?;
+                write_escaped(&mut self.writer, &str)?;
                 self.set_previous_atom(Atom::Op);
                 self.writer.write_all(b"</mi>")?;
 
@@ -728,7 +905,16 @@ where
                     Some(
                         Environment::Script { .. }
                             | Environment::Visual {
-                                ty: Visual::Root | Visual::Fraction(_) | Visual::SquareRoot,
+                                ty: Visual::Root
+                                    | Visual::Fraction(_)
+                                    | Visual::SquareRoot
+                                    | Visual::Framed(_)
+                                    | Visual::Phantom { .. }
+                                    | Visual::Smash { .. }
+                                    | Visual::Overlap(_)
+                                    | Visual::Raise(_)
+                                    | Visual::HorizontalBox
+                                    | Visual::VerticalCenter,
                                 ..
                             }
                     )
@@ -818,6 +1004,47 @@ where
                 self.set_previous_atom(Atom::Punct);
                 self.writer.write_all(b"</mo>")
             }
+            Content::Reference {
+                label,
+                number,
+                parenthesized,
+            } => {
+                self.open_tag("mtext", None)?;
+                self.writer.write_all(b">")?;
+                // MathML Core does not support links on its elements, so the link is an HTML
+                // anchor, which `mtext` can contain when the output is embedded in HTML.
+                if number.is_some() {
+                    self.writer.write_all(b"<a href=\"#")?;
+                    write_escaped(&mut self.writer, &label)?;
+                    self.writer.write_all(b"\">")?;
+                }
+                if parenthesized {
+                    self.writer.write_all(b"(")?;
+                }
+                // Like in `LaTeX`, unresolved references are written as `??`.
+                write_escaped(&mut self.writer, number.as_deref().unwrap_or("??"))?;
+                if parenthesized {
+                    self.writer.write_all(b")")?;
+                }
+                if number.is_some() {
+                    self.writer.write_all(b"</a>")?;
+                }
+                self.set_previous_atom(Atom::Ord);
+                self.writer.write_all(b"</mtext>")
+            }
+            Content::Error { kind, source } => {
+                let error_color = self.config.error_color;
+                write!(
+                    self.writer,
+                    "<merror style=\"border-color: #{:02x}{:02x}{:02x}\" title=\"",
+                    error_color.0, error_color.1, error_color.2
+                )?;
+                write_escaped(&mut self.writer, &kind.to_string())?;
+                self.writer.write_all(b"\"><mtext>")?;
+                write_escaped(&mut self.writer, &source)?;
+                self.set_previous_atom(Atom::Ord);
+                self.writer.write_all(b"</mtext></merror>")
+            }
         }
     }
 
@@ -830,7 +1057,10 @@ where
                 ColorTarget::Border => state.border_color = Some(color),
                 ColorTarget::Background => state.background_color = Some(color),
             },
-            StateChange::Style(style) => state.style = Some(style),
+            StateChange::Style(style) => {
+                state.style = Some(style);
+                state.group_style = Some(style);
+            }
         }
     }
 
@@ -875,6 +1105,7 @@ where
                     | Event::Script { .. },
                 ) => continue,
                 Ok(Event::End | Event::EnvironmentFlow(_)) | Err(_) => None,
+                Ok(Event::Rule { .. }) => Some(Atom::Ord),
                 Ok(Event::Visual(_) | Event::Begin(_)) => Some(Atom::Inner),
                 Ok(Event::Content(content)) => match content {
                     Content::BinaryOp { .. } => Some(Atom::Bin),
@@ -895,6 +1126,103 @@ where
         }
     }
 
+    /// The style in which the next element is rendered, accounting for the implicit style changes
+    /// in scripts, fractions, and root indices.
+    fn current_style(&self) -> Style {
+        let mut style = match self.config.display_mode {
+            DisplayMode::Block => Style::Display,
+            DisplayMode::Inline => Style::Text,
+        };
+        let mut group_styles = self.state_stack.iter().map(|state| state.group_style);
+        if let Some(explicit) = group_styles.next().flatten() {
+            style = explicit;
+        }
+        let script_style = |style| match style {
+            Style::Display | Style::Text => Style::Script,
+            Style::Script | Style::ScriptScript => Style::ScriptScript,
+        };
+
+        for env in &self.env_stack {
+            match env {
+                Environment::Group(_) => {
+                    if let Some(explicit) = group_styles.next().flatten() {
+                        style = explicit;
+                    }
+                }
+                Environment::Visual {
+                    ty: Visual::Fraction(_),
+                    ..
+                } => {
+                    style = match style {
+                        Style::Display => Style::Text,
+                        Style::Text => Style::Script,
+                        Style::Script | Style::ScriptScript => Style::ScriptScript,
+                    }
+                }
+                Environment::Visual {
+                    ty: Visual::Root,
+                    count: 0,
+                } => style = Style::ScriptScript,
+                // The count is the number of elements left after the one being written, so the
+                // base is being written when the scripts are all left.
+                Environment::Script {
+                    ty: ScriptType::Subscript | ScriptType::Superscript,
+                    count: 0,
+                    ..
+                }
+                | Environment::Script {
+                    ty: ScriptType::SubSuperscript,
+                    count: 0 | 1,
+                    ..
+                } => style = script_style(style),
+                _ => {}
+            }
+        }
+        style
+    }
+
+    /// The index one past the end of each of the 4 branches of a `MathChoice`, in the peeked
+    /// events.
+    ///
+    /// Returns `None` if the branches do not form 4 complete elements.
+    fn choice_branches(&mut self) -> Option<[usize; 4]> {
+        let mut ends = [0; 4];
+        let mut index = 0;
+        for end in &mut ends {
+            // The number of elements left to complete the branch.
+            let mut remaining = 1usize;
+            let mut depth = 0usize;
+            while remaining > 0 {
+                if self.input.peeked_nth(index).is_none() {
+                    self.input.peek_next()?;
+                }
+                let event = self.input.peeked_nth(index)?;
+                index += 1;
+                match event {
+                    Ok(Event::Begin(_)) => {
+                        depth += 1;
+                        continue;
+                    }
+                    Ok(Event::End) => {
+                        depth = depth.checked_sub(1)?;
+                        if depth > 0 {
+                            continue;
+                        }
+                    }
+                    Err(_) => return None,
+                    _ if depth > 0 => continue,
+                    Ok(Event::EnvironmentFlow(_)) => return None,
+                    Ok(Event::Visual(visual)) => remaining += usize::from(visual_arity(*visual)),
+                    Ok(Event::Script { ty, .. }) => remaining += usize::from(script_arity(*ty)),
+                    Ok(_) => {}
+                }
+                remaining -= 1;
+            }
+            *end = index;
+        }
+        Some(ends)
+    }
+
     fn state(&self) -> &State {
         self.state_stack.last().expect("state stack is empty")
     }
@@ -922,25 +1250,30 @@ where
         while let Some(event) = self.input.next() {
             self.write_event(event)?;
 
-            while let Some((tag, count, fn_application)) =
+            while let Some((tag, inner, count, fn_application)) =
                 self.env_stack.last_mut().and_then(|env| match env {
                     Environment::Group(_) => None,
-                    Environment::Visual { ty, count } => Some((visual_tag(*ty), count, None)),
+                    Environment::Visual { ty, count } => {
+                        Some((visual_tag(*ty), inner_visual_tags(*ty), count, None))
+                    }
                     Environment::Script {
                         ty,
                         above_below,
                         count,
                         fn_application,
-                    } => Some((script_tag(*ty, *above_below), count, Some(*fn_application))),
+                    } => Some((
+                        script_tag(*ty, *above_below),
+                        &[][..],
+                        count,
+                        Some(*fn_application),
+                    )),
                 })
             {
                 if *count != 0 {
                     *count -= 1;
                     break;
                 }
-                self.writer.write_all(b"</")?;
-                self.writer.write_all(tag.as_bytes())?;
-                self.writer.write_all(b">")?;
+                close_tag(&mut self.writer, tag, inner)?;
                 self.set_previous_atom(Atom::Inner);
                 self.env_stack.pop();
 
@@ -960,22 +1293,20 @@ where
         if self.error_recovery {
             // In error recovery scenario, a tag might not be closed.
             while let Some(env) = self.env_stack.pop() {
-                let tag = match env {
+                let (tag, inner) = match env {
                     Environment::Group(_) => {
                         let _ = self.state_stack.pop();
                         continue;
                     }
-                    Environment::Visual { ty, count: _ } => visual_tag(ty),
+                    Environment::Visual { ty, count: _ } => (visual_tag(ty), inner_visual_tags(ty)),
                     Environment::Script {
                         ty,
                         above_below,
                         count: _,
                         fn_application: _,
-                    } => script_tag(ty, above_below),
+                    } => (script_tag(ty, above_below), &[][..]),
                 };
-                self.writer.write_all(b"</")?;
-                self.writer.write_all(tag.as_bytes())?;
-                self.writer.write_all(b">")?;
+                close_tag(&mut self.writer, tag, inner)?;
             }
         }
 
@@ -983,22 +1314,20 @@ where
         // This can happen with malformed input that confuses the environment tracking.
         if !self.env_stack.is_empty() || self.state_stack.len() != 1 {
             while let Some(env) = self.env_stack.pop() {
-                let tag = match env {
+                let (tag, inner) = match env {
                     Environment::Group(_) => {
                         let _ = self.state_stack.pop();
                         continue;
                     }
-                    Environment::Visual { ty, count: _ } => visual_tag(ty),
+                    Environment::Visual { ty, count: _ } => (visual_tag(ty), inner_visual_tags(ty)),
                     Environment::Script {
                         ty,
                         above_below,
                         count: _,
                         fn_application: _,
-                    } => script_tag(ty, above_below),
+                    } => (script_tag(ty, above_below), &[][..]),
                 };
-                self.writer.write_all(b"</")?;
-                self.writer.write_all(tag.as_bytes())?;
-                self.writer.write_all(b">")?;
+                close_tag(&mut self.writer, tag, inner)?;
             }
             self.state_stack.truncate(1);
         }
@@ -1208,6 +1537,16 @@ enum EnvGrouping {
     Equation,
 }
 
+/// The tag, labels and numbering of a row, which are written in a cell at the end of the row.
+#[derive(Debug, Clone, PartialEq, Default)]
+struct RowLabel<'a> {
+    /// The depth of the environment stack where the row is.
+    depth: usize,
+    tag: Option<(Cow<'a, str>, bool)>,
+    no_number: bool,
+    labels: Vec<Cow<'a, str>>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 enum Environment {
     Group(EnvGrouping),
@@ -1231,11 +1570,7 @@ impl From<EnvGrouping> for Environment {
 
 impl From<(ScriptType, bool)> for Environment {
     fn from((ty, above_below): (ScriptType, bool)) -> Self {
-        let count = match ty {
-            ScriptType::Subscript => 2,
-            ScriptType::Superscript => 2,
-            ScriptType::SubSuperscript => 3,
-        };
+        let count = script_arity(ty);
         Self::Script {
             ty,
             above_below,
@@ -1247,13 +1582,37 @@ impl From<(ScriptType, bool)> for Environment {
 
 impl From<Visual> for Environment {
     fn from(v: Visual) -> Self {
-        let count = match v {
-            Visual::SquareRoot => 1,
-            Visual::Root => 2,
-            Visual::Fraction(_) => 2,
-            Visual::Negation => 1,
-        };
-        Self::Visual { ty: v, count }
+        Self::Visual {
+            ty: v,
+            count: visual_arity(v),
+        }
+    }
+}
+
+/// The number of elements following a script event.
+fn script_arity(ty: ScriptType) -> u8 {
+    match ty {
+        ScriptType::Subscript => 2,
+        ScriptType::Superscript => 2,
+        ScriptType::SubSuperscript => 3,
+    }
+}
+
+/// The number of elements following a visual event.
+fn visual_arity(visual: Visual) -> u8 {
+    match visual {
+        Visual::SquareRoot => 1,
+        Visual::Root => 2,
+        Visual::Fraction(_) => 2,
+        Visual::Negation => 1,
+        Visual::Framed(_) => 1,
+        Visual::Phantom { .. } => 1,
+        Visual::Smash { .. } => 1,
+        Visual::Overlap(_) => 1,
+        Visual::Raise(_) => 1,
+        Visual::MathChoice => 4,
+        Visual::HorizontalBox => 1,
+        Visual::VerticalCenter => 1,
     }
 }
 
@@ -1273,8 +1632,45 @@ fn visual_tag(visual: Visual) -> &'static str {
         Visual::Root => "mroot",
         Visual::Fraction(_) => "mfrac",
         Visual::SquareRoot => "msqrt",
-        Visual::Negation => "mrow",
+        Visual::Negation | Visual::MathChoice | Visual::HorizontalBox => "mrow",
+        Visual::Framed(_) | Visual::VerticalCenter => "mtable",
+        Visual::Phantom {
+            horizontal: true,
+            vertical: true,
+        } => "mphantom",
+        Visual::Phantom { .. } | Visual::Smash { .. } | Visual::Overlap(_) | Visual::Raise(_) => {
+            "mpadded"
+        }
+    }
+}
+
+/// The tags opened inside of the tag of a visual, from the outermost to the innermost.
+fn inner_visual_tags(visual: Visual) -> &'static [&'static str] {
+    match visual {
+        // The content of a frame, or of a vertically centered box, is in the single cell of a
+        // table.
+        Visual::Framed(_) | Visual::VerticalCenter => &["mtr", "mtd"],
+        // The dimensions of a partial phantom are removed by its `mpadded` tag.
+        Visual::Phantom {
+            horizontal: false, ..
+        }
+        | Visual::Phantom {
+            vertical: false, ..
+        } => &["mphantom"],
+        // The content of an overlap that extends to the left is shifted by its own width, which is
+        // that of its inner `mrow` tag.
+        Visual::Overlap(ColumnAlignment::Left | ColumnAlignment::Center) => &["mrow"],
+        _ => &[],
+    }
+}
+
+fn close_tag<W: io::Write>(writer: &mut W, tag: &str, inner: &[&str]) -> io::Result<()> {
+    for inner in inner.iter().rev() {
+        write!(writer, "</{inner}>")?;
     }
+    writer.write_all(b"</")?;
+    writer.write_all(tag.as_bytes())?;
+    writer.write_all(b">")
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -1283,7 +1679,10 @@ struct State {
     text_color: Option<(u8, u8, u8)>,
     border_color: Option<(u8, u8, u8)>,
     background_color: Option<(u8, u8, u8)>,
+    /// The style that is not yet applied to an element.
     style: Option<Style>,
+    /// The last style set within the group, which is kept after it is applied.
+    group_style: Option<Style>,
 }
 
 struct ManyPeek<I: Iterator> {
@@ -1523,6 +1922,7 @@ fn write_escaped<W: io::Write>(writer: &mut W, s: &str) -> io::Result<()> {
             b'&' => b"&amp;",
             b'<' => b"&lt;",
             b'>' => b"&gt;",
+            b'"' => b"&quot;",
             _ => continue,
         };
         if start < i {
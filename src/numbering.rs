@@ -0,0 +1,537 @@
+//! Number the equations of a document, and resolve the references to them.
+//!
+//! The parser only sees one equation at a time, so it cannot know the number of an equation, nor
+//! what a `\ref{...}` refers to. A [`Numbering`] processes the equations of a document in two
+//! passes:
+//! 1. [`Numbering::number`] is given the events of every equation, in order. It assigns a number
+//!    to every numbered row of the environments by inserting an [`EnvironmentFlow::Tag`] event,
+//!    and collects the labels attached to the rows.
+//! 2. [`Numbering::resolve`] is then given the events of every equation again, and fills in the
+//!    number of the [`Content::Reference`]s produced by `\ref{...}` and `\eqref{...}`.
+//!
+//! Equations can be numbered within sections (e.g., `(2.3)`), and grouped into subequations
+//! (e.g., `(4a)`, `(4b)`). Like in `LaTeX`, problems such as references to unknown labels are not
+//! errors, and are reported as [`Warning`]s.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use pulldown_latex::{numbering::Numbering, push_mathml, Parser, ParserError, RenderConfig, Storage};
+//!
+//! let storage = Storage::new();
+//! let sources = [
+//!     r"\begin{equation} E = mc^2 \label{eq:energy} \end{equation}",
+//!     r"\begin{align} a &= b \\ c &= d \notag \end{align}",
+//!     r"\text{by } \eqref{eq:energy}, m = E / c^2",
+//! ];
+//!
+//! let mut numbering = Numbering::new();
+//! let mut equations = Vec::new();
+//! for source in sources {
+//!     let mut events = Parser::new(source, &storage)
+//!         .collect::<Result<Vec<_>, _>>()
+//!         .unwrap();
+//!     assert!(numbering.number(&mut events).is_empty());
+//!     equations.push(events);
+//! }
+//! assert_eq!(numbering.label("eq:energy"), Some("1"));
+//!
+//! let mut output = String::new();
+//! for mut events in equations {
+//!     assert!(numbering.resolve(&mut events).is_empty());
+//!     let events = events.into_iter().map(Ok::<_, ParserError>);
+//!     push_mathml(&mut output, events, RenderConfig::default()).unwrap();
+//! }
+//! assert!(output.contains(r##"<mtext><a href="#eq:energy">(1)</a></mtext>"##));
+//! ```
+
+use std::{
+    borrow::Cow,
+    collections::{hash_map::Entry, HashMap},
+    fmt::Display,
+};
+
+use crate::event::{Content, EnvironmentFlow, Event, Grouping};
+
+/// Configuration for a [`Numbering`].
+///
+/// The default value is: [`NumberingConfig::default`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NumberingConfig {
+    /// Whether equations are numbered within sections, e.g., `(2.3)` for the third equation of
+    /// the second section.
+    ///
+    /// See [`Numbering::section`].
+    pub per_section: bool,
+    /// Whether resolved references link to the row they refer to.
+    ///
+    /// If `false`, resolved references are replaced by their number as text.
+    ///
+    /// In `MathML`, a link is an `HTML` anchor (`<a href="...">`) within an `<mtext>` element,
+    /// since `MathML` Core does not support links. It is only followed when the output is
+    /// embedded in an `HTML` document.
+    pub links: bool,
+}
+
+impl Default for NumberingConfig {
+    /// # Default Value
+    /// ```rust
+    /// # use pulldown_latex::numbering::NumberingConfig;
+    /// const DEFAULT: NumberingConfig = NumberingConfig {
+    ///     per_section: false,
+    ///     links: true,
+    /// };
+    /// assert_eq!(NumberingConfig::default(), DEFAULT);
+    /// ```
+    fn default() -> Self {
+        Self {
+            per_section: false,
+            links: true,
+        }
+    }
+}
+
+/// The numbering state of a document.
+///
+/// See the [module documentation](self) for more details.
+#[derive(Debug, Clone, Default)]
+pub struct Numbering {
+    config: NumberingConfig,
+    section: u32,
+    equation: u32,
+    /// The number of the last subequation, if subequations are being numbered.
+    subequation: Option<u32>,
+    /// The number of every label found so far.
+    labels: HashMap<String, String>,
+}
+
+impl Numbering {
+    /// Create a numbering with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a numbering with the given configuration.
+    pub fn with_config(config: NumberingConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Start a new section.
+    ///
+    /// If equations are numbered within sections, the number of the following equation is reset
+    /// to 1. Before the first call to this method, equations are in section 0.
+    pub fn section(&mut self) {
+        self.section += 1;
+        if self.config.per_section {
+            self.equation = 0;
+        }
+    }
+
+    /// Start numbering subequations, as `LaTeX`'s `subequations` environment.
+    ///
+    /// The following rows share the next equation number, followed by a letter, e.g., `(4a)`,
+    /// `(4b)`, etc., until [`Numbering::end_subequations`] is called.
+    pub fn begin_subequations(&mut self) {
+        self.equation += 1;
+        self.subequation = Some(0);
+    }
+
+    /// Stop numbering subequations.
+    pub fn end_subequations(&mut self) {
+        self.subequation = None;
+    }
+
+    /// The number of the row with the given label, if it was found by [`Numbering::number`].
+    ///
+    /// For rows with a custom tag, this is the text of the tag.
+    pub fn label(&self, label: &str) -> Option<&str> {
+        self.labels.get(label).map(String::as_str)
+    }
+
+    /// Number the rows of the environments in the events of an equation, and collect their
+    /// labels.
+    ///
+    /// Every numbered row without a custom tag is given an [`EnvironmentFlow::Tag`] event
+    /// containing its number. Since their numbers are then explicit, numbered environments are
+    /// changed into their unnumbered variant, e.g., `align` into `align*`.
+    ///
+    /// The events must be structurally valid, as checked by
+    /// [`event::validate`](crate::event::validate).
+    pub fn number<'a>(&mut self, events: &mut Vec<Event<'a>>) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        let mut output = Vec::with_capacity(events.len());
+        // The current row of every open group that is an environment.
+        let mut stack: Vec<Option<Row>> = Vec::new();
+
+        for event in events.drain(..) {
+            if let Some(Some(row)) = stack.last_mut() {
+                if !matches!(event, Event::End | Event::EnvironmentFlow(_)) {
+                    row.empty = false;
+                }
+            }
+
+            match &event {
+                Event::Begin(grouping) if grouping.is_math_env() => {
+                    stack.push(Some(Row::new(grouping, output.len())))
+                }
+                Event::Begin(_) => stack.push(None),
+                Event::End => {
+                    if let Some(Some(row)) = stack.pop() {
+                        if let Event::Begin(grouping) = &mut output[row.begin] {
+                            unnumber(grouping);
+                        }
+                        self.finish_row(row, &mut output, &mut warnings);
+                    }
+                }
+                Event::EnvironmentFlow(flow) => {
+                    if let Some(Some(row)) = stack.last_mut() {
+                        match flow {
+                            EnvironmentFlow::NewLine { .. } if !row.single => {
+                                let next = Row {
+                                    tag: None,
+                                    no_number: false,
+                                    labels: Vec::new(),
+                                    empty: true,
+                                    ..*row
+                                };
+                                let row = std::mem::replace(row, next);
+                                self.finish_row(row, &mut output, &mut warnings);
+                            }
+                            EnvironmentFlow::Tag { tag, .. } => row.tag = Some(tag.to_string()),
+                            EnvironmentFlow::NoNumber => row.no_number = true,
+                            EnvironmentFlow::Label(label) => row.labels.push(label.to_string()),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+            output.push(event);
+        }
+
+        *events = output;
+        warnings
+    }
+
+    /// Fill in the number of the references in the events of an equation.
+    ///
+    /// This must be called after every equation of the document was given to
+    /// [`Numbering::number`], so that references to the following equations are resolved.
+    /// References to unknown labels are left unresolved.
+    pub fn resolve(&self, events: &mut [Event]) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        for event in events {
+            let Event::Content(Content::Reference {
+                label,
+                number,
+                parenthesized,
+            }) = event
+            else {
+                continue;
+            };
+            let Some(resolved) = self.labels.get(label.as_ref()) else {
+                warnings.push(Warning::UnresolvedReference(label.to_string()));
+                continue;
+            };
+
+            if self.config.links {
+                *number = Some(Cow::Owned(resolved.clone()));
+            } else {
+                let text = if *parenthesized {
+                    format!("({resolved})")
+                } else {
+                    resolved.clone()
+                };
+                *event = Event::Content(Content::Text(text.into()));
+            }
+        }
+        warnings
+    }
+
+    /// Give a number to the row if it needs one, and record the number of its labels.
+    fn finish_row(&mut self, row: Row, output: &mut Vec<Event>, warnings: &mut Vec<Warning>) {
+        let number = match row.tag {
+            Some(tag) => Some(tag),
+            None if row.numbered && !row.no_number && (!row.empty || !row.labels.is_empty()) => {
+                let number = self.next_number();
+                output.push(Event::EnvironmentFlow(EnvironmentFlow::Tag {
+                    tag: Cow::Owned(number.clone()),
+                    parenthesized: true,
+                }));
+                Some(number)
+            }
+            None => None,
+        };
+
+        for label in row.labels {
+            let Some(number) = &number else {
+                warnings.push(Warning::UnnumberedLabel(label));
+                continue;
+            };
+            match self.labels.entry(label) {
+                Entry::Occupied(entry) => {
+                    warnings.push(Warning::DuplicateLabel(entry.key().clone()))
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(number.clone());
+                }
+            }
+        }
+    }
+
+    fn next_number(&mut self) -> String {
+        if self.subequation.is_none() {
+            self.equation += 1;
+        }
+        let mut number = if self.config.per_section {
+            format!("{}.{}", self.section, self.equation)
+        } else {
+            self.equation.to_string()
+        };
+        if let Some(subequation) = &mut self.subequation {
+            *subequation += 1;
+            push_letters(&mut number, *subequation);
+        }
+        number
+    }
+}
+
+/// The state of the current row of an environment.
+struct Row {
+    /// Whether the rows of the environment are numbered.
+    numbered: bool,
+    /// Whether the environment is numbered as a whole rather than by row, e.g., `multline`.
+    single: bool,
+    /// The index of the `Begin` event of the environment in the output.
+    begin: usize,
+    empty: bool,
+    tag: Option<String>,
+    no_number: bool,
+    labels: Vec<String>,
+}
+
+impl Row {
+    fn new(grouping: &Grouping, begin: usize) -> Self {
+        let numbered = match grouping {
+            Grouping::Equation { eq_numbers }
+            | Grouping::Align { eq_numbers }
+            | Grouping::Alignat { eq_numbers, .. }
+            | Grouping::Gather { eq_numbers } => *eq_numbers,
+            Grouping::Multline => true,
+            _ => false,
+        };
+        Self {
+            numbered,
+            single: matches!(grouping, Grouping::Equation { .. } | Grouping::Multline),
+            begin,
+            empty: true,
+            tag: None,
+            no_number: false,
+            labels: Vec::new(),
+        }
+    }
+}
+
+/// Mark an environment as unnumbered.
+fn unnumber(grouping: &mut Grouping) {
+    if let Grouping::Equation { eq_numbers }
+    | Grouping::Align { eq_numbers }
+    | Grouping::Alignat { eq_numbers, .. }
+    | Grouping::Gather { eq_numbers } = grouping
+    {
+        *eq_numbers = false;
+    }
+}
+
+/// Push the letters numbering a subequation, i.e., `a` to `z`, then `aa`, `ab`, etc.
+fn push_letters(string: &mut String, mut number: u32) {
+    let start = string.len();
+    while number > 0 {
+        number -= 1;
+        string.insert(start, char::from(b'a' + (number % 26) as u8));
+        number /= 26;
+    }
+}
+
+/// A problem found while numbering equations or resolving references.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// A label was attached to more than one row. The first row keeps the label.
+    DuplicateLabel(String),
+    /// A label was attached to a row without a number, so it cannot be referred to.
+    UnnumberedLabel(String),
+    /// A reference refers to a label that was never found.
+    UnresolvedReference(String),
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::DuplicateLabel(label) => write!(f, "label `{label}` is multiply defined"),
+            Warning::UnnumberedLabel(label) => {
+                write!(f, "label `{label}` is attached to an unnumbered row")
+            }
+            Warning::UnresolvedReference(label) => {
+                write!(f, "reference to undefined label `{label}`")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser, Storage};
+
+    use super::*;
+
+    fn events<'a>(input: &'a str, storage: &'a Storage) -> Vec<Event<'a>> {
+        Parser::new(input, storage)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    fn tags(events: &[Event]) -> Vec<String> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                Event::EnvironmentFlow(EnvironmentFlow::Tag { tag, .. }) => Some(tag.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rows() {
+        let storage = Storage::new();
+        let mut numbering = Numbering::new();
+
+        let mut equation = events(r"\begin{equation} a \\ b \end{equation}", &storage);
+        assert!(numbering.number(&mut equation).is_empty());
+        assert_eq!(tags(&equation), ["1"]);
+        assert_eq!(
+            equation[0],
+            Event::Begin(Grouping::Equation { eq_numbers: false })
+        );
+
+        let mut align = events(
+            r"\begin{align} a \\ b \notag \\ c \tag{*} \\ \begin{aligned} d \\ e \end{aligned} \\ \end{align}",
+            &storage,
+        );
+        assert!(numbering.number(&mut align).is_empty());
+        assert_eq!(tags(&align), ["2", "*", "3"]);
+
+        let mut unnumbered = events(r"\begin{gather*} a \\ b \end{gather*} x", &storage);
+        let expected = unnumbered.clone();
+        assert!(numbering.number(&mut unnumbered).is_empty());
+        assert_eq!(unnumbered, expected);
+
+        let mut multline = events(r"\begin{multline} a \\ b \end{multline}", &storage);
+        assert!(numbering.number(&mut multline).is_empty());
+        assert_eq!(tags(&multline), ["4"]);
+    }
+
+    #[test]
+    fn sections_and_subequations() {
+        let storage = Storage::new();
+        let mut numbering = Numbering::with_config(NumberingConfig {
+            per_section: true,
+            ..NumberingConfig::default()
+        });
+        let mut numbers = Vec::new();
+        let mut number = |numbering: &mut Numbering, input| {
+            let mut events = events(input, &storage);
+            numbering.number(&mut events);
+            numbers.extend(tags(&events));
+        };
+
+        number(&mut numbering, r"\begin{equation} a \end{equation}");
+        numbering.section();
+        number(&mut numbering, r"\begin{gather} a \\ b \end{gather}");
+        numbering.begin_subequations();
+        number(&mut numbering, r"\begin{align} a \\ b \end{align}");
+        number(&mut numbering, r"\begin{equation} a \end{equation}");
+        numbering.end_subequations();
+        number(&mut numbering, r"\begin{equation} a \end{equation}");
+        numbering.section();
+        number(&mut numbering, r"\begin{equation} a \end{equation}");
+
+        assert_eq!(
+            numbers,
+            ["0.1", "1.1", "1.2", "1.3a", "1.3b", "1.3c", "1.4", "2.1"]
+        );
+
+        let mut letters = String::new();
+        for number in [1, 26, 27, 52, 703] {
+            push_letters(&mut letters, number);
+            letters.push(' ');
+        }
+        assert_eq!(letters, "a z aa az aaa ");
+    }
+
+    #[test]
+    fn references() {
+        let storage = Storage::new();
+        let mut numbering = Numbering::new();
+
+        let mut first = events(
+            r"\begin{align} a \label{a} \\ b \tag{B} \label{b} \end{align} \eqref{c} \ref{d}",
+            &storage,
+        );
+        let mut second = events(
+            r"\begin{gather} c \label{c} \\ d \notag \label{d} \\ e \label{a} \end{gather}",
+            &storage,
+        );
+        assert!(numbering.number(&mut first).is_empty());
+        assert_eq!(
+            numbering.number(&mut second),
+            [
+                Warning::UnnumberedLabel("d".to_string()),
+                Warning::DuplicateLabel("a".to_string())
+            ]
+        );
+        assert_eq!(numbering.label("a"), Some("1"));
+        assert_eq!(numbering.label("b"), Some("B"));
+
+        assert_eq!(
+            numbering.resolve(&mut first),
+            [Warning::UnresolvedReference("d".to_string())]
+        );
+        assert_eq!(
+            first[first.len() - 2..],
+            [
+                Event::Content(Content::Reference {
+                    label: "c".into(),
+                    number: Some("2".into()),
+                    parenthesized: true,
+                }),
+                Event::Content(Content::Reference {
+                    label: "d".into(),
+                    number: None,
+                    parenthesized: false,
+                }),
+            ]
+        );
+
+        let numbering = Numbering {
+            config: NumberingConfig {
+                links: false,
+                ..NumberingConfig::default()
+            },
+            ..numbering
+        };
+        let mut references = events(r"\eqref{b} \ref{c}", &storage);
+        assert!(numbering.resolve(&mut references).is_empty());
+        assert_eq!(
+            references,
+            [
+                Event::Content(Content::Text("(B)".into())),
+                Event::Content(Content::Text("2".into())),
+            ]
+        );
+    }
+}
@@ -7,28 +7,33 @@
 //! [`write_mathml`]: crate::mathml::write_mathml
 pub mod error;
 mod lex;
-mod macros;
+pub mod macros;
 mod primitives;
 mod state;
 pub mod storage;
 mod tables;
+mod text;
 
 use std::ops::Range;
 
-use macros::MacroContext;
+use macros::{MacroContext, MacroSet};
 
-use crate::event::{Event, Grouping, ScriptPosition, ScriptType};
+use crate::{
+    config::ParserConfig,
+    event::{Content, Event, Grouping, ScriptType},
+};
 
 use self::{state::ParserState, storage::Storage};
 
 pub(crate) use error::{ErrorKind, InnerResult, ParserError};
+pub(crate) use primitives::char_event;
+pub(crate) use text::{bold, italic, upright};
 
-// Guard against infinite macro recursion and excessive
-// expansion. Each expansion allocates in the bump arena
-// which is never freed, so we limit both depth and total
-// expansion bytes to prevent OOM.
-const MAX_EXPANSION_DEPTH: usize = 64;
-const MAX_EXPANSION_BYTES: usize = 1024 * 1024; // 1 MB
+/// The maximum depth of the recursion of the parser, which bounds its stack usage.
+///
+/// Both control sequences given as arguments of one another and text flattened from a nested
+/// source (e.g., the name of an operator) count towards this single limit.
+const MAX_NESTING_DEPTH: usize = 64;
 
 /// The parser completes the task of transforming the input `LaTeX` into a symbolic representation,
 /// namely a stream of [`Event`]s.
@@ -49,7 +54,9 @@ pub struct Parser<'store> {
     ///
     /// Instructions are stored backward in this stack, in the sense that the next event to be popped
     /// is the next event to be outputed.
-    instruction_stack: Vec<Instruction<'store>>,
+    ///
+    /// Each instruction is paired with the byte range of the original input it originates from.
+    instruction_stack: Vec<(Instruction<'store>, Range<usize>)>,
 
     /// This buffer serves as a staging area when parsing a command.
     ///
@@ -65,26 +72,90 @@ pub struct Parser<'store> {
 
     /// A stack that serves to provide context when an error occurs.
     span_stack: SpanStack<'store>,
+
+    /// The configuration of the parser.
+    config: ParserConfig,
+
+    /// The number of events emitted so far.
+    event_count: usize,
+
+    /// The current group nesting depth of the emitted events.
+    group_depth: usize,
+
+    /// The number of parsing steps taken so far.
+    step_count: usize,
 }
 
 impl<'store> Parser<'store> {
     /// Create a new parser from the given input string and storage.
     pub fn new<'input>(input: &'input str, storage: &'store Storage) -> Self
+    where
+        'input: 'store,
+    {
+        Self::with_macros(input, storage, &MacroSet::new())
+    }
+
+    /// Create a new parser from the given input string and storage, where the macros of the
+    /// given [`MacroSet`] are defined.
+    ///
+    /// Macros defined while parsing the input do not affect the given [`MacroSet`].
+    pub fn with_macros<'input>(
+        input: &'input str,
+        storage: &'store Storage,
+        macros: &MacroSet<'store>,
+    ) -> Self
+    where
+        'input: 'store,
+    {
+        Self::with_config(input, storage, macros, ParserConfig::default())
+    }
+
+    /// Create a new parser from the given input string, storage, macros and configuration.
+    ///
+    /// An empty [`MacroSet`] can be given if no macros need to be predefined.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use pulldown_latex::{event::Content, Event, MacroSet, Parser, ParserConfig, Storage};
+    ///
+    /// let storage = Storage::new();
+    /// let config = ParserConfig {
+    ///     error_recovery: true,
+    ///     ..Default::default()
+    /// };
+    /// let parser = Parser::with_config(r"a + \typo", &storage, &MacroSet::new(), config);
+    /// let events = parser.collect::<Result<Vec<_>, _>>().unwrap();
+    /// assert!(matches!(events[2], Event::Content(Content::Error { .. })));
+    /// ```
+    pub fn with_config<'input>(
+        input: &'input str,
+        storage: &'store Storage,
+        macros: &MacroSet<'store>,
+        config: ParserConfig,
+    ) -> Self
     where
         'input: 'store,
     {
         let mut instruction_stack = Vec::with_capacity(32);
-        instruction_stack.push(Instruction::SubGroup {
-            content: input,
-            allowed_alignment_count: None,
-        });
+        instruction_stack.push((
+            Instruction::SubGroup {
+                content: input,
+                allowed_alignment_count: None,
+            },
+            0..input.len(),
+        ));
         let buffer = Vec::with_capacity(16);
         Self {
             instruction_stack,
             buffer,
-            macro_context: MacroContext::new(),
+            macro_context: macros.context.clone(),
             storage: &storage.0,
             span_stack: SpanStack::from_input(input),
+            config,
+            event_count: 0,
+            group_depth: 0,
+            step_count: 0,
         }
     }
 }
@@ -93,95 +164,270 @@ impl<'store> Iterator for Parser<'store> {
     type Item = Result<Event<'store>, ParserError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.instruction_stack.last_mut() {
-            Some(Instruction::Event(_)) => Some(Ok(self
-                .instruction_stack
-                .pop()
-                .and_then(|i| match i {
-                    Instruction::Event(e) => Some(e),
-                    _ => None,
-                })
-                .expect("there is something in the stack"))),
-            Some(Instruction::SubGroup { content, .. }) if content.trim_start().is_empty() => {
-                self.instruction_stack.pop();
-                self.next()
+        self.next_with_span().map(|(item, _)| item)
+    }
+}
+
+impl<'store> Parser<'store> {
+    /// Consume the parser and return the macros defined while parsing, along with the macros it
+    /// was created with.
+    ///
+    /// This allows for definitions made in one equation to be used in the following ones, by
+    /// giving the returned [`MacroSet`] to [`Parser::with_macros`]. Only the definitions
+    /// encountered up to this point are returned, so the parser should usually be exhausted
+    /// beforehand.
+    ///
+    /// The definitions may refer to macro expansions allocated in the [`Storage`], and thus the
+    /// returned [`MacroSet`] is valid as long as the storage is.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use pulldown_latex::{MacroSet, Parser, Storage};
+    ///
+    /// let storage = Storage::new();
+    /// let mut macros = MacroSet::new();
+    /// for input in [r"\newcommand{\R}{\mathbb{R}} x \in \R", r"y \in \R"] {
+    ///     let mut parser = Parser::with_macros(input, &storage, &macros);
+    ///     assert!(parser.by_ref().all(|event| event.is_ok()));
+    ///     macros = parser.into_macros();
+    /// }
+    /// ```
+    pub fn into_macros(self) -> MacroSet<'store> {
+        MacroSet {
+            context: self.macro_context,
+        }
+    }
+
+    /// Consume the parser and return an iterator over the events along with the byte range of the
+    /// original input that produced them.
+    ///
+    /// Events produced by a macro expansion are given the span of the macro's call site in the
+    /// original input. Events that delimit a group or an argument (e.g., [`Event::Begin`] and
+    /// [`Event::End`]) are given the span of the whole command or group that produced them.
+    pub fn into_offset_iter(self) -> OffsetIter<'store> {
+        OffsetIter { parser: self }
+    }
+
+    fn next_with_span(&mut self) -> Option<(Result<Event<'store>, ParserError>, Range<usize>)> {
+        if self
+            .config
+            .max_input_length
+            .is_some_and(|max| self.span_stack.input.len() > max)
+            && !self.instruction_stack.is_empty()
+        {
+            return Some(self.abort(ErrorKind::InputLengthLimit, 0..self.span_stack.input.len()));
+        }
+
+        let (item, span) = self.next_unchecked()?;
+        if let Ok(event) = &item {
+            self.event_count += 1;
+            match event {
+                Event::Begin(_) => self.group_depth += 1,
+                Event::End => self.group_depth = self.group_depth.saturating_sub(1),
+                _ => {}
             }
-            Some(Instruction::SubGroup {
-                content,
-                allowed_alignment_count,
-                ..
-            }) => {
-                let state = ParserState {
-                    allowed_alignment_count: allowed_alignment_count.as_mut(),
-                    ..Default::default()
-                };
 
-                let inner = InnerParser {
-                    content,
-                    buffer: &mut self.buffer,
-                    state,
-                    macro_context: &mut self.macro_context,
-                    storage: self.storage,
-                    span_stack: &mut self.span_stack,
-                };
+            if self
+                .config
+                .max_events
+                .is_some_and(|max| self.event_count > max)
+            {
+                return Some(self.abort(ErrorKind::EventLimit, span));
+            }
+            if self
+                .config
+                .max_group_depth
+                .is_some_and(|max| self.group_depth > max)
+            {
+                return Some(self.abort(ErrorKind::GroupNestingLimit, span));
+            }
+        }
+        Some((item, span))
+    }
+
+    /// Stop parsing altogether, and return an error of the given kind.
+    ///
+    /// This is used when a limit of the [`ParserConfig`] is exceeded.
+    fn abort(
+        &mut self,
+        kind: ErrorKind,
+        span: Range<usize>,
+    ) -> (Result<Event<'store>, ParserError>, Range<usize>) {
+        self.instruction_stack.clear();
+        self.buffer.clear();
+        let place = self.span_stack.input[span.start..].as_ptr();
+        (
+            Err(ParserError::new(
+                kind,
+                place,
+                &mut self.span_stack,
+                span.clone(),
+            )),
+            span,
+        )
+    }
 
-                let (desc, rest) = inner.parse_next();
-                *content = rest;
-
-                let script_event = match desc {
-                    Err(e) => {
-                        let content_str = *content;
-                        return Some(Err(ParserError::new(
-                            e,
-                            content_str.as_ptr(),
-                            &mut self.span_stack,
-                        )));
+    fn next_unchecked(&mut self) -> Option<(Result<Event<'store>, ParserError>, Range<usize>)> {
+        // Instructions that do not produce an event are processed in a loop, rather than
+        // recursively, so that stack usage does not depend on the input.
+        loop {
+            match self.instruction_stack.last_mut() {
+                Some((Instruction::Event(_), _)) => {
+                    return self.instruction_stack.pop().and_then(|(i, span)| match i {
+                        Instruction::Event(e) => Some((Ok(e), span)),
+                        _ => None,
+                    })
+                }
+                Some((Instruction::SubGroup { content, .. }, _))
+                    if content.trim_start().is_empty() =>
+                {
+                    self.instruction_stack.pop();
+                }
+                Some((
+                    Instruction::SubGroup {
+                        content,
+                        allowed_alignment_count,
+                        ..
+                    },
+                    _,
+                )) => {
+                    let start = content.trim_start();
+                    let span_start = self.span_stack.original_offset(start.as_ptr(), false);
+
+                    self.step_count += 1;
+                    if self
+                        .config
+                        .step_budget
+                        .is_some_and(|budget| self.step_count > budget)
+                    {
+                        return Some(self.abort(ErrorKind::StepLimit, span_start..span_start));
                     }
-                    Ok(Some((e, desc))) => {
-                        if desc.subscript_start > desc.superscript_start {
-                            let content = self.buffer.drain(desc.superscript_start..).rev();
-                            let added_len = content.len();
-
-                            self.instruction_stack.reserve(added_len);
-                            let spare =
-                                &mut self.instruction_stack.spare_capacity_mut()[..added_len];
-                            let mut idx = desc.subscript_start - desc.superscript_start;
-
-                            for e in content {
-                                if idx == added_len {
-                                    idx = 0;
-                                }
-                                spare[idx].write(e);
-                                idx += 1;
+                    let state = ParserState {
+                        allowed_alignment_count: allowed_alignment_count.as_mut(),
+                        ..Default::default()
+                    };
+
+                    let inner = InnerParser {
+                        content,
+                        buffer: &mut self.buffer,
+                        state,
+                        macro_context: &mut self.macro_context,
+                        storage: self.storage,
+                        span_stack: &mut self.span_stack,
+                        config: &self.config,
+                        nesting_depth: 0,
+                    };
+
+                    let (desc, rest) = inner.parse_next();
+                    *content = rest;
+                    let span_end = self
+                        .span_stack
+                        .original_offset(rest.as_ptr(), true)
+                        .max(span_start);
+                    let trailing_whitespace = self.span_stack.input.as_bytes()
+                        [span_start..span_end]
+                        .iter()
+                        .rev()
+                        .take_while(|b| b.is_ascii_whitespace())
+                        .count();
+                    let span = span_start..span_end - trailing_whitespace;
+
+                    let script_event = match desc {
+                        Err(e) if self.config.error_recovery => {
+                            // Discard whatever the faulty construct has produced, and make sure that
+                            // parsing advances.
+                            self.buffer.clear();
+                            if rest.as_ptr() == start.as_ptr() {
+                                *content = &start[start.chars().next().map_or(0, char::len_utf8)..];
                             }
+                            let source =
+                                self.span_stack.input.get(span.clone()).unwrap_or_default();
+                            return Some((
+                                Ok(Event::Content(Content::Error {
+                                    kind: e,
+                                    source: source.into(),
+                                })),
+                                span,
+                            ));
+                        }
+                        Err(e) => {
+                            return Some((
+                                Err(ParserError::new(
+                                    e,
+                                    rest.as_ptr(),
+                                    &mut self.span_stack,
+                                    span.clone(),
+                                )),
+                                span,
+                            ));
+                        }
+                        Ok(Some((e, desc))) => {
+                            if desc.subscript_start > desc.superscript_start {
+                                let content = self.buffer.drain(desc.superscript_start..).rev();
+                                let added_len = content.len();
+
+                                self.instruction_stack.reserve(added_len);
+                                let spare =
+                                    &mut self.instruction_stack.spare_capacity_mut()[..added_len];
+                                let mut idx = desc.subscript_start - desc.superscript_start;
+
+                                for e in content {
+                                    if idx == added_len {
+                                        idx = 0;
+                                    }
+                                    spare[idx].write((e, span.clone()));
+                                    idx += 1;
+                                }
 
-                            // Safety: The new length is less than the vector's capacity because we
-                            // reserved `added_len` previously. Every element in the vector up to
-                            // that new length is also initialized by the loop.
-                            unsafe {
-                                self.instruction_stack
-                                    .set_len(self.instruction_stack.len() + added_len)
-                            };
-                        } else {
-                            self.instruction_stack
-                                .extend(self.buffer.drain(desc.subscript_start..).rev());
+                                // Safety: The new length is less than the vector's capacity because we
+                                // reserved `added_len` previously. Every element in the vector up to
+                                // that new length is also initialized by the loop.
+                                unsafe {
+                                    self.instruction_stack
+                                        .set_len(self.instruction_stack.len() + added_len)
+                                };
+                            } else {
+                                self.instruction_stack.extend(
+                                    self.buffer
+                                        .drain(desc.subscript_start..)
+                                        .rev()
+                                        .map(|i| (i, span.clone())),
+                                );
+                            }
+                            Some(e)
                         }
-                        Some(e)
-                    }
-                    Ok(None) => None,
-                };
+                        Ok(None) => None,
+                    };
 
-                self.instruction_stack.extend(self.buffer.drain(..).rev());
-                if let Some(e) = script_event {
-                    self.instruction_stack.push(Instruction::Event(e));
+                    self.instruction_stack
+                        .extend(self.buffer.drain(..).rev().map(|i| (i, span.clone())));
+                    if let Some(e) = script_event {
+                        self.instruction_stack.push((Instruction::Event(e), span));
+                    }
                 }
-                self.next()
+                None => return None,
             }
-            None => None,
         }
     }
 }
 
+/// An iterator over the events of a [`Parser`] and their byte range in the original input.
+///
+/// This struct is created by the [`Parser::into_offset_iter`] method.
+#[derive(Debug)]
+pub struct OffsetIter<'store> {
+    parser: Parser<'store>,
+}
+
+impl<'store> Iterator for OffsetIter<'store> {
+    type Item = (Result<Event<'store>, ParserError>, Range<usize>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.parser.next_with_span()
+    }
+}
+
 #[derive(Debug)]
 struct InnerParser<'b, 'store> {
     content: &'store str,
@@ -190,6 +436,10 @@ struct InnerParser<'b, 'store> {
     macro_context: &'b mut MacroContext<'store>,
     storage: &'store bumpalo::Bump,
     span_stack: &'b mut SpanStack<'store>,
+    config: &'b ParserConfig,
+    /// The depth of the recursion, counting both the control sequences being handled as arguments
+    /// and the text being flattened.
+    nesting_depth: usize,
 }
 
 impl<'b, 'store> InnerParser<'b, 'store> {
@@ -199,11 +449,18 @@ impl<'b, 'store> InnerParser<'b, 'store> {
     fn handle_argument(&mut self, argument: Argument<'store>) -> InnerResult<()> {
         match argument {
             Argument::Token(token) => {
+                // Control sequences given as arguments are handled recursively, so their nesting
+                // must be bounded (e.g., `\sqrt\sqrt\sqrt...x`).
+                if self.nesting_depth >= MAX_NESTING_DEPTH {
+                    return Err(ErrorKind::GroupNestingLimit);
+                }
+                self.nesting_depth += 1;
                 self.state.handling_argument = true;
                 match token {
                     Token::ControlSequence(cs) => self.handle_primitive(cs)?,
                     Token::Character(c) => self.handle_char_token(c)?,
                 };
+                self.nesting_depth -= 1;
             }
             Argument::Group(group) => {
                 self.buffer.extend([
@@ -219,6 +476,63 @@ impl<'b, 'store> InnerParser<'b, 'store> {
         Ok(())
     }
 
+    /// Expand the macro with the given name if it is defined, and return the content to parse
+    /// instead of `rest`, which follows the control sequence starting at `call_site`.
+    fn expand_macro(
+        &mut self,
+        name: &'store str,
+        call_site: &'store str,
+        rest: &'store str,
+    ) -> InnerResult<Option<&'store str>> {
+        let Some(result) = self.macro_context.try_expand_in(name, rest, self.storage) else {
+            return Ok(None);
+        };
+        // Each expansion allocates in the storage, which is never freed while parsing, so we limit
+        // both the depth and the total size of expansions.
+        if self.span_stack.expansions.len() >= self.config.max_expansion_depth {
+            return Err(ErrorKind::MacroRecursionLimit);
+        }
+
+        let (new_content, arguments_consumed_length) = result?;
+        self.span_stack.total_expansion_bytes += new_content.len();
+        if self.span_stack.total_expansion_bytes > self.config.max_expansion_bytes {
+            return Err(ErrorKind::ExpansionSizeLimit);
+        }
+
+        // The whitespace skipped after the control sequence is part of the call site.
+        let call_site_length = call_site.len() - (rest.len() - arguments_consumed_length);
+        self.span_stack
+            .add(new_content, call_site, call_site_length);
+        Ok(Some(new_content))
+    }
+
+    /// Parse the next token and push its events to the buffer, expanding macros until a primitive
+    /// or a character is found. Returns `false` if the content is exhausted.
+    fn handle_next_token(&mut self) -> InnerResult<bool> {
+        loop {
+            let original_content = self.content.trim_start();
+            let token = match lex::token(&mut self.content) {
+                Ok(token) => token,
+                Err(ErrorKind::Token) => return Ok(false),
+                Err(e) => return Err(e),
+            };
+            match token {
+                Token::ControlSequence(cs) => {
+                    if let Some(new_content) =
+                        self.expand_macro(cs, original_content, self.content)?
+                    {
+                        self.content = new_content;
+                        continue;
+                    }
+
+                    self.handle_primitive(cs)?
+                }
+                Token::Character(c) => self.handle_char_token(c)?,
+            };
+            return Ok(true);
+        }
+    }
+
     /// ## Script parsing
     ///
     /// The script parser first checks for directives about script placement, i.e. `\limits` and `\nolimits`,
@@ -228,40 +542,11 @@ impl<'b, 'store> InnerParser<'b, 'store> {
     ///
     /// [amsdocs]: https://mirror.its.dal.ca/ctan/macros/latex/required/amsmath/amsldoc.pdf
     fn parse(&mut self) -> InnerResult<Option<(Event<'store>, ScriptDescriptor)>> {
-        // 1. Parse the next token and output everything to the staging stack.
-        let original_content = self.content.trim_start();
-        let token = match lex::token(&mut self.content) {
-            Ok(token) => token,
-            Err(ErrorKind::Token) => return Ok(None),
-            Err(e) => return Err(e),
-        };
-        match token {
-            Token::ControlSequence(cs) => {
-                if let Some(result) =
-                    self.macro_context
-                        .try_expand_in(cs, self.content, self.storage)
-                {
-                    if self.span_stack.expansions.len() >= MAX_EXPANSION_DEPTH
-                        || self.span_stack.total_expansion_bytes >= MAX_EXPANSION_BYTES
-                    {
-                        return Err(ErrorKind::MacroRecursionLimit);
-                    }
-
-                    let (new_content, arguments_consumed_length) = result?;
-                    self.span_stack.total_expansion_bytes += new_content.len();
-
-                    let call_site_length = cs.len() + arguments_consumed_length + 1;
-                    self.span_stack
-                        .add(new_content, original_content, call_site_length);
-
-                    self.content = new_content;
-                    return self.parse();
-                }
-
-                self.handle_primitive(cs)?
-            }
-            Token::Character(c) => self.handle_char_token(c)?,
-        };
+        // 1. Parse the next token and output everything to the staging stack, expanding macros
+        // until a primitive or a character is found.
+        if !self.handle_next_token()? {
+            return Ok(None);
+        }
 
         // 2. Check for scripts, to complete the atom.
         if self.state.skip_scripts {
@@ -269,12 +554,8 @@ impl<'b, 'store> InnerParser<'b, 'store> {
         }
 
         if self.state.allow_script_modifiers {
-            if let Some(limits) = lex::limit_modifiers(&mut self.content) {
-                if limits {
-                    self.state.script_position = ScriptPosition::AboveBelow;
-                } else {
-                    self.state.script_position = ScriptPosition::Right;
-                }
+            if let Some(position) = lex::limit_modifiers(&mut self.content) {
+                self.state.script_position = position;
             }
         }
 
@@ -425,15 +706,32 @@ enum Instruction<'a> {
 struct AlignmentCount {
     count: u16,
     max: u16,
+    /// Whether the environment allows `\tag`, `\notag` and `\label`.
+    tags: bool,
+    /// Whether the current line has a tag.
+    tagged: bool,
 }
 
 impl AlignmentCount {
     fn new(max: u16) -> Self {
-        Self { count: 0, max }
+        Self {
+            count: 0,
+            max,
+            tags: false,
+            tagged: false,
+        }
+    }
+
+    fn with_tags(max: u16) -> Self {
+        Self {
+            tags: true,
+            ..Self::new(max)
+        }
     }
 
     fn reset(&mut self) {
         self.count = 0;
+        self.tagged = false;
     }
 
     fn increment(&mut self) {
@@ -500,6 +798,32 @@ impl<'store> SpanStack<'store> {
         assert!(ptr_val >= input_start && ptr_val <= input_start + self.input.len() as isize);
         (ptr_val - input_start) as usize
     }
+
+    /// Map a pointer into the input, or into one of the expansions currently on the stack, to a
+    /// byte offset in the original input.
+    fn original_offset(&mut self, ptr: *const u8, end: bool) -> usize {
+        let index = self.reach_original_call_site(ptr);
+        self.map_to_input(self.expansions.len(), index, end)
+    }
+
+    /// Map an index in the string at the given depth of the stack to a byte offset in the original
+    /// input, where depth `0` is the original input and depth `n` is the `n`th expansion.
+    ///
+    /// An index located inside the expanded part of a macro is mapped to the beginning of the
+    /// macro's call site, or to its end if `end` is set.
+    fn map_to_input(&self, depth: usize, mut index: usize, end: bool) -> usize {
+        for expansion in self.expansions[..depth].iter().rev() {
+            let call_site = &expansion.call_site_in_origin;
+            index = if index >= expansion.expansion_length {
+                call_site.end + (index - expansion.expansion_length)
+            } else if end {
+                call_site.end
+            } else {
+                call_site.start
+            };
+        }
+        index
+    }
 }
 
 /// A span of the input string. Used for error reporting.
@@ -528,7 +852,10 @@ struct ExpansionSpan<'a> {
 
 #[cfg(test)]
 mod tests {
-    use crate::event::{Content, DelimiterType, Dimension, DimensionUnit, RelationContent, Visual};
+    use crate::event::{
+        ColumnAlignment, Content, DelimiterType, Dimension, DimensionUnit, EnvironmentFlow, Font,
+        Framing, RelationContent, ScriptPosition, StateChange, Style, Visual,
+    };
 
     use super::*;
 
@@ -577,14 +904,14 @@ mod tests {
                     content: 'a',
                     stretchy: false,
                 }),
-                Event::Content(Content::Number("2")),
+                Event::Content(Content::Number("2".into())),
                 Event::Begin(Grouping::Normal),
-                Event::Content(Content::Number("1")),
+                Event::Content(Content::Number("1".into())),
                 Event::Content(Content::BinaryOp {
                     content: '+',
                     small: false
                 }),
-                Event::Content(Content::Number("3")),
+                Event::Content(Content::Number("3".into())),
                 Event::End,
             ]
         );
@@ -611,68 +938,68 @@ mod tests {
                     ty: ScriptType::Subscript,
                     position: ScriptPosition::Right
                 },
-                Event::Content(Content::Number("5")),
+                Event::Content(Content::Number("5".into())),
                 Event::Begin(Grouping::Normal),
                 Event::Script {
                     ty: ScriptType::Subscript,
                     position: ScriptPosition::Right
                 },
-                Event::Content(Content::Number("5")),
+                Event::Content(Content::Number("5".into())),
                 Event::Begin(Grouping::Normal),
                 Event::Script {
                     ty: ScriptType::Subscript,
                     position: ScriptPosition::Right
                 },
-                Event::Content(Content::Number("5")),
+                Event::Content(Content::Number("5".into())),
                 Event::Begin(Grouping::Normal),
                 Event::Script {
                     ty: ScriptType::Subscript,
                     position: ScriptPosition::Right
                 },
-                Event::Content(Content::Number("5")),
+                Event::Content(Content::Number("5".into())),
                 Event::Begin(Grouping::Normal),
                 Event::Script {
                     ty: ScriptType::Subscript,
                     position: ScriptPosition::Right
                 },
-                Event::Content(Content::Number("5")),
+                Event::Content(Content::Number("5".into())),
                 Event::Begin(Grouping::Normal),
                 Event::Script {
                     ty: ScriptType::Subscript,
                     position: ScriptPosition::Right
                 },
-                Event::Content(Content::Number("5")),
+                Event::Content(Content::Number("5".into())),
                 Event::Begin(Grouping::Normal),
                 Event::Script {
                     ty: ScriptType::Subscript,
                     position: ScriptPosition::Right
                 },
-                Event::Content(Content::Number("5")),
+                Event::Content(Content::Number("5".into())),
                 Event::Begin(Grouping::Normal),
                 Event::Script {
                     ty: ScriptType::Subscript,
                     position: ScriptPosition::Right
                 },
-                Event::Content(Content::Number("5")),
+                Event::Content(Content::Number("5".into())),
                 Event::Begin(Grouping::Normal),
                 Event::Script {
                     ty: ScriptType::Subscript,
                     position: ScriptPosition::Right
                 },
-                Event::Content(Content::Number("5")),
+                Event::Content(Content::Number("5".into())),
                 Event::Begin(Grouping::Normal),
                 Event::Script {
                     ty: ScriptType::Subscript,
                     position: ScriptPosition::Right
                 },
-                Event::Content(Content::Number("5")),
+                Event::Content(Content::Number("5".into())),
                 Event::Begin(Grouping::Normal),
                 Event::Script {
                     ty: ScriptType::Subscript,
                     position: ScriptPosition::Right
                 },
-                Event::Content(Content::Number("5")),
-                Event::Content(Content::Number("5")),
+                Event::Content(Content::Number("5".into())),
+                Event::Content(Content::Number("5".into())),
                 Event::End,
                 Event::End,
                 Event::End,
@@ -703,13 +1030,13 @@ mod tests {
                 },
                 Event::Visual(Visual::Fraction(None)),
                 Event::Begin(Grouping::Normal),
-                Event::Content(Content::Number("1")),
+                Event::Content(Content::Number("1".into())),
                 Event::End,
                 Event::Begin(Grouping::Normal),
-                Event::Content(Content::Number("2")),
+                Event::Content(Content::Number("2".into())),
                 Event::End,
-                Event::Content(Content::Number("2")),
-                Event::Content(Content::Number("4")),
+                Event::Content(Content::Number("2".into())),
+                Event::Content(Content::Number("4".into())),
             ]
         );
     }
@@ -720,7 +1047,7 @@ mod tests {
         let parser = Parser::new("123", &store);
         let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
 
-        assert_eq!(events, vec![Event::Content(Content::Number("123"))]);
+        assert_eq!(events, vec![Event::Content(Content::Number("123".into()))]);
     }
 
     #[test]
@@ -736,8 +1063,8 @@ mod tests {
             events,
             vec![
                 Event::Visual(Visual::Fraction(None)),
-                Event::Content(Content::Number("1")),
-                Event::Content(Content::Number("2")),
+                Event::Content(Content::Number("1".into())),
+                Event::Content(Content::Number("2".into())),
             ]
         );
     }
@@ -753,8 +1080,8 @@ mod tests {
             events,
             vec![
                 Event::Visual(Visual::SquareRoot),
-                Event::Content(Content::Number("1")),
-                Event::Content(Content::Number("2")),
+                Event::Content(Content::Number("1".into())),
+                Event::Content(Content::Number("2".into())),
             ]
         );
     }
@@ -772,9 +1099,9 @@ mod tests {
             events,
             vec![
                 Event::Visual(Visual::Fraction(None)),
-                Event::Content(Content::Number("1")),
+                Event::Content(Content::Number("1".into())),
                 Event::Content(Content::Punctuation('.')),
-                Event::Content(Content::Number("5")),
+                Event::Content(Content::Number("5".into())),
                 Event::Begin(Grouping::Normal),
                 Event::Content(Content::Ordinary {
                     content: 'x',
@@ -809,7 +1136,7 @@ mod tests {
                     ty: ScriptType::Subscript,
                     position: ScriptPosition::Right
                 },
-                Event::Content(Content::Function("lim")),
+                Event::Content(Content::Function("lim".into())),
                 Event::Begin(Grouping::Normal),
                 Event::Content(Content::Ordinary {
                     content: 'x',
@@ -819,7 +1146,7 @@ mod tests {
                     content: RelationContent::single_char('→'),
                     small: false
                 }),
-                Event::Content(Content::Number("0")),
+                Event::Content(Content::Number("0".into())),
                 Event::End,
                 Event::Content(Content::Ordinary {
                     content: 'f',
@@ -853,6 +1180,799 @@ mod tests {
         assert!(parser.by_ref().collect::<Result<Vec<_>, _>>().is_err());
         assert!(parser.span_stack.expansions.is_empty());
     }
+
+    #[test]
+    fn error_recovery() {
+        let store = Storage::new();
+        let config = ParserConfig {
+            error_recovery: true,
+            ..Default::default()
+        };
+        let parser = Parser::with_config(
+            r"\begin{align} a & \foo \\ \frac{1} \end{align} x",
+            &store,
+            &MacroSet::new(),
+            config,
+        );
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Begin(Grouping::Align { eq_numbers: true }),
+                Event::Content(Content::Ordinary {
+                    content: 'a',
+                    stretchy: false
+                }),
+                Event::EnvironmentFlow(crate::event::EnvironmentFlow::Alignment),
+                Event::Content(Content::Error {
+                    kind: ErrorKind::UnknownPrimitive,
+                    source: r"\foo".into()
+                }),
+                Event::EnvironmentFlow(crate::event::EnvironmentFlow::NewLine {
+                    spacing: None,
+                    horizontal_lines: Box::new([])
+                }),
+                Event::Content(Content::Error {
+                    kind: ErrorKind::Token,
+                    source: r"\frac{1}".into()
+                }),
+                Event::End,
+                Event::Content(Content::Ordinary {
+                    content: 'x',
+                    stretchy: false
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn error_recovery_unbalanced() {
+        let store = Storage::new();
+        let config = ParserConfig {
+            error_recovery: true,
+            ..Default::default()
+        };
+        for input in [r"{a", "}", r"\left( x", r"x \right)", "a^b^c", "$#&"] {
+            let parser = Parser::with_config(input, &store, &MacroSet::new(), config);
+            let mut depth = 0usize;
+            for event in parser {
+                match event.unwrap() {
+                    Event::Begin(_) => depth += 1,
+                    Event::End => depth = depth.checked_sub(1).unwrap(),
+                    _ => {}
+                }
+            }
+            assert_eq!(depth, 0, "unbalanced events for input: {input}");
+        }
+    }
+
+    #[test]
+    fn offsets() {
+        let store = Storage::new();
+        let input = r"a + \frac{1}{x}";
+        let parser = Parser::new(input, &store);
+        let spans = parser
+            .into_offset_iter()
+            .map(|(e, span)| (e.unwrap(), &input[span]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            spans,
+            vec![
+                (
+                    Event::Content(Content::Ordinary {
+                        content: 'a',
+                        stretchy: false
+                    }),
+                    "a"
+                ),
+                (
+                    Event::Content(Content::BinaryOp {
+                        content: '+',
+                        small: false
+                    }),
+                    "+"
+                ),
+                (Event::Visual(Visual::Fraction(None)), r"\frac{1}{x}"),
+                (Event::Begin(Grouping::Normal), r"\frac{1}{x}"),
+                (Event::Content(Content::Number("1".into())), "1"),
+                (Event::End, r"\frac{1}{x}"),
+                (Event::Begin(Grouping::Normal), r"\frac{1}{x}"),
+                (
+                    Event::Content(Content::Ordinary {
+                        content: 'x',
+                        stretchy: false
+                    }),
+                    "x"
+                ),
+                (Event::End, r"\frac{1}{x}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn offsets_in_expansions() {
+        let store = Storage::new();
+        let input = r"\def\abc#1{#1+y} \abc x c";
+        let parser = Parser::new(input, &store);
+        let spans = parser
+            .into_offset_iter()
+            .map(|(e, span)| (e.unwrap(), &input[span]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            spans,
+            vec![
+                (
+                    Event::Content(Content::Ordinary {
+                        content: 'x',
+                        stretchy: false
+                    }),
+                    r"\abc x"
+                ),
+                (
+                    Event::Content(Content::BinaryOp {
+                        content: '+',
+                        small: false
+                    }),
+                    r"\abc x"
+                ),
+                (
+                    Event::Content(Content::Ordinary {
+                        content: 'y',
+                        stretchy: false
+                    }),
+                    r"\abc x"
+                ),
+                (
+                    Event::Content(Content::Ordinary {
+                        content: 'c',
+                        stretchy: false
+                    }),
+                    "c"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn offsets_of_errors() {
+        let store = Storage::new();
+        let input = r"a \undefined b";
+        let parser = Parser::new(input, &store);
+        let (error, span) = parser.into_offset_iter().nth(1).unwrap();
+
+        assert!(error.is_err());
+        assert_eq!(&input[span], r"\undefined");
+    }
+
+    #[test]
+    fn text_nested_math() {
+        let store = Storage::new();
+        let parser = Parser::new(r"\text{if $x > 0$ or \(y\)}", &store);
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Begin(Grouping::Normal),
+                Event::Content(Content::Text("if ".into())),
+                Event::Begin(Grouping::Normal),
+                Event::StateChange(StateChange::Font(None)),
+                Event::Content(Content::Ordinary {
+                    content: 'x',
+                    stretchy: false,
+                }),
+                Event::Content(Content::Relation {
+                    content: RelationContent::single_char('>'),
+                    small: false,
+                }),
+                Event::Content(Content::Number("0".into())),
+                Event::End,
+                Event::Content(Content::Text(" or ".into())),
+                Event::Begin(Grouping::Normal),
+                Event::StateChange(StateChange::Font(None)),
+                Event::Content(Content::Ordinary {
+                    content: 'y',
+                    stretchy: false,
+                }),
+                Event::End,
+                Event::End,
+            ]
+        );
+    }
+
+    #[test]
+    fn text_control_sequences() {
+        let store = Storage::new();
+        let parser = Parser::new(r"\def\name{Euler} \text{\name's \bf number}", &store);
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+
+        // Macros are expanded, and unknown control sequences are displayed as is.
+        assert_eq!(
+            events,
+            vec![Event::Content(Content::Text("Euler’s \\bf number".into()))]
+        );
+    }
+
+    #[test]
+    fn text_spaces_and_rules() {
+        let store = Storage::new();
+        let parser = Parser::new(r"\text{a\hspace{1em}b \rule[1pt]{2pt}{3pt}}", &store);
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Begin(Grouping::Normal),
+                Event::Content(Content::Text("a".into())),
+                Event::Space {
+                    width: Some(Dimension::new(1., DimensionUnit::Em)),
+                    height: None,
+                    depth: None,
+                },
+                Event::Content(Content::Text("b ".into())),
+                Event::Visual(Visual::Raise(Dimension::new(1., DimensionUnit::Pt))),
+                Event::Rule {
+                    width: Dimension::new(2., DimensionUnit::Pt),
+                    height: Dimension::new(3., DimensionUnit::Pt),
+                    depth: Dimension::new(0., DimensionUnit::Em),
+                },
+                Event::End,
+            ]
+        );
+    }
+
+    #[test]
+    fn text_fonts() {
+        let store = Storage::new();
+        let parser = Parser::new(r"\textbf{a \textit{b} \emph{c \emph{d}}}", &store);
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Begin(Grouping::Normal),
+                Event::StateChange(StateChange::Font(Some(Font::Bold))),
+                Event::Content(Content::Text("a ".into())),
+                Event::Begin(Grouping::Normal),
+                Event::StateChange(StateChange::Font(Some(Font::BoldItalic))),
+                Event::Content(Content::Text("b".into())),
+                Event::End,
+                Event::Content(Content::Text(" ".into())),
+                Event::Begin(Grouping::Normal),
+                Event::StateChange(StateChange::Font(Some(Font::BoldItalic))),
+                Event::Content(Content::Text("c ".into())),
+                Event::Begin(Grouping::Normal),
+                Event::StateChange(StateChange::Font(Some(Font::Bold))),
+                Event::Content(Content::Text("d".into())),
+                Event::End,
+                Event::End,
+                Event::End,
+            ]
+        );
+    }
+
+    #[test]
+    fn text_accents_ligatures_and_spaces() {
+        let store = Storage::new();
+        let parser = Parser::new(
+            r#"\text{caf\'e \c{c}a, \v s, \^{}, ``quoted'' -- --- it's\ a~b\quad\$5 {\"o}}"#,
+            &store,
+        );
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+
+        assert_eq!(
+            events,
+            vec![Event::Content(Content::Text(
+                "cafe\u{301} c\u{327}a, s\u{30C}, ^, “quoted” – — it’s a\u{A0}b\u{2003}$5 o\u{308}"
+                    .into()
+            ))]
+        );
+    }
+
+    #[test]
+    fn text_as_script_base() {
+        let store = Storage::new();
+        let parser = Parser::new(r"\text{a $b$}^2", &store);
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+
+        assert_eq!(
+            events[..2],
+            [
+                Event::Script {
+                    ty: ScriptType::Superscript,
+                    position: ScriptPosition::Right
+                },
+                Event::Begin(Grouping::Normal),
+            ]
+        );
+        assert_eq!(events[events.len() - 2], Event::End);
+        assert_eq!(
+            events[events.len() - 1],
+            Event::Content(Content::Number("2".into()))
+        );
+    }
+
+    #[test]
+    fn operator_names() {
+        let store = Storage::new();
+        let parser = Parser::new(
+            r"\operatorname*{arg\,max}_x \operatorname{\text{a b}\alpha-2} \sum\displaylimits",
+            &store,
+        );
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Script {
+                    ty: ScriptType::Subscript,
+                    position: ScriptPosition::Movable
+                },
+                Event::Content(Content::Function("arg max".into())),
+                Event::Content(Content::Ordinary {
+                    content: 'x',
+                    stretchy: false
+                }),
+                Event::Content(Content::Function("a bα-2".into())),
+                Event::Content(Content::LargeOp {
+                    content: '∑',
+                    small: false
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn primitive_table() {
+        use tables::PRIMITIVES;
+
+        assert!(PRIMITIVES.windows(2).all(|pair| pair[0] < pair[1]));
+        let storage = Storage::new();
+        for name in PRIMITIVES {
+            let input = format!("\\{name}");
+            let first = Parser::new(&input, &storage).next();
+            assert!(
+                !matches!(first, Some(Err(e)) if *e.kind() == ErrorKind::UnknownPrimitive),
+                "{name}"
+            );
+        }
+
+        // Every control sequence matched by `handle_primitive` must be listed.
+        let source = include_str!("parser/primitives.rs");
+        let start = source.find("fn handle_primitive").unwrap();
+        let end = start + source[start..].find("\n    }\n").unwrap();
+        for line in source[start..end].lines() {
+            let Some(pattern) = line.strip_prefix("            ") else {
+                continue;
+            };
+            let pattern = pattern.strip_prefix("| ").unwrap_or(pattern);
+            if !pattern.starts_with('"') {
+                continue;
+            }
+            let pattern = pattern.split(" => ").next().unwrap();
+            let pattern = pattern.split(" if ").next().unwrap();
+            for name in pattern.split('"').skip(1).step_by(2) {
+                let name = name.replace(r"\\", r"\");
+                assert!(tables::is_primitive(&name), "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn environment_table() {
+        use tables::ENVIRONMENTS;
+
+        assert!(ENVIRONMENTS.windows(2).all(|pair| pair[0] < pair[1]));
+        let storage = Storage::new();
+        for name in ENVIRONMENTS {
+            let input = format!(r"\begin{{{name}}}{{c}} x \end{{{name}}}");
+            let first = Parser::new(&input, &storage).next();
+            assert!(
+                !matches!(first, Some(Err(e)) if *e.kind() == ErrorKind::Environment),
+                "{name}"
+            );
+        }
+
+        // Every environment matched by `begin_environment` must be listed.
+        let source = include_str!("parser/primitives.rs");
+        let start = source.find("fn begin_environment").unwrap();
+        let end = start + source[start..].find("\n    }\n").unwrap();
+        for line in source[start..end].lines() {
+            let Some(pattern) = line.strip_prefix("            \"") else {
+                continue;
+            };
+            let name = pattern.split('"').next().unwrap();
+            assert!(tables::is_environment(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn declare_math_operator() {
+        let store = Storage::new();
+        let parser = Parser::new(
+            r"\DeclareMathOperator*{\argmax}{arg\,max} \DeclareMathOperator\tr{tr} \argmax_x \tr_x",
+            &store,
+        );
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+        let x = Event::Content(Content::Ordinary {
+            content: 'x',
+            stretchy: false,
+        });
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Script {
+                    ty: ScriptType::Subscript,
+                    position: ScriptPosition::Movable
+                },
+                Event::Content(Content::Function("arg max".into())),
+                x.clone(),
+                Event::Script {
+                    ty: ScriptType::Subscript,
+                    position: ScriptPosition::Right
+                },
+                Event::Content(Content::Function("tr".into())),
+                x,
+            ]
+        );
+
+        for input in [
+            r"\DeclareMathOperator{\a}{a} \DeclareMathOperator{\a}{b}",
+            r"\DeclareMathOperator{\a}{\frac12} \a",
+            r"\DeclareMathOperator{\sin}{sine}",
+            r"\DeclareMathOperator*{\frac}{frac}",
+            r"\operatorname{x^2}",
+        ] {
+            let parser = Parser::new(input, &store);
+            assert!(parser.collect::<Result<Vec<_>, _>>().is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn tags() {
+        let store = Storage::new();
+        let parser = Parser::new(
+            r"\begin{align} a \tag{1.a} \label{ eq:a } \\ b \nonumber \tag*{$*$ -- \$} \end{align}",
+            &store,
+        );
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+        let ordinary = |content| {
+            Event::Content(Content::Ordinary {
+                content,
+                stretchy: false,
+            })
+        };
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Begin(Grouping::Align { eq_numbers: true }),
+                ordinary('a'),
+                Event::EnvironmentFlow(EnvironmentFlow::Tag {
+                    tag: "1.a".into(),
+                    parenthesized: true
+                }),
+                Event::EnvironmentFlow(EnvironmentFlow::Label("eq:a".into())),
+                Event::EnvironmentFlow(EnvironmentFlow::NewLine {
+                    spacing: None,
+                    horizontal_lines: Box::new([])
+                }),
+                ordinary('b'),
+                Event::EnvironmentFlow(EnvironmentFlow::NoNumber),
+                Event::EnvironmentFlow(EnvironmentFlow::Tag {
+                    tag: "* – $".into(),
+                    parenthesized: false
+                }),
+                Event::End,
+            ]
+        );
+
+        for input in [
+            r"\tag{1}",
+            r"\begin{aligned} a \notag \end{aligned}",
+            r"\begin{equation} {a \label{a}} \end{equation}",
+            r"\begin{gather} a \tag{1} \tag{2} \end{gather}",
+        ] {
+            let parser = Parser::new(input, &store);
+            assert!(parser.collect::<Result<Vec<_>, _>>().is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn frames() {
+        let store = Storage::new();
+        let parser = Parser::new(r"\boxed{x} \fbox{a b} \framebox[2cm][l]{c}", &store);
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Visual(Visual::Framed(Framing::default())),
+                Event::Begin(Grouping::Normal),
+                Event::StateChange(StateChange::Style(Style::Display)),
+                Event::Content(Content::Ordinary {
+                    content: 'x',
+                    stretchy: false
+                }),
+                Event::End,
+                Event::Visual(Visual::Framed(Framing::default())),
+                Event::Content(Content::Text("a b".into())),
+                Event::Visual(Visual::Framed(Framing {
+                    width: Some(Dimension::new(2., DimensionUnit::Cm)),
+                    alignment: ColumnAlignment::Left,
+                })),
+                Event::Content(Content::Text("c".into())),
+            ]
+        );
+
+        for input in [
+            r"\boxed",
+            r"\fbox",
+            r"\framebox[2][c]{x}",
+            r"\framebox[1em][t]{x}",
+        ] {
+            let parser = Parser::new(input, &store);
+            assert!(parser.collect::<Result<Vec<_>, _>>().is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn phantoms_and_smashes() {
+        let store = Storage::new();
+        let parser = Parser::new(r"\hphantom x \vphantom{y} \smash[b]z", &store);
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Visual(Visual::Phantom {
+                    horizontal: true,
+                    vertical: false
+                }),
+                Event::Content(Content::Ordinary {
+                    content: 'x',
+                    stretchy: false
+                }),
+                Event::Visual(Visual::Phantom {
+                    horizontal: false,
+                    vertical: true
+                }),
+                Event::Begin(Grouping::Normal),
+                Event::Content(Content::Ordinary {
+                    content: 'y',
+                    stretchy: false
+                }),
+                Event::End,
+                Event::Visual(Visual::Smash {
+                    top: false,
+                    bottom: true
+                }),
+                Event::Content(Content::Ordinary {
+                    content: 'z',
+                    stretchy: false
+                }),
+            ]
+        );
+
+        for input in [r"\phantom", r"\smash[]{x}", r"\smash[c]{x}"] {
+            let parser = Parser::new(input, &store);
+            assert!(parser.collect::<Result<Vec<_>, _>>().is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn overlaps() {
+        let store = Storage::new();
+        let parser = Parser::new(r"\llap{a b}\mathclap x", &store);
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Visual(Visual::Overlap(ColumnAlignment::Left)),
+                Event::Content(Content::Text("a b".into())),
+                Event::Visual(Visual::Overlap(ColumnAlignment::Center)),
+                Event::Content(Content::Ordinary {
+                    content: 'x',
+                    stretchy: false
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn rules_and_raises() {
+        let store = Storage::new();
+        let parser = Parser::new(
+            r"\rule[-1pt]{0pt}{2em} \lower2pt x \raisebox{0.5ex}{a}",
+            &store,
+        );
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Visual(Visual::Raise(Dimension::new(-1., DimensionUnit::Pt))),
+                Event::Rule {
+                    width: Dimension::new(0., DimensionUnit::Pt),
+                    height: Dimension::new(2., DimensionUnit::Em),
+                    depth: Dimension::new(0., DimensionUnit::Em),
+                },
+                Event::Visual(Visual::Raise(Dimension::new(-2., DimensionUnit::Pt))),
+                Event::Content(Content::Ordinary {
+                    content: 'x',
+                    stretchy: false
+                }),
+                Event::Visual(Visual::Raise(Dimension::new(0.5, DimensionUnit::Ex))),
+                Event::Content(Content::Text("a".into())),
+            ]
+        );
+
+        for input in [r"\rule{1em}", r"\raise x", r"\raisebox{1ex}[0pt]{a}"] {
+            let parser = Parser::new(input, &store);
+            assert!(parser.collect::<Result<Vec<_>, _>>().is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn math_choice() {
+        let store = Storage::new();
+        let parser = Parser::new(
+            r"\newcommand{\choice}[1]{\mathchoice{#1 D}{#1 T}{S}{SS}}\choice\alpha",
+            &store,
+        );
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+
+        assert_eq!(events[0], Event::Visual(Visual::MathChoice));
+        let alpha = Event::Content(Content::Ordinary {
+            content: 'α',
+            stretchy: false,
+        });
+        assert_eq!(events.iter().filter(|event| **event == alpha).count(), 2);
+        assert_eq!(
+            events[events.len() - 4..],
+            [
+                Event::Begin(Grouping::Normal),
+                Event::Content(Content::Ordinary {
+                    content: 'S',
+                    stretchy: false
+                }),
+                Event::Content(Content::Ordinary {
+                    content: 'S',
+                    stretchy: false
+                }),
+                Event::End,
+            ]
+        );
+
+        let parser = Parser::new(r"\mathchoice{a}{b}{c}", &store);
+        assert!(parser.collect::<Result<Vec<_>, _>>().is_err());
+    }
+
+    #[test]
+    fn boxes() {
+        let store = Storage::new();
+        let parser = Parser::new(r"\mbox{ if $x$}\hbox{a}\vcenter{x}", &store);
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Visual(Visual::HorizontalBox),
+                Event::Begin(Grouping::Normal),
+                Event::Content(Content::Text(" if ".into())),
+                Event::Begin(Grouping::Normal),
+                Event::StateChange(StateChange::Font(None)),
+                Event::Content(Content::Ordinary {
+                    content: 'x',
+                    stretchy: false
+                }),
+                Event::End,
+                Event::End,
+                Event::Visual(Visual::HorizontalBox),
+                Event::Content(Content::Text("a".into())),
+                Event::Visual(Visual::VerticalCenter),
+                Event::Begin(Grouping::Normal),
+                Event::Content(Content::Ordinary {
+                    content: 'x',
+                    stretchy: false
+                }),
+                Event::End,
+            ]
+        );
+
+        for input in [r"\hbox x", r"\hbox to 1em{x}", r"\mbox", r"\vcenter"] {
+            let parser = Parser::new(input, &store);
+            assert!(parser.collect::<Result<Vec<_>, _>>().is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn text_boxes() {
+        let store = Storage::new();
+        let parser = Parser::new(
+            r"\text{a\fbox{b}\llap{c}\rlap{}}\raisebox{1ex}{\raisebox{2ex}{x}}",
+            &store,
+        );
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Begin(Grouping::Normal),
+                Event::Content(Content::Text("a".into())),
+                Event::Visual(Visual::Framed(Framing::default())),
+                Event::Begin(Grouping::Normal),
+                Event::Content(Content::Text("b".into())),
+                Event::End,
+                Event::Visual(Visual::Overlap(ColumnAlignment::Left)),
+                Event::Begin(Grouping::Normal),
+                Event::Content(Content::Text("c".into())),
+                Event::End,
+                Event::Visual(Visual::Overlap(ColumnAlignment::Right)),
+                Event::Begin(Grouping::Normal),
+                Event::End,
+                Event::End,
+                Event::Visual(Visual::Raise(Dimension::new(1., DimensionUnit::Ex))),
+                Event::Visual(Visual::Raise(Dimension::new(2., DimensionUnit::Ex))),
+                Event::Begin(Grouping::Normal),
+                Event::Content(Content::Text("x".into())),
+                Event::End,
+            ]
+        );
+
+        let parser = Parser::new(
+            r"\fbox{\framebox[1em][l]{\mbox{\hbox{\rule{1pt}{2pt}}}}}",
+            &store,
+        );
+        let events = parser.collect::<Result<Vec<_>, ParserError>>().unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Visual(Visual::Framed(Framing::default())),
+                Event::Visual(Visual::Framed(Framing {
+                    width: Some(Dimension::new(1., DimensionUnit::Em)),
+                    alignment: ColumnAlignment::Left,
+                })),
+                Event::Begin(Grouping::Normal),
+                Event::Visual(Visual::HorizontalBox),
+                Event::Begin(Grouping::Normal),
+                Event::Visual(Visual::HorizontalBox),
+                Event::Begin(Grouping::Normal),
+                Event::Rule {
+                    width: Dimension::new(1., DimensionUnit::Pt),
+                    height: Dimension::new(2., DimensionUnit::Pt),
+                    depth: Dimension::new(0., DimensionUnit::Em),
+                },
+                Event::End,
+                Event::End,
+                Event::End,
+            ]
+        );
+
+        for input in [
+            r"\text{\hbox x}",
+            r"\text{\raisebox{1ex}[0pt]{a}}",
+            r"\mbox{\fbox}",
+        ] {
+            let parser = Parser::new(input, &store);
+            assert!(parser.collect::<Result<Vec<_>, _>>().is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn text_errors() {
+        let store = Storage::new();
+        for input in [r"\text{$x}", r"\text{\(x}"] {
+            let parser = Parser::new(input, &store);
+            assert!(parser.collect::<Result<Vec<_>, _>>().is_err(), "{input}");
+        }
+    }
 }
 
 // Token parsing procedure, as per TeXbook p. 46-47.
@@ -1,7 +1,9 @@
 //! Error type returned by the parser upon failure.
 //!
 //! This error type is used to provide context to an error which occurs during the parsing stage.
-use std::{error::Error, fmt::Display};
+use std::{borrow::Cow, error::Error, fmt::Display};
+
+use std::ops::Range;
 
 use super::SpanStack;
 use crate::event::GroupingKind;
@@ -20,10 +22,47 @@ pub struct ParserError {
 struct Inner {
     error: ErrorKind,
     context: Box<str>,
+    span: Range<usize>,
+    expansions: Box<[Expansion]>,
+}
+
+/// A macro expansion that led to an error.
+///
+/// The expansions of a [`ParserError`] are available through [`ParserError::expansions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expansion {
+    span: Range<usize>,
+    call_site: Box<str>,
+    expanded: Box<str>,
+}
+
+impl Expansion {
+    /// The byte range of the original input that contains the macro call.
+    ///
+    /// For nested expansions, this is the span of the outermost macro call, since the inner call
+    /// sites do not appear in the original input.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// The macro call, i.e., the control sequence and its arguments.
+    pub fn call_site(&self) -> &str {
+        &self.call_site
+    }
+
+    /// The result of expanding the macro call.
+    pub fn expanded(&self) -> &str {
+        &self.expanded
+    }
 }
 
 impl ParserError {
-    pub(super) fn new(error: ErrorKind, place: *const u8, span_stack: &mut SpanStack) -> Self {
+    pub(super) fn new(
+        error: ErrorKind,
+        place: *const u8,
+        span_stack: &mut SpanStack,
+        span: Range<usize>,
+    ) -> Self {
         const CONTEXT_SIZE: usize = 12;
         const CONTEXT_PREFIX: &str = "╭─► context:\n";
         const EXPANSION_PREFIX: &str = "─► which was expanded from:\n";
@@ -85,13 +124,56 @@ impl ParserError {
         );
         context.shrink_to_fit();
 
+        let expansions = span_stack
+            .expansions
+            .iter()
+            .enumerate()
+            .map(|(depth, expansion)| {
+                let origin = depth
+                    .checked_sub(1)
+                    .map(|index| span_stack.expansions[index].full_expansion)
+                    .unwrap_or(span_stack.input);
+                let call_site = &expansion.call_site_in_origin;
+                let start = span_stack.map_to_input(depth, call_site.start, false);
+                let end = span_stack.map_to_input(depth, call_site.end, true);
+                Expansion {
+                    span: start..end,
+                    call_site: origin[call_site.clone()].into(),
+                    expanded: expansion.full_expansion[..expansion.expansion_length].into(),
+                }
+            })
+            .collect();
+
         Self {
             inner: Box::new(Inner {
                 error,
                 context: context.into_boxed_str(),
+                span,
+                expansions,
             }),
         }
     }
+
+    /// The kind of error that occurred.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.inner.error
+    }
+
+    /// The byte range of the original input where the error occurred.
+    ///
+    /// If the error occurred inside of a macro expansion, this is the span of the macro call in
+    /// the original input.
+    pub fn span(&self) -> Range<usize> {
+        self.inner.span.clone()
+    }
+
+    /// The chain of macro expansions in which the error occurred, from the outermost macro call
+    /// (found in the original input) to the innermost one.
+    ///
+    /// This is empty if the error did not occur inside of a macro expansion.
+    pub fn expansions(&self) -> &[Expansion] {
+        &self.inner.expansions
+    }
 }
 
 fn write_context_str(context: &str, out: &mut String, last: bool, has_previous_content: bool) {
@@ -143,46 +225,139 @@ impl Display for ParserError {
 
 pub(crate) type InnerResult<T> = std::result::Result<T, ErrorKind>;
 
-#[derive(Debug)]
-pub(crate) enum ErrorKind {
+/// The kind of error that occurred while parsing.
+///
+/// The message associated with each kind is available through its [`Display`] implementation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A group was not closed, or a group closing was found without a matching opening.
+    ///
+    /// Contains the kind of the group that was not closed, if any.
     UnbalancedGroup(Option<GroupingKind>),
+    /// An unknown mathematical environment was found.
     Environment,
+    /// A `$` character was found inside of math mode.
     MathShift,
+    /// A `#` character was found outside of a macro definition.
     HashSign,
+    /// A dimension or glue argument was expected.
     DimensionArgument,
+    /// A dimensional unit was expected.
     DimensionUnit,
+    /// Mathematical units (`mu`) were expected.
     MathUnit,
+    /// A delimiter token was expected.
     Delimiter,
+    /// A control sequence was expected.
     ControlSequence,
+    /// A number was expected.
     Number,
+    /// A non-ASCII character was found after '`' where a character representing a number was
+    /// expected.
     CharacterNumber,
+    /// An argument was expected.
     Argument,
+    /// An argument delimited by `{}` was expected.
     GroupArgument,
+    /// A subscript was added twice to the same element.
     DoubleSubscript,
+    /// A superscript was added twice to the same element.
     DoubleSuperscript,
+    /// An unknown primitive command was found.
     UnknownPrimitive,
+    /// A control sequence was given as argument to a command that does not support it.
     ControlSequenceAsArgument,
+    /// A subscript or superscript was given as argument to a command.
     ScriptAsArgument,
+    /// An empty control sequence (i.e., a lone `\`) was found.
     EmptyControlSequence,
+    /// An unknown color was specified.
     UnknownColor,
+    /// A character number outside of the range `0..=255` was found.
     InvalidCharNumber,
+    /// The `\relax` command was used where it is not allowed.
     Relax,
+    /// The parameter text of a macro definition contains braces.
     BracesInParamText,
+    /// The parameter text of a macro definition contains a comment.
     CommentInParamText,
+    /// A macro definition declares its parameters out of order.
+    ///
+    /// Contains the parameter number that was found, and the one that was expected.
     IncorrectMacroParams(u8, u8),
+    /// A macro replacement text uses a parameter that was not declared.
+    ///
+    /// Contains the parameter number that was found, and the number of declared parameters.
     IncorrectReplacementParams(u8, u8),
+    /// A macro definition contains more than 9 parameters.
     TooManyParams,
+    /// A macro definition contains a standalone `#`.
     StandaloneHashSign,
+    /// A macro use does not begin with the prefix specified in its definition.
     IncorrectMacroPrefix,
+    /// A macro use does not end with the suffix specified in its definition.
     MacroSuffixNotFound,
+    /// A macro was defined while it was already defined.
     MacroAlreadyDefined,
+    /// A macro was redefined while it was not defined.
     MacroNotDefined,
+    /// An environment was defined while it was already defined, e.g., with `\newenvironment`.
+    EnvironmentAlreadyDefined,
+    /// An environment was redefined while it was not defined, e.g., with `\renewenvironment`.
+    EnvironmentNotDefined,
+    /// An alignment (`&`) was found where it is not allowed.
     Alignment,
+    /// A new line (`\\`) was found where it is not allowed.
     NewLine,
+    /// A `\tag`, `\notag`, `\nonumber` or `\label` command was found outside of an environment
+    /// that allows equation numbers.
+    Tag,
+    /// A `\tag` command was found in a line that already has a tag.
+    MultipleTags,
+    /// An array was declared without any content column.
     ArrayNoColumns,
+    /// A macro definition is missing its expansion.
     MissingExpansion,
+    /// The maximum macro expansion depth was exceeded.
+    ///
+    /// See [`ParserConfig::max_expansion_depth`](crate::config::ParserConfig::max_expansion_depth).
     MacroRecursionLimit,
+    /// The maximum total size of macro expansions was exceeded.
+    ///
+    /// See [`ParserConfig::max_expansion_bytes`](crate::config::ParserConfig::max_expansion_bytes).
+    ExpansionSizeLimit,
+    /// The maximum group nesting depth was exceeded.
+    ///
+    /// This is also reported when control sequences given as arguments to other control sequences
+    /// (e.g., `\sqrt\sqrt\sqrt x`) are nested too deeply.
+    ///
+    /// See [`ParserConfig::max_group_depth`](crate::config::ParserConfig::max_group_depth).
+    GroupNestingLimit,
+    /// The maximum number of events was exceeded.
+    ///
+    /// See [`ParserConfig::max_events`](crate::config::ParserConfig::max_events).
+    EventLimit,
+    /// The maximum input length was exceeded.
+    ///
+    /// See [`ParserConfig::max_input_length`](crate::config::ParserConfig::max_input_length).
+    InputLengthLimit,
+    /// The step budget was exhausted.
+    ///
+    /// See [`ParserConfig::step_budget`](crate::config::ParserConfig::step_budget).
+    StepLimit,
+    /// A token was expected.
     Token,
+    /// The signature of a custom primitive contains an unknown argument specifier.
+    ///
+    /// See [`MacroSet::define_primitive`](crate::MacroSet::define_primitive).
+    PrimitiveSignature,
+    /// A custom primitive produced events that are not structurally valid, as checked by
+    /// [`event::validate`](crate::event::validate).
+    PrimitiveOutput,
+    /// An error reported by a custom primitive, containing its message.
+    Custom(Cow<'static, str>),
 }
 
 impl Display for ErrorKind {
@@ -228,14 +403,26 @@ impl Display for ErrorKind {
             ErrorKind::MacroSuffixNotFound => f.write_str("macro use does not match its definition, expected its argument(s) to end with a suffix string as specified in the definition"),
             ErrorKind::MacroAlreadyDefined => f.write_str("macro already defined"),
             ErrorKind::MacroNotDefined => f.write_str("macro not defined"),
+            ErrorKind::EnvironmentAlreadyDefined => f.write_str("environment already defined"),
+            ErrorKind::EnvironmentNotDefined => f.write_str("environment not defined"),
             ErrorKind::DimensionArgument => f.write_str("expected a dimension or glue argument"),
             ErrorKind::DimensionUnit => f.write_str("expected a dimensional unit"),
             ErrorKind::Alignment => f.write_str("alignment not allowed in current environment"),
             ErrorKind::NewLine => f.write_str("new line command not allowed in current environment"),
+            ErrorKind::Tag => f.write_str("tag, label or `\\notag` not allowed in current environment"),
+            ErrorKind::MultipleTags => f.write_str("multiple tags in the same line"),
             ErrorKind::ArrayNoColumns => f.write_str("array must have at least one column of the type `c`, `l` or `r`"),
             ErrorKind::MissingExpansion => f.write_str("The macro definition is missing an expansion"),
             ErrorKind::MacroRecursionLimit => f.write_str("macro expansion depth limit exceeded (possible infinite recursion)"),
+            ErrorKind::ExpansionSizeLimit => f.write_str("macro expansion size limit exceeded"),
+            ErrorKind::GroupNestingLimit => f.write_str("group nesting depth limit exceeded"),
+            ErrorKind::EventLimit => f.write_str("maximum number of events exceeded"),
+            ErrorKind::InputLengthLimit => f.write_str("input length limit exceeded"),
+            ErrorKind::StepLimit => f.write_str("parsing step budget exhausted"),
             ErrorKind::Token => f.write_str("expected a token"),
+            ErrorKind::PrimitiveSignature => f.write_str("primitive signature contains an argument specifier other than `m`, `o` or `s`"),
+            ErrorKind::PrimitiveOutput => f.write_str("custom primitive produced an invalid stream of events"),
+            ErrorKind::Custom(message) => f.write_str(message),
         }
     }
 }
@@ -1,4 +1,6 @@
-use crate::event::{DelimiterType, Dimension, DimensionUnit, Glue, GroupingKind, Line};
+use crate::event::{
+    DelimiterType, Dimension, DimensionUnit, Glue, GroupingKind, Line, ScriptPosition,
+};
 
 use super::{
     tables::{dvipsnames_color, primitive_color, token_to_delim},
@@ -14,7 +16,7 @@ pub fn definition<'a>(input: &mut &'a str) -> InnerResult<(&'a str, &'a str, &'a
     let control_sequence = control_sequence(input)?;
     let (parameter_text, rest) = input.split_once('{').ok_or(ErrorKind::MissingExpansion)?;
 
-    if let Some(idx) = parameter_text.find(|c: char| c == '%' || c == '}') {
+    if let Some(idx) = parameter_text.find(['%', '}']) {
         return Err(if parameter_text.as_bytes()[idx] == b'%' {
             ErrorKind::CommentInParamText
         } else {
@@ -39,6 +41,15 @@ pub fn argument<'a>(input: &mut &'a str) -> InnerResult<Argument<'a>> {
     }
 }
 
+/// Parse the argument of a text-mode command, which is either a group or a single character.
+pub fn text_argument<'a>(input: &mut &'a str) -> InnerResult<&'a str> {
+    match argument(input)? {
+        Argument::Group(content) => Ok(content),
+        Argument::Token(Token::Character(c)) => Ok(&c.as_str()[..char::from(c).len_utf8()]),
+        Argument::Token(Token::ControlSequence(_)) => Err(ErrorKind::ControlSequenceAsArgument),
+    }
+}
+
 pub fn optional_argument<'a>(input: &mut &'a str) -> Option<&'a str> {
     if let Some(rest) = input.trim_start().strip_prefix('[') {
         *input = rest;
@@ -63,8 +74,20 @@ pub fn brace_argument<'a>(input: &mut &'a str) -> InnerResult<&'a str> {
 /// The output is the content within the group without the surrounding `start` and `end`.
 /// This content is guaranteed to be balanced.
 pub fn group_content<'a>(input: &mut &'a str, grouping_kind: GroupingKind) -> InnerResult<&'a str> {
-    let start = grouping_kind.opening_str();
-    let end = grouping_kind.closing_str();
+    let content = delimited_content(
+        input,
+        &grouping_kind.opening_str(),
+        &grouping_kind.closing_str(),
+    );
+    content.ok_or(ErrorKind::UnbalancedGroup(Some(grouping_kind)))
+}
+
+/// Parses the inside of a group delimited by the given strings, when the first opening string is
+/// already parsed.
+///
+/// This is the same as [`group_content`], for groups whose delimiters are already known, e.g.,
+/// user-defined environments. `None` is returned if the group is not closed.
+pub fn delimited_content<'a>(input: &mut &'a str, start: &str, end: &str) -> Option<&'a str> {
     let mut escaped = false;
     let mut index = 0;
     let mut depth = 0u32;
@@ -72,7 +95,7 @@ pub fn group_content<'a>(input: &mut &'a str, grouping_kind: GroupingKind) -> In
     while escaped || depth > 0 || !bytes[index..].starts_with(end.as_bytes()) {
         if index + end.len() > input.len() {
             *input = &input[input.len()..];
-            return Err(ErrorKind::UnbalancedGroup(Some(grouping_kind)));
+            return None;
         }
         if !escaped && bytes[index..].starts_with(start.as_bytes()) {
             depth += 1;
@@ -90,9 +113,7 @@ pub fn group_content<'a>(input: &mut &'a str, grouping_kind: GroupingKind) -> In
         match bytes[index] {
             b'\\' => escaped = !escaped,
             b'%' if !escaped => {
-                let Some(rest_pos) = bytes[index..].iter().position(|&c| c == b'\n') else {
-                    return Err(ErrorKind::UnbalancedGroup(Some(grouping_kind)));
-                };
+                let rest_pos = bytes[index..].iter().position(|&c| c == b'\n')?;
                 index += rest_pos;
             }
             _ => escaped = false,
@@ -101,7 +122,7 @@ pub fn group_content<'a>(input: &mut &'a str, grouping_kind: GroupingKind) -> In
     }
     let (argument, rest) = input.split_at(index);
     *input = &rest[end.len()..];
-    Ok(argument)
+    Some(argument)
 }
 
 /// Finds the shortest prefix of `input` that contains balanced groups and ends with the given `suffix`.
@@ -188,23 +209,24 @@ pub fn control_sequence<'a>(input: &mut &'a str) -> InnerResult<&'a str> {
     }
 }
 
-pub fn limit_modifiers(input: &mut &str) -> Option<bool> {
+/// Parse the `\limits`, `\nolimits` and `\displaylimits` modifiers following an operator, and
+/// return the script position given by the last one.
+pub fn limit_modifiers(input: &mut &str) -> Option<ScriptPosition> {
     let mut output = None;
-    while let Some((rest, limits)) = input
-        .trim_start()
-        .strip_prefix(r"\limits")
-        .map(|rest| (rest, true))
-        .or_else(|| {
-            input
-                .trim_start()
-                .strip_prefix(r"\nolimits")
-                .map(|rest| (rest, false))
-        })
-    {
+    loop {
+        let trimmed = input.trim_start();
+        let (rest, position) = if let Some(rest) = trimmed.strip_prefix(r"\limits") {
+            (rest, ScriptPosition::AboveBelow)
+        } else if let Some(rest) = trimmed.strip_prefix(r"\nolimits") {
+            (rest, ScriptPosition::Right)
+        } else if let Some(rest) = trimmed.strip_prefix(r"\displaylimits") {
+            (rest, ScriptPosition::Movable)
+        } else {
+            return output;
+        };
         *input = rest;
-        output = Some(limits);
+        output = Some(position);
     }
-    output
 }
 
 /// Parse the right side of a control sequence (`\` already being parsed).
@@ -228,6 +250,29 @@ pub fn rhs_control_sequence<'a>(input: &mut &'a str) -> InnerResult<&'a str> {
     Ok(control_sequence)
 }
 
+/// Parse the name of a control sequence in text mode, where the `\` is already parsed.
+///
+/// Unlike [`rhs_control_sequence`], whitespace is only skipped after control words, as whitespace
+/// is significant after control symbols in text mode (e.g., `\$ 5`).
+pub fn text_control_sequence<'a>(input: &mut &'a str) -> InnerResult<&'a str> {
+    let len = input
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(input.len());
+    if len > 0 {
+        let (control_sequence, rest) = input.split_at(len);
+        *input = rest.trim_start();
+        Ok(control_sequence)
+    } else {
+        let first_char = input
+            .chars()
+            .next()
+            .ok_or(ErrorKind::EmptyControlSequence)?;
+        let (control_sequence, rest) = input.split_at(first_char.len_utf8());
+        *input = rest;
+        Ok(control_sequence)
+    }
+}
+
 /// Parse a glue (TeXBook p. 267).
 pub fn glue(input: &mut &str) -> InnerResult<Glue> {
     let mut dimen = (dimension(input)?, None, None);
@@ -1,18 +1,349 @@
-use std::collections::HashMap;
+//! A set of macro definitions that can be shared between parsers.
+use std::{collections::HashMap, sync::Arc};
 
-use crate::parser::{ErrorKind, InnerResult, Token};
+use crate::{
+    event::{max_alignments, ArrayColumn, Event, Grouping, GroupingKind, Style},
+    parser::{ErrorKind, InnerResult, Token},
+};
 
-use super::{lex, Argument};
+use super::{lex, storage::Storage, Argument, Parser, ParserError};
 
-#[derive(Debug)]
-pub struct MacroContext<'input> {
-    definitions: HashMap<&'input str, Definition<'input>>,
+/// A set of macro definitions, which can be given to many [`Parser`]s through
+/// [`Parser::with_macros`].
+///
+/// A macro set can either be built programmatically, using [`MacroSet::define`] and
+/// [`MacroSet::new_command`], or from a preamble of `LaTeX` definitions using
+/// [`MacroSet::from_preamble`]. Commands whose behavior cannot be expressed as macros can be
+/// defined with [`MacroSet::define_primitive`]. Environments can be defined in the same way as
+/// `\newenvironment` would with [`MacroSet::new_environment`], or as a variant of a built-in
+/// environment with [`MacroSet::define_environment`].
+///
+/// Macro sets are cheap to share, as the definitions are reference counted and only copied when a
+/// parser (re)defines a macro. The macros defined by a parser can be retrieved using
+/// [`Parser::into_macros`].
+///
+/// ## Example
+///
+/// ```rust
+/// use pulldown_latex::{MacroSet, Parser, Storage};
+///
+/// let storage = Storage::new();
+/// let macros = MacroSet::from_preamble(r"\newcommand{\R}{\mathbb{R}}", &storage).unwrap();
+///
+/// for input in [r"x \in \R", r"f: \R \to \R"] {
+///     let parser = Parser::with_macros(input, &storage, &macros);
+///     assert!(parser.collect::<Result<Vec<_>, _>>().is_ok());
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MacroSet<'a> {
+    pub(super) context: MacroContext<'a>,
+}
+
+impl<'a> MacroSet<'a> {
+    /// Create an empty macro set.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create a macro set from a preamble containing macro definitions, such as `\def`,
+    /// `\newcommand` and `\let`.
+    ///
+    /// Anything in the preamble that is not a definition is parsed, but ignored.
+    pub fn from_preamble(preamble: &'a str, storage: &'a Storage) -> Result<Self, ParserError> {
+        let mut parser = Parser::new(preamble, storage);
+        parser.by_ref().try_for_each(|event| event.map(|_| ()))?;
+        Ok(parser.into_macros())
+    }
+
+    /// Define a macro in the same way as `\def` would, from its name, parameter text, and
+    /// replacement text.
+    ///
+    /// The name is the name of the control sequence, with or without its leading backslash. The
+    /// replacement text must be balanced.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use pulldown_latex::MacroSet;
+    ///
+    /// let mut macros = MacroSet::new();
+    /// // Equivalent to `\def\norm#1{\left\lVert #1 \right\rVert}`
+    /// macros.define("norm", "#1", r"\left\lVert #1 \right\rVert").unwrap();
+    /// ```
+    pub fn define(
+        &mut self,
+        name: &'a str,
+        parameter_text: &'a str,
+        replacement_text: &'a str,
+    ) -> Result<(), ErrorKind> {
+        if let Some(idx) = parameter_text.find(['%', '{', '}']) {
+            return Err(if parameter_text.as_bytes()[idx] == b'%' {
+                ErrorKind::CommentInParamText
+            } else {
+                ErrorKind::BracesInParamText
+            });
+        }
+        self.context
+            .define(strip_backslash(name), parameter_text, replacement_text)
+    }
+
+    /// Define a command in the same way as `\newcommand` would, from its name, number of
+    /// arguments, default value of the first argument, and replacement text.
+    ///
+    /// The name is the name of the control sequence, with or without its leading backslash. If a
+    /// default value is given, the first argument becomes optional. The replacement text must be
+    /// balanced.
+    ///
+    /// Unlike `\newcommand`, this method overwrites any existing definition.
+    pub fn new_command(
+        &mut self,
+        name: &'a str,
+        argument_count: u8,
+        first_arg_default: Option<&'a str>,
+        replacement_text: &'a str,
+    ) -> Result<(), ErrorKind> {
+        if argument_count > 9 {
+            return Err(ErrorKind::TooManyParams);
+        }
+        if argument_count == 0 && first_arg_default.is_some() {
+            return Err(ErrorKind::Argument);
+        }
+        self.context.insert_command(
+            strip_backslash(name),
+            argument_count,
+            first_arg_default,
+            replacement_text,
+        )
+    }
+
+    /// Define a custom primitive command, whose events are produced by the given handler.
+    ///
+    /// The name is the name of the control sequence, with or without its leading backslash. The
+    /// signature describes the arguments of the command, in the manner of `xparse`, with one
+    /// character per argument:
+    /// - `m`: a mandatory argument, either a group or a single token,
+    /// - `o`: an optional argument delimited by `[]`,
+    /// - `s`: an optional star (`*`).
+    ///
+    /// The handler receives the content of the arguments, without their delimiters, in the order
+    /// of the signature. Optional arguments and stars that are absent are given as `None`, and a
+    /// star that is present is given as `Some("*")`. The handler returns the events produced by
+    /// the command, which are wrapped in a group unless they form a single element or only
+    /// contain state changes. If it returns an error instead, the error is reported by the parser
+    /// at the location of the command, in the same way as other errors, and the message of an
+    /// [`ErrorKind::Custom`] error is displayed as is.
+    ///
+    /// Custom primitives are only consulted for control sequences that are not built into the
+    /// parser, and they can be shadowed by macros of the same name.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use pulldown_latex::{
+    ///     event::{Content, Event, Font, Grouping, StateChange},
+    ///     MacroSet, Parser, Storage,
+    /// };
+    ///
+    /// let storage = Storage::new();
+    /// let mut macros = MacroSet::new();
+    /// macros
+    ///     .define_primitive("gene", "m", |arguments| {
+    ///         Ok(vec![
+    ///             Event::Begin(Grouping::Normal),
+    ///             Event::StateChange(StateChange::Font(Some(Font::Italic))),
+    ///             Event::Content(Content::Text(arguments[0].unwrap_or_default().into())),
+    ///             Event::End,
+    ///         ])
+    ///     })
+    ///     .unwrap();
+    ///
+    /// let events = Parser::with_macros(r"\gene{BRCA1}", &storage, &macros)
+    ///     .collect::<Result<Vec<_>, _>>()
+    ///     .unwrap();
+    /// assert_eq!(events[2], Event::Content(Content::Text("BRCA1".into())));
+    /// ```
+    pub fn define_primitive<F>(
+        &mut self,
+        name: &'a str,
+        signature: &str,
+        handler: F,
+    ) -> Result<(), ErrorKind>
+    where
+        F: for<'b> Fn(&[Option<&'b str>]) -> Result<Vec<Event<'b>>, ErrorKind>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let signature = signature
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| match c {
+                'm' => Ok(ArgumentSpec::Mandatory),
+                'o' => Ok(ArgumentSpec::Optional),
+                's' => Ok(ArgumentSpec::Star),
+                _ => Err(ErrorKind::PrimitiveSignature),
+            })
+            .collect::<InnerResult<Box<[_]>>>()?;
+        if signature.len() > 9 {
+            return Err(ErrorKind::TooManyParams);
+        }
+        self.context.insert_primitive(
+            strip_backslash(name),
+            Primitive {
+                signature: signature.into(),
+                handler: Arc::new(handler),
+            },
+        );
+        Ok(())
+    }
+
+    /// Define an environment in the same way as `\newenvironment` would, from its name, number of
+    /// arguments, default value of the first argument, and the replacement texts of `\begin` and
+    /// `\end`.
+    ///
+    /// The arguments can only be used in the replacement text of `\begin`. As in `LaTeX`, the
+    /// content of the environment is placed in a group, between the two replacement texts.
+    ///
+    /// Unlike `\newenvironment`, this method overwrites any existing definition.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use pulldown_latex::{MacroSet, Parser, Storage};
+    ///
+    /// let storage = Storage::new();
+    /// let mut macros = MacroSet::new();
+    /// // Equivalent to `\newenvironment{system}[1][x]{#1 = \left\{\begin{array}{l}}{\end{array}\right.}`
+    /// macros
+    ///     .new_environment("system", 1, Some("x"), r"#1 = \left\{\begin{array}{l}", r"\end{array}\right.")
+    ///     .unwrap();
+    ///
+    /// let input = r"\begin{system}[y] 1 \\ 2 \end{system}";
+    /// let parser = Parser::with_macros(input, &storage, &macros);
+    /// assert!(parser.collect::<Result<Vec<_>, _>>().is_ok());
+    /// ```
+    pub fn new_environment(
+        &mut self,
+        name: &'a str,
+        argument_count: u8,
+        first_arg_default: Option<&'a str>,
+        begin: &'a str,
+        end: &'a str,
+    ) -> Result<(), ErrorKind> {
+        if argument_count > 9 {
+            return Err(ErrorKind::TooManyParams);
+        }
+        if argument_count == 0 && first_arg_default.is_some() {
+            return Err(ErrorKind::Argument);
+        }
+        self.context
+            .insert_environment(name, argument_count, first_arg_default, begin, end)
+    }
+
+    /// Define an environment behaving like a built-in environment, from its name, the [`Grouping`]
+    /// it produces, the delimiters surrounding it, and the style applied to its content.
+    ///
+    /// The number of alignments (`&`) allowed in each row of the environment is the one of the
+    /// grouping, e.g., one per pair of columns for [`Grouping::Alignat`], and any number for
+    /// [`Grouping::Matrix`]. The grouping cannot be [`Grouping::Normal`] or
+    /// [`Grouping::LeftRight`], and the columns of a [`Grouping::Array`] must contain at least
+    /// one content column.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use pulldown_latex::{
+    ///     event::{ColumnAlignment, Event, Grouping},
+    ///     MacroSet, Parser, Storage,
+    /// };
+    ///
+    /// let storage = Storage::new();
+    /// let mut macros = MacroSet::new();
+    /// // A `pmatrix` with brackets and left-aligned columns.
+    /// let grouping = Grouping::Matrix {
+    ///     alignment: ColumnAlignment::Left,
+    /// };
+    /// macros
+    ///     .define_environment("lmatrix", grouping.clone(), Some(('[', ']')), None)
+    ///     .unwrap();
+    ///
+    /// let events = Parser::with_macros(r"\begin{lmatrix} a & b \end{lmatrix}", &storage, &macros)
+    ///     .collect::<Result<Vec<_>, _>>()
+    ///     .unwrap();
+    /// assert_eq!(events[0], Event::Begin(Grouping::LeftRight(Some('['), Some(']'))));
+    /// assert_eq!(events[1], Event::Begin(grouping));
+    /// ```
+    pub fn define_environment(
+        &mut self,
+        name: &'a str,
+        grouping: Grouping,
+        delimiters: Option<(char, char)>,
+        style: Option<Style>,
+    ) -> Result<(), ErrorKind> {
+        let max_alignments = max_alignments(&grouping).ok_or(ErrorKind::Environment)?;
+        if let Grouping::Array(columns) = &grouping {
+            if !columns
+                .iter()
+                .any(|column| matches!(column, ArrayColumn::Column(_)))
+            {
+                return Err(ErrorKind::ArrayNoColumns);
+            }
+        }
+        Arc::make_mut(&mut self.context.environments).insert(
+            name,
+            EnvironmentDef::Grouping(Arc::new(GroupingEnvironment {
+                opening: format!("\\begin{{{name}}}").into(),
+                closing: format!("\\end{{{name}}}").into(),
+                grouping,
+                delimiters,
+                style,
+                max_alignments: max_alignments.try_into().unwrap_or(u16::MAX),
+            })),
+        );
+        Ok(())
+    }
+
+    /// Whether an environment with the given name is defined in the set.
+    pub fn contains_environment(&self, name: &str) -> bool {
+        self.context.contains_environment(name)
+    }
+
+    /// Whether a control sequence with the given name is defined in the set.
+    pub fn contains(&self, name: &str) -> bool {
+        self.context.contains(strip_backslash(name))
+    }
+
+    /// The number of definitions in the set, counting both control sequences and environments.
+    pub fn len(&self) -> usize {
+        self.context.definitions.len() + self.context.environments.len()
+    }
+
+    /// Whether the set does not contain any definition of a control sequence or an environment.
+    pub fn is_empty(&self) -> bool {
+        self.context.definitions.is_empty() && self.context.environments.is_empty()
+    }
+}
+
+fn strip_backslash(name: &str) -> &str {
+    name.strip_prefix('\\').unwrap_or(name)
+}
+
+/// The macro definitions used by a parser.
+///
+/// Definitions are reference counted so that they can be shared with other parsers through a
+/// [`MacroSet`], and are copied on the first write.
+#[derive(Debug, Clone)]
+pub(crate) struct MacroContext<'input> {
+    definitions: Arc<HashMap<&'input str, Definition<'input>>>,
+    environments: Arc<HashMap<&'input str, EnvironmentDef<'input>>>,
 }
 
 impl<'input> MacroContext<'input> {
     pub fn new() -> Self {
         Self {
-            definitions: HashMap::new(),
+            definitions: Arc::new(HashMap::new()),
+            environments: Arc::new(HashMap::new()),
         }
     }
 
@@ -74,7 +405,7 @@ impl<'input> MacroContext<'input> {
 
         let replacement = parse_replacement_text(replacement_text, parameters.len() as u8)?;
 
-        self.definitions.insert(
+        Arc::make_mut(&mut self.definitions).insert(
             name,
             Definition::Macro(MacroDef {
                 prefix,
@@ -90,9 +421,60 @@ impl<'input> MacroContext<'input> {
         self.definitions.contains_key(name)
     }
 
+    pub(crate) fn contains_environment(&self, name: &str) -> bool {
+        self.environments.contains_key(name)
+    }
+
+    /// The argument count must be less than 9 if the optional argument is None, and less than 8 if
+    /// the optional argument is Some.
+    pub(crate) fn insert_environment(
+        &mut self,
+        name: &'input str,
+        argument_count: u8,
+        first_arg_default: Option<&'input str>,
+        begin: &'input str,
+        end: &'input str,
+    ) -> InnerResult<()> {
+        let begin = parse_replacement_text(begin, argument_count)?;
+        let end = parse_replacement_text(end, 0)?;
+        Arc::make_mut(&mut self.environments).insert(
+            name,
+            EnvironmentDef::Macro {
+                begin: CommandDef {
+                    argument_count,
+                    first_arg_default,
+                    replacement: begin,
+                },
+                end,
+            },
+        );
+        Ok(())
+    }
+
+    /// Returns the environment with the given name, if it is defined through
+    /// [`MacroSet::define_environment`].
+    pub(crate) fn grouping_environment(&self, name: &str) -> Option<Arc<GroupingEnvironment>> {
+        match self.environments.get(name)? {
+            EnvironmentDef::Grouping(environment) => Some(environment.clone()),
+            EnvironmentDef::Macro { .. } => None,
+        }
+    }
+
+    pub(crate) fn insert_primitive(&mut self, name: &'input str, primitive: Primitive) {
+        Arc::make_mut(&mut self.definitions).insert(name, Definition::Primitive(primitive));
+    }
+
+    /// Returns the custom primitive with the given name, if it is defined.
+    pub(crate) fn primitive(&self, name: &str) -> Option<Primitive> {
+        match self.definitions.get(name)? {
+            Definition::Primitive(primitive) => Some(primitive.clone()),
+            _ => None,
+        }
+    }
+
     /// Assign a new control sequence to a token.
     pub(crate) fn assign(&mut self, name: &'input str, alias_for: Token<'input>) {
-        self.definitions.insert(name, Definition::Alias(alias_for));
+        Arc::make_mut(&mut self.definitions).insert(name, Definition::Alias(alias_for));
     }
 
     /// The argument count must be less than 9 if the optional argument is None, and less than 8 if
@@ -105,7 +487,7 @@ impl<'input> MacroContext<'input> {
         replacement: &'input str,
     ) -> InnerResult<()> {
         let replacement = parse_replacement_text(replacement, argument_count)?;
-        self.definitions.insert(
+        Arc::make_mut(&mut self.definitions).insert(
             name,
             Definition::Command(CommandDef {
                 argument_count,
@@ -127,7 +509,50 @@ impl<'input> MacroContext<'input> {
         input_rest: &'input str,
         storage: &'input bumpalo::Bump,
     ) -> Option<InnerResult<(&'input str, usize)>> {
-        Some(self.expand_definition_in(self.definitions.get(name)?, input_rest, storage))
+        if name == "begin" {
+            return self.try_expand_environment_in(input_rest, storage);
+        }
+        match self.definitions.get(name)? {
+            // Primitives are not expanded, but handled along with the built-in ones.
+            Definition::Primitive(_) => None,
+            definition => Some(self.expand_definition_in(definition, input_rest, storage)),
+        }
+    }
+
+    /// Expand the environment starting in the input, if it is defined through `\newenvironment`.
+    ///
+    /// The input must start right after the `\begin` control sequence.
+    fn try_expand_environment_in(
+        &self,
+        input_rest: &'input str,
+        storage: &'input bumpalo::Bump,
+    ) -> Option<InnerResult<(&'input str, usize)>> {
+        let original_length = input_rest.len();
+        let mut input_rest = input_rest;
+        let Ok(Argument::Group(name)) = lex::argument(&mut input_rest) else {
+            return None;
+        };
+        let Some(EnvironmentDef::Macro { begin, end }) = self.environments.get(name) else {
+            return None;
+        };
+
+        let mut expand = || {
+            let arguments = command_arguments(begin, &mut input_rest)?;
+            let content = lex::group_content(
+                &mut input_rest,
+                GroupingKind::Environment(name.to_owned().into()),
+            )?;
+
+            let mut string = bumpalo::collections::String::new_in(storage);
+            string.push('{');
+            push_replacement(&mut string, &begin.replacement, &arguments);
+            string.push_str(content);
+            push_replacement(&mut string, end, &[]);
+            string.push('}');
+            string.push_str(input_rest);
+            Ok((string.into_bump_str(), original_length - input_rest.len()))
+        };
+        Some(expand())
     }
 
     /// Expand a definition in the storage and return the full expanded string along with the
@@ -205,29 +630,14 @@ impl<'input> MacroContext<'input> {
                 string.push_str(input_rest);
                 (string.into_bump_str(), 0)
             }
-            Definition::Command(CommandDef {
-                argument_count,
-                first_arg_default,
-                replacement,
-            }) => {
-                let mut arguments = Vec::with_capacity(*argument_count as usize);
-
-                if let Some(default_argument) = first_arg_default {
-                    arguments.push(Ok(Argument::Group(
-                        lex::optional_argument(&mut input_rest).unwrap_or(default_argument),
-                    )));
-                }
-
-                (0..(*argument_count - first_arg_default.is_some() as u8)).try_for_each(|_| {
-                    arguments.push(Ok(lex::argument(&mut input_rest)?));
-                    Ok(())
-                })?;
-
+            Definition::Command(command) => {
+                let arguments = command_arguments(command, &mut input_rest)?;
                 (
-                    expand_replacement(storage, replacement, &arguments, input_rest),
+                    expand_replacement(storage, &command.replacement, &arguments, input_rest),
                     original_length - input_rest.len(),
                 )
             }
+            Definition::Primitive(_) => unreachable!("primitives are not expanded"),
         })
     }
 }
@@ -302,6 +712,28 @@ fn parse_replacement_text(
     Ok(replacement_tokens)
 }
 
+/// Parse the arguments of a command from the input.
+fn command_arguments<'a>(
+    command: &CommandDef<'a>,
+    input_rest: &mut &'a str,
+) -> InnerResult<Vec<Result<Argument<'a>, &'a str>>> {
+    let mut arguments = Vec::with_capacity(command.argument_count as usize);
+
+    if let Some(default_argument) = command.first_arg_default {
+        arguments.push(Ok(Argument::Group(
+            lex::optional_argument(input_rest).unwrap_or(default_argument),
+        )));
+    }
+
+    (0..(command.argument_count - command.first_arg_default.is_some() as u8)).try_for_each(
+        |_| {
+            arguments.push(Ok(lex::argument(input_rest)?));
+            Ok(())
+        },
+    )?;
+    Ok(arguments)
+}
+
 fn expand_replacement<'store>(
     storage: &'store bumpalo::Bump,
     replacement: &[ReplacementToken],
@@ -310,7 +742,18 @@ fn expand_replacement<'store>(
     input_rest: &str,
 ) -> &'store str {
     let mut replacement_string = bumpalo::collections::String::new_in(storage);
+    push_replacement(&mut replacement_string, replacement, arguments);
+    replacement_string.push_str(input_rest);
+    replacement_string.shrink_to_fit();
 
+    replacement_string.into_bump_str()
+}
+
+fn push_replacement(
+    replacement_string: &mut bumpalo::collections::String,
+    replacement: &[ReplacementToken],
+    arguments: &[Result<Argument, &str>],
+) {
     for token in replacement {
         match token {
             ReplacementToken::Parameter(idx) => match &arguments[*idx as usize - 1] {
@@ -335,11 +778,6 @@ fn expand_replacement<'store>(
             }
         }
     }
-
-    replacement_string.push_str(input_rest);
-    replacement_string.shrink_to_fit();
-
-    replacement_string.into_bump_str()
 }
 
 impl<'input> Default for MacroContext<'input> {
@@ -348,7 +786,7 @@ impl<'input> Default for MacroContext<'input> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct MacroDef<'a> {
     prefix: Option<&'a str>,
     parameters: Vec<Parameter<'a>>,
@@ -356,7 +794,7 @@ struct MacroDef<'a> {
     replacement: Vec<ReplacementToken<'a>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct CommandDef<'a> {
     argument_count: u8,
     first_arg_default: Option<&'a str>,
@@ -372,16 +810,108 @@ enum ReplacementToken<'a> {
     String(&'a str),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum Definition<'a> {
     Macro(MacroDef<'a>),
     Alias(Token<'a>),
     Command(CommandDef<'a>),
+    Primitive(Primitive),
+}
+
+#[derive(Debug, Clone)]
+enum EnvironmentDef<'a> {
+    /// An environment defined through `\newenvironment`.
+    Macro {
+        begin: CommandDef<'a>,
+        end: Vec<ReplacementToken<'a>>,
+    },
+    Grouping(Arc<GroupingEnvironment>),
+}
+
+/// An environment defined through [`MacroSet::define_environment`].
+#[derive(Debug)]
+pub(crate) struct GroupingEnvironment {
+    /// The string opening the environment, i.e., `\begin{name}`.
+    pub(crate) opening: Box<str>,
+    /// The string closing the environment, i.e., `\end{name}`.
+    pub(crate) closing: Box<str>,
+    pub(crate) grouping: Grouping,
+    pub(crate) delimiters: Option<(char, char)>,
+    pub(crate) style: Option<Style>,
+    pub(crate) max_alignments: u16,
+}
+
+/// The function producing the events of a custom primitive from its arguments.
+///
+/// The handler does not borrow anything, so that the parser does not need to outlive it.
+type PrimitiveHandler =
+    dyn for<'a> Fn(&[Option<&'a str>]) -> Result<Vec<Event<'a>>, ErrorKind> + Send + Sync;
+
+/// A custom primitive, defined through [`MacroSet::define_primitive`].
+#[derive(Clone)]
+pub(crate) struct Primitive {
+    signature: Arc<[ArgumentSpec]>,
+    handler: Arc<PrimitiveHandler>,
+}
+
+impl Primitive {
+    /// Parse the arguments of the primitive from the input, and call its handler with them.
+    pub(crate) fn call<'a>(&self, input: &mut &'a str) -> InnerResult<Vec<Event<'a>>> {
+        let arguments = self
+            .signature
+            .iter()
+            .map(|spec| {
+                Ok(match spec {
+                    ArgumentSpec::Mandatory => {
+                        let start = input.trim_start();
+                        match lex::argument(input)? {
+                            Argument::Group(content) => Some(content),
+                            Argument::Token(_) => {
+                                Some(start[..start.len() - input.len()].trim_end())
+                            }
+                        }
+                    }
+                    ArgumentSpec::Optional => lex::optional_argument(input),
+                    ArgumentSpec::Star => {
+                        let start = input.trim_start();
+                        start.strip_prefix('*').map(|rest| {
+                            *input = rest;
+                            &start[..1]
+                        })
+                    }
+                })
+            })
+            .collect::<InnerResult<Vec<_>>>()?;
+        (self.handler)(&arguments)
+    }
+}
+
+impl std::fmt::Debug for Primitive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Primitive")
+            .field("signature", &self.signature)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArgumentSpec {
+    Mandatory,
+    Optional,
+    Star,
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{MacroContext, ReplacementToken};
+    use super::{MacroContext, MacroSet, ReplacementToken};
+    use crate::{
+        event::{
+            ArrayColumn, ColumnAlignment, Content, Event, Font, Grouping, GroupingKind,
+            ScriptPosition, ScriptType, StateChange, Style,
+        },
+        parser::{ErrorKind, Parser},
+        ParserConfig, Storage,
+    };
 
     #[test]
     fn no_params() {
@@ -478,4 +1008,338 @@ mod tests {
         assert!(def.last_param_brace_delimited);
         assert_eq!(def.replacement, vec![ReplacementToken::String("2 + 2 = 4")]);
     }
+
+    #[test]
+    fn macro_set_from_preamble() {
+        let storage = Storage::new();
+        let macros = MacroSet::from_preamble(
+            r"\newcommand{\R}{\mathbb{R}} \def\abs#1{|#1|} \let\eps\varepsilon",
+            &storage,
+        )
+        .unwrap();
+        assert_eq!(macros.len(), 3);
+        assert!(macros.contains("R") && macros.contains(r"\abs") && macros.contains("eps"));
+
+        let events = Parser::with_macros(r"\R", &storage, &macros)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                Event::Begin(Grouping::Normal),
+                Event::StateChange(StateChange::Font(Some(Font::DoubleStruck))),
+                Event::Content(Content::Ordinary {
+                    content: 'R',
+                    stretchy: false
+                }),
+                Event::End,
+            ]
+        );
+    }
+
+    #[test]
+    fn macro_set_is_not_modified_by_parser() {
+        let storage = Storage::new();
+        let mut macros = MacroSet::new();
+        macros.define("foo", "#1", "#1 + #1").unwrap();
+
+        let parser = Parser::with_macros(r"\def\bar{x} \bar \foo a", &storage, &macros);
+        assert!(parser.collect::<Result<Vec<_>, _>>().is_ok());
+        assert!(!macros.contains("bar"));
+
+        let parser = Parser::with_macros(r"\bar", &storage, &macros);
+        assert!(parser.collect::<Result<Vec<_>, _>>().is_err());
+    }
+
+    #[test]
+    fn macros_persist_between_parsers() {
+        let storage = Storage::new();
+        let mut macros = MacroSet::new();
+        let mut outputs = Vec::new();
+        for input in [
+            r"\def\mk#1{\def#1{z}} \mk\foo",
+            r"\newcommand{\bar}{y} \foo",
+            r"\renewcommand{\foo}{x} \bar \foo",
+        ] {
+            let mut parser = Parser::with_macros(input, &storage, &macros);
+            outputs.push(parser.by_ref().collect::<Result<Vec<_>, _>>().unwrap());
+            macros = parser.into_macros();
+        }
+
+        let ordinary = |content| {
+            Event::Content(Content::Ordinary {
+                content,
+                stretchy: false,
+            })
+        };
+        assert_eq!(
+            outputs,
+            vec![
+                vec![],
+                vec![ordinary('z')],
+                vec![ordinary('y'), ordinary('x')]
+            ]
+        );
+        assert_eq!(macros.len(), 3);
+    }
+
+    #[test]
+    fn macro_set_definition_errors() {
+        let mut macros = MacroSet::new();
+        assert_eq!(
+            macros.define("foo", "#1{", "#1"),
+            Err(ErrorKind::BracesInParamText)
+        );
+        assert_eq!(
+            macros.define("foo", "#1", "#2"),
+            Err(ErrorKind::IncorrectReplacementParams(2, 1))
+        );
+        assert_eq!(
+            macros.new_command("foo", 10, None, "#1"),
+            Err(ErrorKind::TooManyParams)
+        );
+        assert!(macros.is_empty());
+    }
+
+    #[test]
+    fn custom_primitives() {
+        let storage = Storage::new();
+        let mut macros = MacroSet::new();
+        macros
+            .define_primitive(r"\unit", "s o m", |arguments| {
+                let unit = arguments[2].unwrap_or_default();
+                if unit.is_empty() {
+                    return Err(ErrorKind::Custom("empty unit".into()));
+                }
+                let mut events = vec![Event::Content(Content::Text(unit.into()))];
+                if let Some(prefix) = arguments[1] {
+                    events.insert(0, Event::Content(Content::Text(prefix.into())));
+                }
+                if arguments[0].is_some() {
+                    events.push(Event::Content(Content::Ordinary {
+                        content: '*',
+                        stretchy: false,
+                    }));
+                }
+                Ok(events)
+            })
+            .unwrap();
+        assert!(macros.contains("unit"));
+
+        let events = Parser::with_macros(r"1\unit{m/s} \unit*[k]m^2", &storage, &macros)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let text = |text: &'static str| Event::Content(Content::Text(text.into()));
+        assert_eq!(
+            events,
+            vec![
+                Event::Content(Content::Number("1".into())),
+                text("m/s"),
+                Event::Script {
+                    ty: ScriptType::Superscript,
+                    position: ScriptPosition::Right
+                },
+                Event::Begin(Grouping::Normal),
+                text("k"),
+                text("m"),
+                Event::Content(Content::Ordinary {
+                    content: '*',
+                    stretchy: false
+                }),
+                Event::End,
+                Event::Content(Content::Number("2".into())),
+            ]
+        );
+
+        let error = Parser::with_macros(r"x + \unit{}", &storage, &macros)
+            .find_map(Result::err)
+            .unwrap();
+        assert_eq!(error.kind(), &ErrorKind::Custom("empty unit".into()));
+        assert_eq!(error.span(), 4..11);
+
+        // Custom primitives are also handled in text mode.
+        let events = Parser::with_macros(r"\text{per \unit{s} }", &storage, &macros)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                Event::Begin(Grouping::Normal),
+                text("per "),
+                text("s"),
+                text(" "),
+                Event::End,
+            ]
+        );
+
+        // Macros shadow custom primitives.
+        let parser = Parser::with_macros(r"\def\unit{y} \unit", &storage, &macros);
+        assert_eq!(
+            parser.collect::<Result<Vec<_>, _>>().unwrap(),
+            vec![Event::Content(Content::Ordinary {
+                content: 'y',
+                stretchy: false
+            })]
+        );
+    }
+
+    #[test]
+    fn custom_primitive_errors() {
+        let storage = Storage::new();
+        let mut macros = MacroSet::new();
+        assert_eq!(
+            macros.define_primitive("foo", "mx", |_| Ok(Vec::new())),
+            Err(ErrorKind::PrimitiveSignature)
+        );
+        macros
+            .define_primitive("unclosed", "", |_| Ok(vec![Event::Begin(Grouping::Normal)]))
+            .unwrap();
+        macros
+            .define_primitive("bold", "", |_| {
+                Ok(vec![Event::StateChange(StateChange::Font(Some(
+                    Font::Bold,
+                )))])
+            })
+            .unwrap();
+
+        let error = Parser::with_macros(r"\unclosed", &storage, &macros)
+            .find_map(Result::err)
+            .unwrap();
+        assert_eq!(error.kind(), &ErrorKind::PrimitiveOutput);
+
+        let config = ParserConfig {
+            error_recovery: true,
+            ..Default::default()
+        };
+        let events = Parser::with_config(r"\bold \unclosed", &storage, &macros, config)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                Event::StateChange(StateChange::Font(Some(Font::Bold))),
+                Event::Content(Content::Error {
+                    kind: ErrorKind::PrimitiveOutput,
+                    source: r"\unclosed".into()
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn new_environment() {
+        let storage = Storage::new();
+        let parse = |input| {
+            Parser::new(input, &storage)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.kind().clone())
+        };
+        let expected = |input| parse(input).unwrap();
+
+        assert_eq!(
+            parse(
+                r"\newenvironment{sys}[1][x]{#1 = \left\{\begin{array}{l}}{\end{array}\right.}
+                \begin{sys} a \\ b \end{sys} \begin{sys}[y] c \end{sys}"
+            ),
+            Ok(expected(
+                r"{{x} = \left\{\begin{array}{l} a \\ b \end{array}\right.}
+                {{y} = \left\{\begin{array}{l} c \end{array}\right.}"
+            ))
+        );
+        assert_eq!(
+            parse(
+                r"\newenvironment{b}{[}{]}
+                \renewenvironment{b}{\{}{\}} \begin{b} \begin{b} x \end{b} \end{b}^2"
+            ),
+            Ok(expected(r"{\{{\{x\}}\}}^2"))
+        );
+        assert_eq!(
+            parse(r"\renewenvironment{foo}{}{}"),
+            Err(ErrorKind::EnvironmentNotDefined)
+        );
+        assert_eq!(
+            parse(r"\newenvironment{foo}{}{} \newenvironment{foo}{}{}"),
+            Err(ErrorKind::EnvironmentAlreadyDefined)
+        );
+        assert_eq!(
+            parse(r"\newenvironment{align}{}{}"),
+            Err(ErrorKind::EnvironmentAlreadyDefined)
+        );
+        assert_eq!(
+            parse(r"\renewenvironment{cases}{(}{)} \begin{cases} x \end{cases}"),
+            Ok(expected(r"{(x)}"))
+        );
+        assert_eq!(
+            parse(r"\newenvironment{foo}{}{#1}"),
+            Err(ErrorKind::IncorrectReplacementParams(1, 0))
+        );
+        assert_eq!(
+            parse(r"\newenvironment{foo}{}{} \begin{foo} x"),
+            Err(ErrorKind::UnbalancedGroup(Some(GroupingKind::Environment(
+                "foo".into()
+            ))))
+        );
+    }
+
+    #[test]
+    fn define_environment() {
+        let storage = Storage::new();
+        let mut macros = MacroSet::new();
+        macros
+            .define_environment(
+                "rcl",
+                Grouping::Array(
+                    [
+                        ColumnAlignment::Right,
+                        ColumnAlignment::Center,
+                        ColumnAlignment::Left,
+                    ]
+                    .map(ArrayColumn::Column)
+                    .into(),
+                ),
+                None,
+                Some(Style::Display),
+            )
+            .unwrap();
+        macros
+            .define_environment(
+                "pairs",
+                Grouping::Alignedat { pairs: 1 },
+                Some(('(', ')')),
+                None,
+            )
+            .unwrap();
+        assert!(macros.contains_environment("rcl") && !macros.contains("rcl"));
+        assert_eq!(macros.len(), 2);
+
+        let parse = |input| {
+            Parser::with_macros(input, &storage, &macros)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.kind().clone())
+        };
+        let expected = |input| Parser::new(input, &storage).collect::<Result<Vec<_>, _>>();
+        assert_eq!(
+            parse(r"\begin{rcl} \hline a & = & b \end{rcl}"),
+            Ok(expected(r"\begin{darray}{rcl} \hline a & = & b \end{darray}").unwrap())
+        );
+        assert_eq!(
+            parse(r"\begin{pairs} a &= b \\ c &= d \end{pairs}"),
+            Ok(
+                expected(r"\left(\begin{alignedat}{1} a &= b \\ c &= d \end{alignedat}\right)")
+                    .unwrap()
+            )
+        );
+        assert_eq!(
+            parse(r"\begin{pairs} a &= b & c \end{pairs}"),
+            Err(ErrorKind::Alignment)
+        );
+        assert_eq!(
+            macros.define_environment("foo", Grouping::Normal, None, None),
+            Err(ErrorKind::Environment)
+        );
+        assert_eq!(
+            macros.define_environment("foo", Grouping::Array(Box::new([])), None, None),
+            Err(ErrorKind::ArrayNoColumns)
+        );
+    }
 }
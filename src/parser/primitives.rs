@@ -2,21 +2,24 @@
 //! includes every primitive macro and active character.
 
 use core::panic;
+use std::{borrow::Cow, convert::Infallible};
 
 use crate::event::{
-    ArrayColumn as AC, ColorChange as CC, ColorTarget as CT, ColumnAlignment, Content as C,
+    self, ArrayColumn as AC, ColorChange as CC, ColorTarget as CT, ColumnAlignment, Content as C,
     DelimiterSize, DelimiterType, Dimension, DimensionUnit, EnvironmentFlow, Event as E, Font,
-    Grouping as G, GroupingKind, Line, MatrixType, RelationContent, ScriptPosition as SP,
-    ScriptType as ST, StateChange as SC, Style as S, Visual as V,
+    Framing, Grouping as G, GroupingKind, Line, MatrixType, RelationContent, ScriptPosition as SP,
+    ScriptType as ST, StateChange as SC, Style as S, ValidationError, Visual as V,
 };
 
 use super::{
     lex,
     tables::{
-        char_delimiter_map, control_sequence_delimiter_map, is_binary, is_relation, token_to_delim,
+        char_delimiter_map, control_sequence_delimiter_map, is_binary, is_environment,
+        is_primitive, is_relation, token_to_delim,
     },
+    text::text_box,
     AlignmentCount, Argument, CharToken, ErrorKind, InnerParser, InnerResult, Instruction as I,
-    Token,
+    ParserState, Token, MAX_NESTING_DEPTH,
 };
 
 impl<'b, 'store> InnerParser<'b, 'store> {
@@ -81,7 +84,7 @@ impl<'b, 'store> InnerParser<'b, 'store> {
             },
 
             '~' => {
-                E::Content(C::Text("&nbsp;"))
+                E::Content(C::Text("&nbsp;".into()))
             },
 
             '0'..='9' => {
@@ -103,29 +106,10 @@ impl<'b, 'store> InnerParser<'b, 'store> {
                 let (number, rest) = content.split_at(len);
                 self.content = rest;
                 self.buffer
-                    .push(I::Event(E::Content(C::Number(number))));
+                    .push(I::Event(E::Content(C::Number(number.into()))));
                 return Ok(())
             }
-            // Punctuation
-            '.' | ',' | ';' => E::Content(C::Punctuation(token.into())),
-            '\'' => ordinary('′'),
-            '-' => binary('−'),
-            '*' => binary('∗'),
-            c if is_binary(c) => binary(c),
-            c if is_relation(c) => relation(c),
-            c if char_delimiter_map(c).is_some() => {
-                let (content, ty) = char_delimiter_map(c).unwrap();
-                if ty == DelimiterType::Fence {
-                    ordinary(content)
-                } else {
-                    E::Content(C::Delimiter {
-                        content,
-                        size: None,
-                        ty,
-                    })
-                }
-            }
-            c => ordinary(c),
+            c => char_event(c),
         });
         self.buffer.push(instruction);
         Ok(())
@@ -136,37 +120,76 @@ impl<'b, 'store> InnerParser<'b, 'store> {
         let event = match control_sequence {
             "arccos" | "cos" | "csc" | "exp" | "ker" | "sinh" | "arcsin" | "cosh" | "deg"
             | "lg" | "ln" | "arctan" | "cot" | "det" | "hom" | "log" | "sec" | "tan" | "arg"
-            | "coth" | "dim" | "sin" | "tanh" | "sgn" => E::Content(C::Function(control_sequence)),
+            | "coth" | "dim" | "sin" | "tanh" | "sgn" => {
+                E::Content(C::Function(control_sequence.into()))
+            }
             "lim" | "Pr" | "sup" | "max" | "inf" | "gcd" | "min" => {
                 self.state.allow_script_modifiers = true;
                 self.state.script_position = SP::Movable;
-                E::Content(C::Function(control_sequence))
+                E::Content(C::Function(control_sequence.into()))
             }
             "liminf" => {
                 self.state.allow_script_modifiers = true;
                 self.state.script_position = SP::Movable;
-                E::Content(C::Function("lim inf"))
+                E::Content(C::Function("lim inf".into()))
             }
             "limsup" => {
                 self.state.allow_script_modifiers = true;
                 self.state.script_position = SP::Movable;
-                E::Content(C::Function("lim sup"))
+                E::Content(C::Function("lim sup".into()))
             }
 
             "operatorname" => {
                 self.state.allow_script_modifiers = true;
+                if let Some(rest) = self.content.trim_start().strip_prefix('*') {
+                    self.content = rest;
+                    self.state.script_position = SP::Movable;
+                }
                 let argument = lex::argument(&mut self.content)?;
                 match argument {
                     Argument::Token(Token::ControlSequence(_)) => {
                         return Err(ErrorKind::ControlSequenceAsArgument)
                     }
-                    Argument::Token(Token::Character(char_)) => {
-                        E::Content(C::Function(char_.as_str()))
+                    Argument::Token(Token::Character(char_)) => E::Content(C::Function(
+                        char_.as_str()[..char::from(char_).len_utf8()].into(),
+                    )),
+                    Argument::Group(content) => {
+                        E::Content(C::Function(self.operator_name(content)?))
                     }
-                    Argument::Group(content) => E::Content(C::Function(content)),
                 }
             }
-            "bmod" => E::Content(C::Function("mod")),
+            "DeclareMathOperator" => {
+                let star = if let Some(rest) = self.content.trim_start().strip_prefix('*') {
+                    self.content = rest;
+                    "*"
+                } else {
+                    ""
+                };
+                let cs = match lex::argument(&mut self.content)? {
+                    Argument::Token(Token::ControlSequence(cs)) => cs,
+                    Argument::Group(group) => lex::control_sequence(&mut group.trim())?,
+                    Argument::Token(Token::Character(_)) => return Err(ErrorKind::ControlSequence),
+                };
+                let name = lex::brace_argument(&mut self.content)?;
+                if self.macro_context.contains(cs)
+                    || self.macro_context.primitive(cs).is_some()
+                    || is_primitive(cs)
+                {
+                    return Err(ErrorKind::MacroAlreadyDefined);
+                }
+
+                let mut replacement = bumpalo::collections::String::new_in(self.storage);
+                replacement.push_str("\\operatorname");
+                replacement.push_str(star);
+                replacement.push('{');
+                replacement.push_str(name);
+                replacement.push('}');
+                self.state.skip_scripts = true;
+                return self
+                    .macro_context
+                    .insert_command(cs, 0, None, replacement.into_bump_str());
+            }
+            "bmod" => E::Content(C::Function("mod".into())),
             "pmod" => {
                 let argument = lex::argument(&mut self.content)?;
                 self.buffer.extend([
@@ -181,7 +204,7 @@ impl<'b, 'store> InnerParser<'b, 'store> {
                         size: None,
                         ty: DelimiterType::Open,
                     })),
-                    I::Event(E::Content(C::Function("mod"))),
+                    I::Event(E::Content(C::Function("mod".into()))),
                 ]);
                 self.handle_argument(argument)?;
                 self.buffer.extend([
@@ -195,8 +218,6 @@ impl<'b, 'store> InnerParser<'b, 'store> {
                 return Ok(());
             }
 
-            // TODO: Operators with '*', for operatorname* and friends
-
             ////////////////////////////////
             // Atom-type (\math*) commands //
             ////////////////////////////////
@@ -742,8 +763,9 @@ impl<'b, 'store> InnerParser<'b, 'store> {
             "strut" => E::Space {
                 width: None,
                 height: Some(Dimension::new(1.0, DimensionUnit::Em)),
+                depth: None,
             },
-            "~" | "nobreakspace" => E::Content(C::Text("&nbsp;")),
+            "~" | "nobreakspace" => E::Content(C::Text("&nbsp;".into())),
             // Variable spacing
             "kern" => {
                 let dimension = lex::dimension_or_braced(&mut self.content)?;
@@ -803,6 +825,21 @@ impl<'b, 'store> InnerParser<'b, 'store> {
                     depth: None,
                 }
             }
+            "rule" => {
+                let raise = lex::optional_argument(&mut self.content)
+                    .map(|mut raise| lex::dimension(&mut raise))
+                    .transpose()?;
+                let width = lex::dimension_or_braced(&mut self.content)?;
+                let height = lex::dimension_or_braced(&mut self.content)?;
+                if let Some(raise) = raise {
+                    self.buffer.push(I::Event(E::Visual(V::Raise(raise))));
+                }
+                E::Rule {
+                    width,
+                    height,
+                    depth: Dimension::new(0., DimensionUnit::Em),
+                }
+            }
             // MathJax extension: `\Space{width}{height}{depth}`.
             "Space" => {
                 let Argument::Group(mut width_arg) = lex::argument(&mut self.content)? else {
@@ -1466,10 +1503,7 @@ impl<'b, 'store> InnerParser<'b, 'store> {
                     self.buffer.push(I::Event(E::Visual(V::Root)));
                     let arg = lex::argument(&mut self.content)?;
                     self.handle_argument(arg)?;
-                    self.buffer.push(I::SubGroup {
-                        content: index,
-                        allowed_alignment_count: None,
-                    });
+                    self.handle_argument(Argument::Group(index))?;
                 } else {
                     self.buffer.push(I::Event(E::Visual(V::SquareRoot)));
                     let arg = lex::argument(&mut self.content)?;
@@ -1509,6 +1543,17 @@ impl<'b, 'store> InnerParser<'b, 'store> {
             "textit" => return self.text_argument(Some(Font::Italic)),
             "textsf" => return self.text_argument(Some(Font::SansSerif)),
             "texttt" => return self.text_argument(Some(Font::Monospace)),
+            "textup" => return self.text_argument(Some(Font::UpRight)),
+            "emph" => return self.text_argument(Some(Font::Italic)),
+            "textnormal" => {
+                let text = lex::text_argument(&mut self.content)?;
+                self.buffer.extend([
+                    I::Event(E::Begin(G::Normal)),
+                    I::Event(E::StateChange(SC::Font(None))),
+                ]);
+                self.text_mode(text, None)?;
+                E::End
+            }
 
             "not" | "cancel" => {
                 self.buffer.push(I::Event(E::Visual(V::Negation)));
@@ -1516,6 +1561,19 @@ impl<'b, 'store> InnerParser<'b, 'store> {
                 self.handle_argument(argument)?;
                 return Ok(());
             }
+            "boxed" | "phantom" | "hphantom" | "vphantom" | "smash" | "llap" | "rlap" | "clap"
+            | "mathllap" | "mathrlap" | "mathclap" | "raise" | "lower" | "raisebox" | "fbox"
+            | "framebox" | "hbox" | "mbox" | "vcenter" => {
+                return self.box_primitive(control_sequence)
+            }
+            "mathchoice" => {
+                self.buffer.push(I::Event(E::Visual(V::MathChoice)));
+                for _ in 0..4 {
+                    let argument = lex::argument(&mut self.content)?;
+                    self.handle_argument(argument)?;
+                }
+                return Ok(());
+            }
             "char" => {
                 let number = lex::unsigned_integer(&mut self.content)?;
                 if number > 255 {
@@ -1549,355 +1607,7 @@ impl<'b, 'store> InnerParser<'b, 'store> {
             }
             "endgroup" => return Err(ErrorKind::UnbalancedGroup(None)),
 
-            "begin" => {
-                let Argument::Group(argument) = lex::argument(&mut self.content)? else {
-                    return Err(ErrorKind::Argument);
-                };
-
-                let mut style = None;
-                let mut wrap: Option<(char, char)> = None;
-
-                let (environment, align_count, grouping_kind) = match argument {
-                    "array" => {
-                        let (grouping, count) = self.array_environment()?;
-                        (grouping, count, GroupingKind::Array { display: false })
-                    }
-                    "darray" => {
-                        style = Some(S::Display);
-                        let (grouping, count) = self.array_environment()?;
-                        (grouping, count, GroupingKind::Array { display: true })
-                    }
-                    "matrix" => (
-                        G::Matrix {
-                            alignment: ColumnAlignment::Center,
-                        },
-                        u16::MAX,
-                        GroupingKind::Matrix {
-                            ty: MatrixType::Normal,
-                            column_spec: false,
-                        },
-                    ),
-                    "matrix*" => (
-                        G::Matrix {
-                            alignment: self
-                                .optional_alignment()?
-                                .unwrap_or(ColumnAlignment::Center),
-                        },
-                        u16::MAX,
-                        GroupingKind::Matrix {
-                            ty: MatrixType::Normal,
-                            column_spec: true,
-                        },
-                    ),
-                    "smallmatrix" => {
-                        style = Some(S::Text);
-                        (
-                            G::Matrix {
-                                alignment: ColumnAlignment::Center,
-                            },
-                            u16::MAX,
-                            GroupingKind::Matrix {
-                                ty: MatrixType::Small,
-                                column_spec: false,
-                            },
-                        )
-                    }
-                    "pmatrix" => {
-                        wrap = Some(('(', ')'));
-                        (
-                            G::Matrix {
-                                alignment: ColumnAlignment::Center,
-                            },
-                            u16::MAX,
-                            GroupingKind::Matrix {
-                                ty: MatrixType::Parens,
-                                column_spec: false,
-                            },
-                        )
-                    }
-                    "pmatrix*" => {
-                        wrap = Some(('(', ')'));
-                        (
-                            G::Matrix {
-                                alignment: self
-                                    .optional_alignment()?
-                                    .unwrap_or(ColumnAlignment::Center),
-                            },
-                            u16::MAX,
-                            GroupingKind::Matrix {
-                                ty: MatrixType::Parens,
-                                column_spec: true,
-                            },
-                        )
-                    }
-                    "bmatrix" => {
-                        wrap = Some(('[', ']'));
-                        (
-                            G::Matrix {
-                                alignment: ColumnAlignment::Center,
-                            },
-                            u16::MAX,
-                            GroupingKind::Matrix {
-                                ty: MatrixType::Brackets,
-                                column_spec: false,
-                            },
-                        )
-                    }
-                    "bmatrix*" => {
-                        wrap = Some(('[', ']'));
-                        (
-                            G::Matrix {
-                                alignment: self
-                                    .optional_alignment()?
-                                    .unwrap_or(ColumnAlignment::Center),
-                            },
-                            u16::MAX,
-                            GroupingKind::Matrix {
-                                ty: MatrixType::Brackets,
-                                column_spec: true,
-                            },
-                        )
-                    }
-                    "vmatrix" => {
-                        wrap = Some(('|', '|'));
-                        (
-                            G::Matrix {
-                                alignment: ColumnAlignment::Center,
-                            },
-                            u16::MAX,
-                            GroupingKind::Matrix {
-                                ty: MatrixType::Vertical,
-                                column_spec: false,
-                            },
-                        )
-                    }
-                    "vmatrix*" => {
-                        wrap = Some(('|', '|'));
-                        (
-                            G::Matrix {
-                                alignment: self
-                                    .optional_alignment()?
-                                    .unwrap_or(ColumnAlignment::Center),
-                            },
-                            u16::MAX,
-                            GroupingKind::Matrix {
-                                ty: MatrixType::Vertical,
-                                column_spec: true,
-                            },
-                        )
-                    }
-                    "Vmatrix" => {
-                        wrap = Some(('‖', '‖'));
-                        (
-                            G::Matrix {
-                                alignment: ColumnAlignment::Center,
-                            },
-                            u16::MAX,
-                            GroupingKind::Matrix {
-                                ty: MatrixType::DoubleVertical,
-                                column_spec: false,
-                            },
-                        )
-                    }
-                    "Vmatrix*" => {
-                        wrap = Some(('‖', '‖'));
-                        (
-                            G::Matrix {
-                                alignment: self
-                                    .optional_alignment()?
-                                    .unwrap_or(ColumnAlignment::Center),
-                            },
-                            u16::MAX,
-                            GroupingKind::Matrix {
-                                ty: MatrixType::DoubleVertical,
-                                column_spec: true,
-                            },
-                        )
-                    }
-                    "Bmatrix" => {
-                        wrap = Some(('{', '}'));
-                        (
-                            G::Matrix {
-                                alignment: ColumnAlignment::Center,
-                            },
-                            u16::MAX,
-                            GroupingKind::Matrix {
-                                ty: MatrixType::Braces,
-                                column_spec: false,
-                            },
-                        )
-                    }
-                    "Bmatrix*" => {
-                        wrap = Some(('{', '}'));
-                        (
-                            G::Matrix {
-                                alignment: self
-                                    .optional_alignment()?
-                                    .unwrap_or(ColumnAlignment::Center),
-                            },
-                            u16::MAX,
-                            GroupingKind::Matrix {
-                                ty: MatrixType::Braces,
-                                column_spec: true,
-                            },
-                        )
-                    }
-                    "cases" => (
-                        G::Cases { left: true },
-                        1,
-                        GroupingKind::Cases {
-                            left: true,
-                            display: false,
-                        },
-                    ),
-                    "dcases" => {
-                        style = Some(S::Display);
-                        (
-                            G::Cases { left: true },
-                            1,
-                            GroupingKind::Cases {
-                                left: true,
-                                display: true,
-                            },
-                        )
-                    }
-                    "rcases" => (
-                        G::Cases { left: false },
-                        1,
-                        GroupingKind::Cases {
-                            left: false,
-                            display: false,
-                        },
-                    ),
-                    "drcases" => {
-                        style = Some(S::Display);
-                        (
-                            G::Cases { left: false },
-                            1,
-                            GroupingKind::Cases {
-                                left: false,
-                                display: true,
-                            },
-                        )
-                    }
-                    "equation" => (
-                        G::Equation { eq_numbers: true },
-                        0,
-                        GroupingKind::Equation { eq_numbers: true },
-                    ),
-                    "equation*" => (
-                        G::Equation { eq_numbers: false },
-                        0,
-                        GroupingKind::Equation { eq_numbers: false },
-                    ),
-                    "align" => (
-                        G::Align { eq_numbers: true },
-                        u16::MAX,
-                        GroupingKind::Align { eq_numbers: true },
-                    ),
-                    "align*" => (
-                        G::Align { eq_numbers: false },
-                        u16::MAX,
-                        GroupingKind::Align { eq_numbers: false },
-                    ),
-                    "aligned" => (G::Aligned, u16::MAX, GroupingKind::Aligned),
-                    "gather" => (
-                        G::Gather { eq_numbers: true },
-                        0,
-                        GroupingKind::Gather { eq_numbers: true },
-                    ),
-                    "gather*" => (
-                        G::Gather { eq_numbers: false },
-                        0,
-                        GroupingKind::Gather { eq_numbers: false },
-                    ),
-                    "gathered" => (G::Gathered, 0, GroupingKind::Gathered),
-                    "alignat" => {
-                        let pairs = match lex::argument(&mut self.content)? {
-                            Argument::Group(mut content) => lex::unsigned_integer(&mut content),
-                            _ => Err(ErrorKind::Argument),
-                        }? as u16;
-                        (
-                            G::Alignat {
-                                pairs,
-                                eq_numbers: true,
-                            },
-                            (pairs * 2).saturating_sub(1),
-                            GroupingKind::Alignat { eq_numbers: true },
-                        )
-                    }
-                    "alignat*" => {
-                        let pairs = match lex::argument(&mut self.content)? {
-                            Argument::Group(mut content) => lex::unsigned_integer(&mut content),
-                            _ => Err(ErrorKind::Argument),
-                        }? as u16;
-                        (
-                            G::Alignat {
-                                pairs,
-                                eq_numbers: false,
-                            },
-                            (pairs * 2).saturating_sub(1),
-                            GroupingKind::Alignat { eq_numbers: false },
-                        )
-                    }
-                    "alignedat" => {
-                        let pairs = match lex::argument(&mut self.content)? {
-                            Argument::Group(mut content) => lex::unsigned_integer(&mut content),
-                            _ => Err(ErrorKind::Argument),
-                        }? as u16;
-                        (
-                            G::Alignedat { pairs },
-                            (pairs * 2).saturating_sub(1),
-                            GroupingKind::Alignedat,
-                        )
-                    }
-                    "subarray" => {
-                        let alignment = match lex::argument(&mut self.content)? {
-                            Argument::Group("l") => ColumnAlignment::Left,
-                            Argument::Group("c") => ColumnAlignment::Center,
-                            Argument::Group("r") => ColumnAlignment::Right,
-                            _ => return Err(ErrorKind::Argument),
-                        };
-                        (G::SubArray { alignment }, 0, GroupingKind::SubArray)
-                    }
-                    "multline" => (G::Multline, 0, GroupingKind::Multline),
-                    "split" => (G::Split, 1, GroupingKind::Split),
-                    _ => return Err(ErrorKind::Environment),
-                };
-
-                let wrap_used = if let Some((left, right)) = wrap {
-                    self.buffer
-                        .push(I::Event(E::Begin(G::LeftRight(Some(left), Some(right)))));
-                    true
-                } else {
-                    false
-                };
-
-                let horizontal_lines = lex::horizontal_lines(&mut self.content);
-                let content = lex::group_content(&mut self.content, grouping_kind)?;
-                self.buffer.push(I::Event(E::Begin(environment)));
-                if let Some(style) = style {
-                    self.buffer.push(I::Event(E::StateChange(SC::Style(style))));
-                }
-                if horizontal_lines.len() > 0 {
-                    self.buffer
-                        .push(I::Event(E::EnvironmentFlow(EnvironmentFlow::StartLines {
-                            lines: horizontal_lines,
-                        })));
-                }
-                self.buffer.extend([
-                    I::SubGroup {
-                        content,
-                        allowed_alignment_count: Some(AlignmentCount::new(align_count)),
-                    },
-                    I::Event(E::End),
-                ]);
-
-                if wrap_used {
-                    self.buffer.push(I::Event(E::End));
-                }
-                return Ok(());
-            }
+            "begin" => return self.begin_environment(),
             "end" => return Err(ErrorKind::UnbalancedGroup(None)),
             "\\" | "cr"
                 if self.state.allowed_alignment_count.is_some()
@@ -1918,6 +1628,46 @@ impl<'b, 'store> InnerParser<'b, 'store> {
                 })
             }
             "\\" | "cr" => return Err(ErrorKind::NewLine),
+            "tag" | "notag" | "nonumber" | "label"
+                if !self
+                    .state
+                    .allowed_alignment_count
+                    .as_ref()
+                    .is_some_and(|count| count.tags)
+                    || self.state.handling_argument =>
+            {
+                return Err(ErrorKind::Tag)
+            }
+            "tag" => {
+                let parenthesized = if let Some(rest) = self.content.trim_start().strip_prefix('*')
+                {
+                    self.content = rest;
+                    false
+                } else {
+                    true
+                };
+                let text = lex::text_argument(&mut self.content)?;
+                let tag = self.tag(text)?;
+                let count = self.state.allowed_alignment_count.as_mut().unwrap();
+                if count.tagged {
+                    return Err(ErrorKind::MultipleTags);
+                }
+                count.tagged = true;
+                E::EnvironmentFlow(EnvironmentFlow::Tag { tag, parenthesized })
+            }
+            "notag" | "nonumber" => E::EnvironmentFlow(EnvironmentFlow::NoNumber),
+            "ref" | "eqref" => {
+                let label = lex::text_argument(&mut self.content)?.trim();
+                E::Content(C::Reference {
+                    label: label.into(),
+                    number: None,
+                    parenthesized: control_sequence == "eqref",
+                })
+            }
+            "label" => {
+                let label = lex::text_argument(&mut self.content)?.trim();
+                E::EnvironmentFlow(EnvironmentFlow::Label(label.into()))
+            }
 
             // Delimiters
             cs if control_sequence_delimiter_map(cs).is_some() => {
@@ -1930,7 +1680,7 @@ impl<'b, 'store> InnerParser<'b, 'store> {
             }
 
             // Spacing
-            c if c.trim_start().is_empty() => E::Content(C::Text("&nbsp;")),
+            c if c.trim_start().is_empty() => E::Content(C::Text("&nbsp;".into())),
 
             // Macros
             "def" => {
@@ -1957,12 +1707,575 @@ impl<'b, 'store> InnerParser<'b, 'store> {
             "newcommand" => return self.new_command(Some(false)),
             "renewcommand" => return self.new_command(Some(true)),
             "providecommand" => return self.new_command(None),
-            _ => return Err(ErrorKind::UnknownPrimitive),
+            "newenvironment" => return self.new_environment(false),
+            "renewenvironment" => return self.new_environment(true),
+            _ => return self.custom_primitive(control_sequence),
         };
         self.buffer.push(I::Event(event));
         Ok(())
     }
 
+    /// Return the name of an operator from its source, as given to `\operatorname`.
+    ///
+    /// The name may contain spaces (e.g., `\,`) and other commands, which are parsed as math and
+    /// flattened into plain text.
+    fn operator_name(&mut self, content: &'store str) -> InnerResult<Cow<'store, str>> {
+        if content.bytes().all(|b| b.is_ascii_alphabetic()) {
+            return Ok(content.into());
+        }
+        self.flattened_text(content, false).map(Cow::Owned)
+    }
+
+    /// Return the text of a tag from its source, as given to `\tag`.
+    ///
+    /// The source is parsed in text mode, and nested math is flattened into plain text.
+    fn tag(&mut self, content: &'store str) -> InnerResult<Cow<'store, str>> {
+        if content
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b".,:;!?()[]/+*=".contains(&b))
+        {
+            return Ok(content.into());
+        }
+        self.flattened_text(content, true).map(Cow::Owned)
+    }
+
+    /// Parse the content, in text mode if `text` is set or as math otherwise, and flatten the
+    /// resulting events into plain text.
+    ///
+    /// The content is parsed with an explicit stack, like the parser does, so the recursion only
+    /// goes through the control sequences found in the content, e.g., another operator name.
+    fn flattened_text(&mut self, content: &'store str, text: bool) -> InnerResult<String> {
+        if self.nesting_depth >= MAX_NESTING_DEPTH {
+            return Err(ErrorKind::GroupNestingLimit);
+        }
+        self.nesting_depth += 1;
+        let rest = self.content;
+        let state = std::mem::take(&mut self.state);
+        let start = self.buffer.len();
+
+        let result = self.flatten(content, text, start);
+
+        self.buffer.truncate(start);
+        self.state = state;
+        self.content = rest;
+        self.nesting_depth -= 1;
+        result
+    }
+
+    fn flatten(&mut self, content: &'store str, text: bool, start: usize) -> InnerResult<String> {
+        if text {
+            self.text_mode(content, None)?;
+        } else {
+            self.buffer.push(I::SubGroup {
+                content,
+                allowed_alignment_count: None,
+            });
+        }
+        let mut stack: Vec<_> = self.buffer.drain(start..).rev().collect();
+
+        let mut name = String::new();
+        while let Some(instruction) = stack.pop() {
+            let event = match instruction {
+                I::Event(event) => event,
+                I::SubGroup { content, .. } => {
+                    self.content = content;
+                    self.state = ParserState::default();
+                    if !self.handle_next_token()? {
+                        continue;
+                    }
+                    if !self.state.skip_scripts && self.content.trim_start().starts_with(['^', '_'])
+                    {
+                        return Err(ErrorKind::ScriptAsArgument);
+                    }
+                    stack.push(I::SubGroup {
+                        content: self.content,
+                        allowed_alignment_count: None,
+                    });
+                    stack.extend(self.buffer.drain(start..).rev());
+                    continue;
+                }
+            };
+            match event {
+                E::Content(C::Text(text)) if text == "&nbsp;" => name.push(' '),
+                E::Content(C::Text(text) | C::Number(text) | C::Function(text)) => {
+                    name.push_str(&text)
+                }
+                // Like in `LaTeX`, these are text characters in operator names.
+                E::Content(C::BinaryOp { content: '−', .. }) => name.push('-'),
+                E::Content(C::BinaryOp { content: '∗', .. }) => name.push('*'),
+                E::Content(
+                    C::Ordinary { content, .. }
+                    | C::BinaryOp { content, .. }
+                    | C::LargeOp { content, .. }
+                    | C::Delimiter { content, .. }
+                    | C::Punctuation(content),
+                ) => name.push(content),
+                E::Content(C::Relation { content, .. }) => {
+                    let (first, second) = content.chars();
+                    name.push(first);
+                    name.extend(second);
+                }
+                E::Space {
+                    width: Some(width), ..
+                } if width.value > 0. => name.push(' '),
+                E::Space { .. } | E::Begin(G::Normal) | E::End | E::StateChange(_) => {}
+                E::Script { .. } => return Err(ErrorKind::ScriptAsArgument),
+                _ => return Err(ErrorKind::Argument),
+            }
+        }
+        Ok(name)
+    }
+
+    /// Handle the `\begin` command, which starts a built-in or user-defined environment.
+    fn begin_environment(&mut self) -> InnerResult<()> {
+        let Argument::Group(argument) = lex::argument(&mut self.content)? else {
+            return Err(ErrorKind::Argument);
+        };
+        if !is_environment(argument) {
+            return self.user_environment(argument);
+        }
+
+        let mut style = None;
+        let mut wrap: Option<(char, char)> = None;
+
+        let (environment, align_count, grouping_kind) = match argument {
+            "array" => {
+                let (grouping, count) = self.array_environment()?;
+                (grouping, count, GroupingKind::Array { display: false })
+            }
+            "darray" => {
+                style = Some(S::Display);
+                let (grouping, count) = self.array_environment()?;
+                (grouping, count, GroupingKind::Array { display: true })
+            }
+            "matrix" => (
+                G::Matrix {
+                    alignment: ColumnAlignment::Center,
+                },
+                u16::MAX,
+                GroupingKind::Matrix {
+                    ty: MatrixType::Normal,
+                    column_spec: false,
+                },
+            ),
+            "matrix*" => (
+                G::Matrix {
+                    alignment: self
+                        .optional_alignment()?
+                        .unwrap_or(ColumnAlignment::Center),
+                },
+                u16::MAX,
+                GroupingKind::Matrix {
+                    ty: MatrixType::Normal,
+                    column_spec: true,
+                },
+            ),
+            "smallmatrix" => {
+                style = Some(S::Text);
+                (
+                    G::Matrix {
+                        alignment: ColumnAlignment::Center,
+                    },
+                    u16::MAX,
+                    GroupingKind::Matrix {
+                        ty: MatrixType::Small,
+                        column_spec: false,
+                    },
+                )
+            }
+            "pmatrix" => {
+                wrap = Some(('(', ')'));
+                (
+                    G::Matrix {
+                        alignment: ColumnAlignment::Center,
+                    },
+                    u16::MAX,
+                    GroupingKind::Matrix {
+                        ty: MatrixType::Parens,
+                        column_spec: false,
+                    },
+                )
+            }
+            "pmatrix*" => {
+                wrap = Some(('(', ')'));
+                (
+                    G::Matrix {
+                        alignment: self
+                            .optional_alignment()?
+                            .unwrap_or(ColumnAlignment::Center),
+                    },
+                    u16::MAX,
+                    GroupingKind::Matrix {
+                        ty: MatrixType::Parens,
+                        column_spec: true,
+                    },
+                )
+            }
+            "bmatrix" => {
+                wrap = Some(('[', ']'));
+                (
+                    G::Matrix {
+                        alignment: ColumnAlignment::Center,
+                    },
+                    u16::MAX,
+                    GroupingKind::Matrix {
+                        ty: MatrixType::Brackets,
+                        column_spec: false,
+                    },
+                )
+            }
+            "bmatrix*" => {
+                wrap = Some(('[', ']'));
+                (
+                    G::Matrix {
+                        alignment: self
+                            .optional_alignment()?
+                            .unwrap_or(ColumnAlignment::Center),
+                    },
+                    u16::MAX,
+                    GroupingKind::Matrix {
+                        ty: MatrixType::Brackets,
+                        column_spec: true,
+                    },
+                )
+            }
+            "vmatrix" => {
+                wrap = Some(('|', '|'));
+                (
+                    G::Matrix {
+                        alignment: ColumnAlignment::Center,
+                    },
+                    u16::MAX,
+                    GroupingKind::Matrix {
+                        ty: MatrixType::Vertical,
+                        column_spec: false,
+                    },
+                )
+            }
+            "vmatrix*" => {
+                wrap = Some(('|', '|'));
+                (
+                    G::Matrix {
+                        alignment: self
+                            .optional_alignment()?
+                            .unwrap_or(ColumnAlignment::Center),
+                    },
+                    u16::MAX,
+                    GroupingKind::Matrix {
+                        ty: MatrixType::Vertical,
+                        column_spec: true,
+                    },
+                )
+            }
+            "Vmatrix" => {
+                wrap = Some(('‖', '‖'));
+                (
+                    G::Matrix {
+                        alignment: ColumnAlignment::Center,
+                    },
+                    u16::MAX,
+                    GroupingKind::Matrix {
+                        ty: MatrixType::DoubleVertical,
+                        column_spec: false,
+                    },
+                )
+            }
+            "Vmatrix*" => {
+                wrap = Some(('‖', '‖'));
+                (
+                    G::Matrix {
+                        alignment: self
+                            .optional_alignment()?
+                            .unwrap_or(ColumnAlignment::Center),
+                    },
+                    u16::MAX,
+                    GroupingKind::Matrix {
+                        ty: MatrixType::DoubleVertical,
+                        column_spec: true,
+                    },
+                )
+            }
+            "Bmatrix" => {
+                wrap = Some(('{', '}'));
+                (
+                    G::Matrix {
+                        alignment: ColumnAlignment::Center,
+                    },
+                    u16::MAX,
+                    GroupingKind::Matrix {
+                        ty: MatrixType::Braces,
+                        column_spec: false,
+                    },
+                )
+            }
+            "Bmatrix*" => {
+                wrap = Some(('{', '}'));
+                (
+                    G::Matrix {
+                        alignment: self
+                            .optional_alignment()?
+                            .unwrap_or(ColumnAlignment::Center),
+                    },
+                    u16::MAX,
+                    GroupingKind::Matrix {
+                        ty: MatrixType::Braces,
+                        column_spec: true,
+                    },
+                )
+            }
+            "cases" => (
+                G::Cases { left: true },
+                1,
+                GroupingKind::Cases {
+                    left: true,
+                    display: false,
+                },
+            ),
+            "dcases" => {
+                style = Some(S::Display);
+                (
+                    G::Cases { left: true },
+                    1,
+                    GroupingKind::Cases {
+                        left: true,
+                        display: true,
+                    },
+                )
+            }
+            "rcases" => (
+                G::Cases { left: false },
+                1,
+                GroupingKind::Cases {
+                    left: false,
+                    display: false,
+                },
+            ),
+            "drcases" => {
+                style = Some(S::Display);
+                (
+                    G::Cases { left: false },
+                    1,
+                    GroupingKind::Cases {
+                        left: false,
+                        display: true,
+                    },
+                )
+            }
+            "equation" => (
+                G::Equation { eq_numbers: true },
+                0,
+                GroupingKind::Equation { eq_numbers: true },
+            ),
+            "equation*" => (
+                G::Equation { eq_numbers: false },
+                0,
+                GroupingKind::Equation { eq_numbers: false },
+            ),
+            "align" => (
+                G::Align { eq_numbers: true },
+                u16::MAX,
+                GroupingKind::Align { eq_numbers: true },
+            ),
+            "align*" => (
+                G::Align { eq_numbers: false },
+                u16::MAX,
+                GroupingKind::Align { eq_numbers: false },
+            ),
+            "aligned" => (G::Aligned, u16::MAX, GroupingKind::Aligned),
+            "gather" => (
+                G::Gather { eq_numbers: true },
+                0,
+                GroupingKind::Gather { eq_numbers: true },
+            ),
+            "gather*" => (
+                G::Gather { eq_numbers: false },
+                0,
+                GroupingKind::Gather { eq_numbers: false },
+            ),
+            "gathered" => (G::Gathered, 0, GroupingKind::Gathered),
+            "alignat" => {
+                let pairs = match lex::argument(&mut self.content)? {
+                    Argument::Group(mut content) => lex::unsigned_integer(&mut content),
+                    _ => Err(ErrorKind::Argument),
+                }? as u16;
+                (
+                    G::Alignat {
+                        pairs,
+                        eq_numbers: true,
+                    },
+                    (pairs * 2).saturating_sub(1),
+                    GroupingKind::Alignat { eq_numbers: true },
+                )
+            }
+            "alignat*" => {
+                let pairs = match lex::argument(&mut self.content)? {
+                    Argument::Group(mut content) => lex::unsigned_integer(&mut content),
+                    _ => Err(ErrorKind::Argument),
+                }? as u16;
+                (
+                    G::Alignat {
+                        pairs,
+                        eq_numbers: false,
+                    },
+                    (pairs * 2).saturating_sub(1),
+                    GroupingKind::Alignat { eq_numbers: false },
+                )
+            }
+            "alignedat" => {
+                let pairs = match lex::argument(&mut self.content)? {
+                    Argument::Group(mut content) => lex::unsigned_integer(&mut content),
+                    _ => Err(ErrorKind::Argument),
+                }? as u16;
+                (
+                    G::Alignedat { pairs },
+                    (pairs * 2).saturating_sub(1),
+                    GroupingKind::Alignedat,
+                )
+            }
+            "subarray" => {
+                let alignment = match lex::argument(&mut self.content)? {
+                    Argument::Group("l") => ColumnAlignment::Left,
+                    Argument::Group("c") => ColumnAlignment::Center,
+                    Argument::Group("r") => ColumnAlignment::Right,
+                    _ => return Err(ErrorKind::Argument),
+                };
+                (G::SubArray { alignment }, 0, GroupingKind::SubArray)
+            }
+            "multline" => (G::Multline, 0, GroupingKind::Multline),
+            "split" => (G::Split, 1, GroupingKind::Split),
+            _ => unreachable!("the environment is listed in `ENVIRONMENTS`"),
+        };
+
+        let horizontal_lines = lex::horizontal_lines(&mut self.content);
+        let content = lex::group_content(&mut self.content, grouping_kind)?;
+        self.environment(
+            environment,
+            align_count,
+            style,
+            wrap,
+            horizontal_lines,
+            content,
+        );
+        Ok(())
+    }
+
+    /// Push the events of a mathematical environment, possibly wrapped in delimiters.
+    fn environment(
+        &mut self,
+        environment: G,
+        align_count: u16,
+        style: Option<S>,
+        wrap: Option<(char, char)>,
+        horizontal_lines: Box<[Line]>,
+        content: &'store str,
+    ) {
+        if let Some((left, right)) = wrap {
+            self.buffer
+                .push(I::Event(E::Begin(G::LeftRight(Some(left), Some(right)))));
+        }
+        let allowed_alignment_count = if event::allows_tags(&environment) {
+            AlignmentCount::with_tags(align_count)
+        } else {
+            AlignmentCount::new(align_count)
+        };
+        self.buffer.push(I::Event(E::Begin(environment)));
+        if !horizontal_lines.is_empty() {
+            self.buffer
+                .push(I::Event(E::EnvironmentFlow(EnvironmentFlow::StartLines {
+                    lines: horizontal_lines,
+                })));
+        }
+        if let Some(style) = style {
+            self.buffer.push(I::Event(E::StateChange(SC::Style(style))));
+        }
+        self.buffer.extend([
+            I::SubGroup {
+                content,
+                allowed_alignment_count: Some(allowed_alignment_count),
+            },
+            I::Event(E::End),
+        ]);
+        if wrap.is_some() {
+            self.buffer.push(I::Event(E::End));
+        }
+    }
+
+    /// Handle an environment defined through [`MacroSet::define_environment`].
+    ///
+    /// Environments defined through `\newenvironment` are expanded like macros, and never reach
+    /// this point.
+    ///
+    /// [`MacroSet::define_environment`]: crate::MacroSet::define_environment
+    fn user_environment(&mut self, name: &str) -> InnerResult<()> {
+        let environment = self
+            .macro_context
+            .grouping_environment(name)
+            .ok_or(ErrorKind::Environment)?;
+        let horizontal_lines = lex::horizontal_lines(&mut self.content);
+        let content = lex::delimited_content(
+            &mut self.content,
+            &environment.opening,
+            &environment.closing,
+        )
+        .ok_or_else(|| {
+            ErrorKind::UnbalancedGroup(Some(GroupingKind::Environment(name.to_owned().into())))
+        })?;
+        self.environment(
+            environment.grouping.clone(),
+            environment.max_alignments,
+            environment.style,
+            environment.delimiters,
+            horizontal_lines,
+            content,
+        );
+        Ok(())
+    }
+
+    /// Handle a primitive defined through [`MacroSet::define_primitive`].
+    ///
+    /// [`MacroSet::define_primitive`]: crate::MacroSet::define_primitive
+    pub(super) fn custom_primitive(&mut self, control_sequence: &'store str) -> InnerResult<()> {
+        let primitive = self
+            .macro_context
+            .primitive(control_sequence)
+            .ok_or(ErrorKind::UnknownPrimitive)?;
+        let events = event::validate(primitive.call(&mut self.content)?.into_iter().map(Ok))
+            .collect::<Result<Vec<_>, ValidationError<Infallible>>>()
+            .map_err(|_| ErrorKind::PrimitiveOutput)?;
+
+        // Like `\color`, state changes apply to the rest of the group and do not take scripts.
+        if events.iter().all(|e| matches!(e, E::StateChange(_))) {
+            self.state.skip_scripts = true;
+            self.buffer.extend(events.into_iter().map(I::Event));
+            return Ok(());
+        }
+        // Scripts are attached to a single element, so anything else is wrapped in a group.
+        let single_element = match events.as_slice() {
+            [E::Content(_) | E::Space { .. }] => true,
+            [E::Begin(_), .., E::End] => {
+                let mut depth = 0usize;
+                let group_end = events.iter().position(|e| {
+                    match e {
+                        E::Begin(_) => depth += 1,
+                        E::End => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0
+                });
+                group_end == Some(events.len() - 1)
+            }
+            _ => false,
+        };
+        if single_element {
+            self.buffer.extend(events.into_iter().map(I::Event));
+        } else {
+            self.buffer.push(I::Event(E::Begin(G::Normal)));
+            self.buffer.extend(events.into_iter().map(I::Event));
+            self.buffer.push(I::Event(E::End));
+        }
+        Ok(())
+    }
+
     /// Return a delimiter with the given size from the next character in the parser.
     fn sized_delim(&mut self, size: DelimiterSize) -> InnerResult<()> {
         let current = &mut self.content;
@@ -2045,7 +2358,8 @@ impl<'b, 'store> InnerParser<'b, 'store> {
         // classes when given multi-token arguments.
         if matches!(class, AtomClass::Op) {
             if let Argument::Group(content) = argument {
-                self.buffer.push(I::Event(E::Content(C::Function(content))));
+                self.buffer
+                    .push(I::Event(E::Content(C::Function(content.into()))));
                 return Ok(());
             }
         }
@@ -2131,22 +2445,102 @@ impl<'b, 'store> InnerParser<'b, 'store> {
         E::StateChange(SC::Style(style))
     }
 
+    /// Handle the primitives that put their argument in a box, e.g., `\fbox` or `\phantom`.
+    ///
+    /// These are handled out of `handle_primitive`, whose stack frame must stay small since it
+    /// recurses for control sequences given as arguments.
+    fn box_primitive(&mut self, control_sequence: &str) -> InnerResult<()> {
+        match control_sequence {
+            "boxed" => {
+                // `\boxed{x}` is defined as `\fbox{$\displaystyle x$}`.
+                self.buffer.extend([
+                    I::Event(E::Visual(V::Framed(Framing::default()))),
+                    I::Event(E::Begin(G::Normal)),
+                    I::Event(E::StateChange(SC::Style(S::Display))),
+                ]);
+                match lex::argument(&mut self.content)? {
+                    Argument::Group(content) => self.buffer.push(I::SubGroup {
+                        content,
+                        allowed_alignment_count: None,
+                    }),
+                    argument => self.handle_argument(argument)?,
+                }
+                self.buffer.push(I::Event(E::End));
+            }
+            "phantom" | "hphantom" | "vphantom" => {
+                self.buffer.push(I::Event(E::Visual(V::Phantom {
+                    horizontal: control_sequence != "vphantom",
+                    vertical: control_sequence != "hphantom",
+                })));
+                let argument = lex::argument(&mut self.content)?;
+                self.handle_argument(argument)?;
+            }
+            "smash" => {
+                let (top, bottom) = match lex::optional_argument(&mut self.content) {
+                    None | Some("tb" | "bt") => (true, true),
+                    Some("t") => (true, false),
+                    Some("b") => (false, true),
+                    Some(_) => return Err(ErrorKind::Argument),
+                };
+                self.buffer
+                    .push(I::Event(E::Visual(V::Smash { top, bottom })));
+                let argument = lex::argument(&mut self.content)?;
+                self.handle_argument(argument)?;
+            }
+            "llap" | "rlap" | "clap" | "mathllap" | "mathrlap" | "mathclap" | "raisebox"
+            | "hbox" | "mbox" | "fbox" | "framebox" => {
+                let visual = text_box(
+                    control_sequence.trim_start_matches("math"),
+                    &mut self.content,
+                )?;
+                self.buffer.push(I::Event(E::Visual(visual)));
+                if control_sequence.starts_with("math") {
+                    let argument = lex::argument(&mut self.content)?;
+                    self.handle_argument(argument)?;
+                } else {
+                    self.text_argument(None)?;
+                }
+            }
+            "raise" | "lower" => {
+                let mut shift = lex::dimension_or_braced(&mut self.content)?;
+                if control_sequence == "lower" {
+                    shift.value = -shift.value;
+                }
+                self.buffer.push(I::Event(E::Visual(V::Raise(shift))));
+                let argument = lex::argument(&mut self.content)?;
+                self.handle_argument(argument)?;
+            }
+            "vcenter" => {
+                self.buffer.push(I::Event(E::Visual(V::VerticalCenter)));
+                let argument = lex::argument(&mut self.content)?;
+                self.handle_argument(argument)?;
+            }
+            _ => unreachable!("the control sequence is a box primitive"),
+        }
+        Ok(())
+    }
+
     fn text_argument(&mut self, font: Option<Font>) -> InnerResult<()> {
-        let argument = lex::argument(&mut self.content)?;
-        let text = match argument {
-            Argument::Token(Token::Character(c)) => c.as_str(),
-            Argument::Group(inner) => inner,
-            _ => return Err(ErrorKind::ControlSequenceAsArgument),
-        };
+        let text = lex::text_argument(&mut self.content)?;
         if let Some(font) = font {
             self.buffer.extend([
                 I::Event(E::Begin(G::Normal)),
                 I::Event(E::StateChange(SC::Font(Some(font)))),
-                I::Event(E::Content(C::Text(text))),
-                I::Event(E::End),
             ]);
-        } else {
-            self.buffer.push(I::Event(E::Content(C::Text(text))));
+            self.text_mode(text, Some(font))?;
+            self.buffer.push(I::Event(E::End));
+            return Ok(());
+        }
+
+        let start = self.buffer.len();
+        match self.text_mode(text, None)? {
+            0 => self.buffer.push(I::Event(E::Content(C::Text("".into())))),
+            1 => {}
+            // The text must count as a single element, e.g., when it is the base of a script.
+            _ => {
+                self.buffer.insert(start, I::Event(E::Begin(G::Normal)));
+                self.buffer.push(I::Event(E::End));
+            }
         }
         Ok(())
     }
@@ -2249,13 +2643,16 @@ impl<'b, 'store> InnerParser<'b, 'store> {
             });
         }
 
-        let arg_count = (lex::optional_argument(&mut self.content).ok_or(ErrorKind::Argument)?)
-            .parse::<u8>()
+        let arg_count = lex::optional_argument(&mut self.content)
+            .map_or(Ok(0), |count| count.trim().parse::<u8>())
             .map_err(|_| ErrorKind::Number)?;
         let first_arg_default = lex::optional_argument(&mut self.content);
-        if arg_count > 9 && arg_count >= first_arg_default.is_some() as u8 {
+        if arg_count > 9 {
             return Err(ErrorKind::TooManyParams);
         }
+        if arg_count == 0 && first_arg_default.is_some() {
+            return Err(ErrorKind::Argument);
+        }
 
         let replacement_text = lex::brace_argument(&mut self.content)?;
 
@@ -2266,6 +2663,60 @@ impl<'b, 'store> InnerParser<'b, 'store> {
             .insert_command(cs, arg_count, first_arg_default, replacement_text)?;
         Ok(())
     }
+
+    fn new_environment(&mut self, should_already_exist: bool) -> InnerResult<()> {
+        let name = lex::brace_argument(&mut self.content)?.trim();
+        let exists = is_environment(name) || self.macro_context.contains_environment(name);
+        if should_already_exist != exists {
+            return Err(if should_already_exist {
+                ErrorKind::EnvironmentNotDefined
+            } else {
+                ErrorKind::EnvironmentAlreadyDefined
+            });
+        }
+
+        let arg_count = lex::optional_argument(&mut self.content)
+            .map_or(Ok(0), |count| count.trim().parse::<u8>())
+            .map_err(|_| ErrorKind::Number)?;
+        let first_arg_default = lex::optional_argument(&mut self.content);
+        if arg_count > 9 {
+            return Err(ErrorKind::TooManyParams);
+        }
+        if arg_count == 0 && first_arg_default.is_some() {
+            return Err(ErrorKind::Argument);
+        }
+
+        let begin = lex::brace_argument(&mut self.content)?;
+        let end = lex::brace_argument(&mut self.content)?;
+        self.macro_context
+            .insert_environment(name, arg_count, first_arg_default, begin, end)
+    }
+}
+
+/// Returns the event produced by a character token that has no special meaning in math mode.
+pub(crate) fn char_event(c: char) -> E<'static> {
+    match c {
+        // Punctuation
+        '.' | ',' | ';' => E::Content(C::Punctuation(c)),
+        '\'' => ordinary('′'),
+        '-' => binary('−'),
+        '*' => binary('∗'),
+        c if is_binary(c) => binary(c),
+        c if is_relation(c) => relation(c),
+        c if char_delimiter_map(c).is_some() => {
+            let (content, ty) = char_delimiter_map(c).unwrap();
+            if ty == DelimiterType::Fence {
+                ordinary(content)
+            } else {
+                E::Content(C::Delimiter {
+                    content,
+                    size: None,
+                    ty,
+                })
+            }
+        }
+        c => ordinary(c),
+    }
 }
 
 #[inline]
@@ -2318,13 +2769,6 @@ enum AtomClass {
     Inner,
 }
 
-// TODO implementations:
-// - `raise`, `lower`
-// - `hbox`, `mbox`?
-// - `vcenter`
-// - `rule`
-// - `mathchoice` (TeXbook p. 151)
-
 // Unimplemented primitives:
 // `sl` (slanted) font: https://tug.org/texinfohtml/latex2e.html#index-_005csl
 // `bbit` (double-struck italic) font
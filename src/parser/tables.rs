@@ -178,6 +178,144 @@ pub fn control_sequence_delimiter_map(cs: &str) -> Option<(char, DelimiterType)>
     })
 }
 
+/// The control sequences matched by `InnerParser::handle_primitive`, sorted, which is checked by
+/// a test.
+#[rustfmt::skip]
+pub const PRIMITIVES: [&str; 859] = [
+    "!", "#", "$", "%", "&", ",", ":", ";", ">", "Alpha", "And", "Angstrom", "Bbbk", "Beta", "Big",
+    "Bigg", "Biggl", "Biggm", "Biggr", "Bigl", "Bigm", "Bigr", "Bot", "Box", "Bumpeq", "Cap",
+    "Chi", "Colonapprox", "Coloneq", "Coloneqq", "Colonsim", "Cup", "Dash", "DeclareMathOperator",
+    "Delta", "Diamond", "Doteq", "Downarrow", "Earth", "Epsilon", "Eqcolon", "Eqqcolon", "Eta",
+    "Finv", "Game", "Gamma", "Im", "Iota", "Join", "Kappa", "Lambda", "Leftarrow",
+    "Leftrightarrow", "Lleftarrow", "Longleftarrow", "Longleftrightarrow", "Longrightarrow", "Lsh",
+    "Mu", "Nu", "Omega", "Omicron", "Otimes", "Overrightarrow", "P", "Perp", "Phi", "Pi", "Pr",
+    "Psi", "QED", "Re", "Rho", "Rightarrow", "Rrightarrow", "Rsh", "S", "Sigma", "Space", "Subset",
+    "Supset", "Tau", "Theta", "Uparrow", "Updownarrow", "Upsilon", "Vdash", "Vvdash", "Xi", "Zeta",
+    "\\", "_", "acute", "aleph", "alpha", "amalg", "angle", "approx", "approxcolon",
+    "approxcoloncolon", "approxeq", "arccos", "arceq", "arcsin", "arctan", "arg", "ast",
+    "astrosun", "asymp", "backcong", "backdprime", "backepsilon", "backprime", "backsim",
+    "backsimeq", "backslash", "backtrprime", "ballotx", "bar", "barwedge", "because", "begin",
+    "begingroup", "beta", "beth", "between", "bf", "big", "bigcap", "bigcirc", "bigcup", "bigg",
+    "biggl", "biggm", "biggr", "bigl", "bigm", "bigodot", "bigoplus", "bigotimes", "bigr",
+    "bigsqcap", "bigsqcup", "bigstar", "bigtimes", "bigtriangledown", "bigtriangleup", "biguplus",
+    "bigvee", "bigwedge", "binom", "blackhourglass", "blacklozenge", "blacksquare",
+    "blacktriangle", "blacktriangledown", "blacktriangleleft", "blacktriangleright", "bmod",
+    "boldsymbol", "bot", "bowtie", "boxast", "boxbox", "boxcircle", "boxdot", "boxed", "boxminus",
+    "boxplus", "boxtimes", "breve", "buildrel", "bullet", "bumpeq", "cal", "cancel", "cap", "cdot",
+    "cdotp", "cdots", "cent", "centerdot", "cfrac", "char", "check", "checkmark", "chi", "circ",
+    "circeq", "circlearrowleft", "circlearrowright", "circledR", "circledS", "circledast",
+    "circledcirc", "circleddash", "circledequal", "circledparallel", "circledvert", "circlehbar",
+    "clap", "clubsuit", "coh", "colon", "colonapprox", "coloncolon", "coloncolonapprox",
+    "coloncolonequals", "coloncolonminus", "coloncolonsim", "coloneq", "coloneqq", "colonequals",
+    "colonminus", "colonsim", "color", "colorbox", "complement", "concavediamond",
+    "concavediamondtickleft", "concavediamondtickright", "cong", "coprod", "copyright", "cos",
+    "cosh", "cot", "coth", "cr", "csc", "cup", "curlyeqprec", "curlyeqsucc", "curlyvee",
+    "curlywedge", "curvearrowleft", "curvearrowright", "dag", "dagger", "daleth", "dashleftarrow",
+    "dashrightarrow", "dashv", "dbinom", "dblcolon", "ddag", "ddagger", "ddot", "ddots", "def",
+    "deg", "degree", "delta", "det", "dfrac", "diagdown", "diagup", "diameter", "diamond",
+    "diamondsuit", "digamma", "dim", "displaystyle", "div", "divideontimes", "dot", "doteq",
+    "doteqdot", "dotplus", "dots", "dotsb", "dotsc", "dotsi", "dotsm", "dotso", "doublebarwedge",
+    "doublecap", "downarrow", "downdownarrows", "downharpoonleft", "downharpoonright", "dprime",
+    "ell", "emph", "emptyset", "end", "endgroup", "enspace", "epsilon", "eqcirc", "eqcolon",
+    "eqdef", "eqeq", "eqqcolon", "eqref", "eqsim", "eqslantgtr", "eqslantless", "equiv", "eta",
+    "eth", "euro", "exists", "exp", "fallingdotseq", "fbox", "fcolorbox", "female", "fint", "flat",
+    "forall", "frac", "framebox", "frown", "fullouterjoin", "futurelet", "gamma", "gcd", "ge",
+    "genfrac", "geq", "geqq", "geqslant", "gets", "gg", "ggg", "gggtr", "gimel", "gnapprox",
+    "gneq", "gneqq", "gnsim", "grave", "gt", "gtrapprox", "gtrdot", "gtreqless", "gtreqqless",
+    "gtrless", "gtrsim", "gvertneqq", "hat", "hbar", "hbox", "heartsuit", "hom", "hookleftarrow",
+    "hookrightarrow", "hourglass", "hphantom", "hskip", "hslash", "hspace", "iddots", "idotsin",
+    "iff", "iiiint", "iiint", "iint", "imageof", "imath", "impliedby", "implies", "in", "inf",
+    "infty", "int", "intBar", "intbar", "intcap", "intclockwise", "intcup", "intercal", "intlarhk",
+    "intop", "intx", "iota", "isin", "it", "jmath", "kappa", "ker", "kern", "label", "lambda",
+    "land", "ldotp", "ldots", "le", "leadsto", "left", "leftarrow", "leftarrowtail",
+    "leftharpoondown", "leftharpoonup", "leftleftarrows", "leftmodels", "leftmoon",
+    "leftouterjoin", "leftrightarrow", "leftrightarrows", "leftrightharpoons",
+    "leftrightsquigarrow", "leftthreetimes", "leq", "leqq", "leqslant", "lessapprox", "lessdot",
+    "lesseqgtr", "lesseqqgtr", "lessgtr", "lesssim", "let", "lg", "lhd", "lightning", "lim",
+    "liminf", "limsup", "ll", "llap", "lll", "llless", "ln", "lnapprox", "lneq", "lneqq", "lnot",
+    "lnsim", "log", "longleftarrow", "longleftrightarrow", "longmapsto", "longrightarrow",
+    "looparrowleft", "looparrowright", "lor", "lower", "lozenge", "lozengeminus", "lq", "lt",
+    "ltimes", "lvertneqq", "male", "maltese", "mapsfrom", "mapsto", "mathbb", "mathbbit", "mathbf",
+    "mathbfcal", "mathbffrak", "mathbfit", "mathbfsfit", "mathbfsfup", "mathbfup", "mathbin",
+    "mathcal", "mathchoice", "mathclap", "mathclose", "mathellipsis", "mathfrak", "mathinner",
+    "mathit", "mathllap", "mathnormal", "mathop", "mathopen", "mathord", "mathpunct", "mathrel",
+    "mathring", "mathrlap", "mathrm", "mathsf", "mathsfit", "mathsfup", "mathsterling",
+    "mathstrut", "mathtt", "mathup", "max", "mbox", "measeq", "measuredangle", "medspace", "mho",
+    "mid", "middle", "min", "minuscolon", "minuscoloncolon", "mkern", "models", "mp", "mskip",
+    "mu", "multimap", "multimapboth", "multimapinv", "nLeftarrow", "nLeftrightarrow",
+    "nRightarrow", "nVDash", "nVdash", "nabla", "natural", "ncong", "ne", "nearrow", "neg",
+    "negmedspace", "negthickspace", "negthinspace", "neq", "newcommand", "newenvironment",
+    "nexists", "ngeq", "ngeqq", "ngeqslant", "ngtr", "ni", "nleftarrow", "nleftrightarrow", "nleq",
+    "nleqq", "nleqslant", "nless", "nmid", "nobreakspace", "nonumber", "not", "notag", "notin",
+    "notni", "nparallel", "nprec", "npreceq", "nrightarrow", "nshortmid", "nshortparallel", "nsim",
+    "nsubset", "nsubseteq", "nsubseteqq", "nsucc", "nsucceq", "nsupset", "nsupseteq", "nsupseteqq",
+    "ntriangleleft", "ntrianglelefteq", "ntriangleright", "ntrianglerighteq", "nu", "nvDash",
+    "nvdash", "nwarrow", "obar", "obslash", "odiv", "odot", "ogreaterthan", "oiiint", "oiint",
+    "oint", "olessthan", "omega", "omicron", "ominus", "operatorname", "operp", "oplus", "origof",
+    "oslash", "otimes", "otimeshat", "overbrace", "overbracket", "overgroup", "overleftarrow",
+    "overleftharpoon", "overleftrightarrow", "overline", "overparen", "overrightarrow",
+    "overrightharpoon", "overset", "owns", "parallel", "parr", "partial", "permil", "perp",
+    "phantom", "phi", "pi", "pitchfork", "pm", "pmod", "pointint", "pounds", "prec", "precapprox",
+    "preccurlyeq", "preceq", "precnapprox", "precneqq", "precnsim", "precsim", "prime", "prod",
+    "propto", "providecommand", "psi", "qprime", "qquad", "quad", "questeq", "raise", "raisebox",
+    "ratio", "ref", "relax", "renewcommand", "renewenvironment", "restriction", "rhd", "rho",
+    "right", "rightarrow", "rightarrowtail", "rightharpoondown", "rightharpoonup",
+    "rightleftarrows", "rightleftharpoons", "rightmoon", "rightouterjoin", "rightrightarrows",
+    "rightsquigarrow", "rightthreetimes", "risingdotseq", "rlap", "rm", "rppolint", "rq", "rtimes",
+    "rule", "scoh", "scpolint", "scriptscriptstyle", "scriptstyle", "searrow", "sec", "setminus",
+    "sf", "sgn", "sharp", "shortmid", "shortparallel", "sideset", "sigma", "sim", "simeq", "sin",
+    "sincoh", "sinh", "smallfrown", "smallint", "smallsetminus", "smallsmile", "smash", "smile",
+    "smiley", "spadesuit", "sphericalangle", "sqcap", "sqcup", "sqint", "sqrt", "sqsubset",
+    "sqsubseteq", "sqsupset", "sqsupseteq", "square", "sslash", "stackrel", "standardstate",
+    "star", "stareq", "strictfi", "strictif", "strut", "subset", "subseteq", "subseteqq",
+    "subsetneq", "subsetneqq", "substack", "succ", "succapprox", "succcurlyeq", "succeq",
+    "succnapprox", "succneqq", "succnsim", "succsim", "sum", "sun", "sup", "supset", "supseteq",
+    "supseteqq", "supsetneq", "supsetneqq", "surd", "swarrow", "symbb", "symbbit", "symbf",
+    "symbfcal", "symbffrak", "symbfit", "symbfsfit", "symbfsfup", "symbfup", "symcal", "symfrak",
+    "symit", "symnormal", "symrm", "symsf", "symsfit", "symsfup", "symtt", "symup", "tag", "tan",
+    "tanh", "tau", "tbinom", "text", "textbf", "textcolor", "textit", "textnormal", "textrm",
+    "textsf", "textstyle", "texttt", "textup", "tfrac", "therefore", "theta", "thickapprox",
+    "thicksim", "thickspace", "thinspace", "tilde", "times", "to", "top", "triangle",
+    "triangledown", "triangleleft", "trianglelefteq", "triangleminus", "triangleplus", "triangleq",
+    "triangleright", "trianglerighteq", "triangletimes", "trprime", "tt", "twoheadleftarrow",
+    "twoheadrightarrow", "underbar", "underbrace", "underbracket", "undergroup", "underleftarrow",
+    "underleftrightarrow", "underline", "underparen", "underrightarrow", "underset", "unlhd",
+    "unrhd", "uparrow", "updownarrow", "upharpoonleft", "upharpoonright", "uplus", "upsilon",
+    "upuparrows", "vDash", "varDelta", "varGamma", "varLambda", "varOmega", "varPhi", "varPi",
+    "varPsi", "varSigma", "varTheta", "varUpsilon", "varXi", "varclubsuit", "vardiamondsuit",
+    "varepsilon", "varheartsuit", "varkappa", "varnothing", "varointclockwise", "varphi", "varpi",
+    "varpropto", "varrho", "varsigma", "varspadesuit", "varsubsetneq", "varsubsetneqq",
+    "varsupsetneq", "varsupsetneqq", "vartheta", "vartriangle", "vartriangleleft",
+    "vartriangleright", "vcentcolon", "vcenter", "vdash", "vdots", "vec", "vee", "veebar", "veeeq",
+    "vphantom", "wedge", "wedgeq", "whitesquaretickleft", "whitesquaretickright", "widecheck",
+    "widehat", "wideparen", "widetilde", "with", "wp", "wr", "xi", "xleftarrow", "xrightarrow",
+    "yen", "zeta", "|", "~",
+];
+
+/// The environments handled by `InnerParser::begin_environment`, sorted, which is checked by a
+/// test.
+#[rustfmt::skip]
+pub const ENVIRONMENTS: [&str; 33] = [
+    "Bmatrix", "Bmatrix*", "Vmatrix", "Vmatrix*", "align", "align*", "alignat", "alignat*",
+    "aligned", "alignedat", "array", "bmatrix", "bmatrix*", "cases", "darray", "dcases", "drcases",
+    "equation", "equation*", "gather", "gather*", "gathered", "matrix", "matrix*", "multline",
+    "pmatrix", "pmatrix*", "rcases", "smallmatrix", "split", "subarray", "vmatrix", "vmatrix*",
+];
+
+/// Returns whether the environment is built-in, i.e., whether it is handled by `\begin` itself
+/// rather than defined in the macro context.
+pub fn is_environment(name: &str) -> bool {
+    ENVIRONMENTS.binary_search(&name).is_ok()
+}
+
+/// Returns whether the control sequence is a built-in primitive, i.e., whether it is handled by
+/// the parser rather than reported as unknown.
+pub fn is_primitive(cs: &str) -> bool {
+    PRIMITIVES.binary_search(&cs).is_ok()
+        || control_sequence_delimiter_map(cs).is_some()
+        || cs.trim_start().is_empty()
+}
+
 /// Returns the matching delimiter character for the given token, if it exists, along with whether
 /// the delimiter is an opening (left) delimiter.
 pub fn token_to_delim(token: Token) -> Option<(char, DelimiterType)> {
@@ -0,0 +1,505 @@
+//! Parsing of text mode, which is entered by commands such as `\text`, `\textbf`, etc.
+//!
+//! In text mode, characters are typeset as is, with the exception of ligatures (e.g., ``` `` ```
+//! and `--`), accents (e.g., `\'e`), control spaces, text font commands, and nested math
+//! delimited by `$...$` or `\(...\)`.
+
+use std::borrow::Cow;
+
+use crate::event::{
+    ColorChange as CC, ColorTarget as CT, ColumnAlignment, Content as C, Event as E, Font, Framing,
+    Grouping as G, GroupingKind, StateChange as SC, Visual as V,
+};
+
+use super::{lex, ErrorKind, InnerParser, InnerResult, Instruction as I};
+
+/// A text group whose parsing is suspended while a nested group is being parsed.
+struct Frame<'a> {
+    /// The content following the nested group.
+    rest: &'a str,
+    /// The font of the text group.
+    font: Option<Font>,
+    /// Whether the nested group emits `Begin` and `End` events.
+    grouped: bool,
+}
+
+impl<'b, 'store> InnerParser<'b, 'store> {
+    /// Parse the content of a text-mode argument, and push the resulting events to the buffer.
+    ///
+    /// Nested groups are parsed using an explicit stack, so that stack usage does not depend on
+    /// the input.
+    ///
+    /// Returns the number of elements pushed to the buffer, where a group counts as one element.
+    pub(super) fn text_mode(
+        &mut self,
+        mut content: &'store str,
+        mut font: Option<Font>,
+    ) -> InnerResult<usize> {
+        let mut stack: Vec<Frame<'store>> = Vec::new();
+        let mut grouped_depth = 0;
+        let mut element_count = 0;
+        let mut text: Cow<'store, str> = Cow::Borrowed("");
+
+        loop {
+            let Some(index) = content.find(['\\', '$', '{', '}', '~', '`', '\'', '-', '%']) else {
+                push_str(&mut text, content);
+                let Some(frame) = stack.pop() else {
+                    break;
+                };
+                if frame.grouped {
+                    self.push_text(&mut text);
+                    self.buffer.push(I::Event(E::End));
+                    grouped_depth -= 1;
+                }
+                content = frame.rest;
+                font = frame.font;
+                continue;
+            };
+            push_str(&mut text, &content[..index]);
+            let ligatures = font != Some(Font::Monospace);
+            let rest = &content[index..];
+            content = match rest
+                .chars()
+                .next()
+                .expect("a character was found at the index")
+            {
+                '%' => rest
+                    .split_once('\n')
+                    .map_or(&rest[rest.len()..], |(_, rest)| rest.trim_start()),
+                '~' => {
+                    text.to_mut().push('\u{A0}');
+                    &rest[1..]
+                }
+                '`' if ligatures && rest.starts_with("``") => {
+                    text.to_mut().push('“');
+                    &rest[2..]
+                }
+                '\'' if ligatures && rest.starts_with("''") => {
+                    text.to_mut().push('”');
+                    &rest[2..]
+                }
+                '`' if ligatures => {
+                    text.to_mut().push('‘');
+                    &rest[1..]
+                }
+                '\'' if ligatures => {
+                    text.to_mut().push('’');
+                    &rest[1..]
+                }
+                '-' if ligatures && rest.starts_with("---") => {
+                    text.to_mut().push('—');
+                    &rest[3..]
+                }
+                '-' if ligatures && rest.starts_with("--") => {
+                    text.to_mut().push('–');
+                    &rest[2..]
+                }
+                '`' | '\'' | '-' => {
+                    push_str(&mut text, &rest[..1]);
+                    &rest[1..]
+                }
+                '{' => {
+                    let mut rest = &rest[1..];
+                    let group = lex::group_content(&mut rest, GroupingKind::Normal)?;
+                    stack.push(Frame {
+                        rest,
+                        font,
+                        grouped: false,
+                    });
+                    group
+                }
+                '}' => return Err(ErrorKind::UnbalancedGroup(None)),
+                '$' => {
+                    let mut rest = &rest[1..];
+                    let math = math_content(&mut rest, GroupingKind::InlineMath)?;
+                    let count = self.push_math(&mut text, math);
+                    if grouped_depth == 0 {
+                        element_count += count;
+                    }
+                    rest
+                }
+                _ => {
+                    let call_site = rest;
+                    let mut rest = &rest[1..];
+                    let control_sequence = lex::text_control_sequence(&mut rest)?;
+                    if let Some(expansion) = self.expand_macro(control_sequence, call_site, rest)? {
+                        content = expansion;
+                        continue;
+                    }
+                    // The state change applied to the group, if the control sequence is a font or
+                    // color command. `None` means that the group keeps the current state.
+                    let state_change = match control_sequence {
+                        "text" => None,
+                        "textnormal" => Some(SC::Font(None)),
+                        "textrm" => Some(SC::Font(Some(Font::UpRight))),
+                        "textsf" => Some(SC::Font(Some(Font::SansSerif))),
+                        "texttt" => Some(SC::Font(Some(Font::Monospace))),
+                        "textbf" => Some(SC::Font(Some(bold(font)))),
+                        "textit" => Some(SC::Font(Some(italic(font)))),
+                        "textup" => Some(SC::Font(Some(upright(font)))),
+                        "emph" if is_italic(font) => Some(SC::Font(Some(upright(font)))),
+                        "emph" => Some(SC::Font(Some(italic(font)))),
+                        "textcolor" => {
+                            let color = lex::brace_argument(&mut rest)?;
+                            let color = lex::color(color).ok_or(ErrorKind::UnknownColor)?;
+                            Some(SC::Color(CC {
+                                color,
+                                target: CT::Text,
+                            }))
+                        }
+                        "(" => {
+                            let math = math_content(&mut rest, GroupingKind::ParenthesizedMath)?;
+                            let count = self.push_math(&mut text, math);
+                            if grouped_depth == 0 {
+                                element_count += count;
+                            }
+                            content = rest;
+                            continue;
+                        }
+                        "llap" | "rlap" | "clap" | "raisebox" | "hbox" | "mbox" | "fbox"
+                        | "framebox" => {
+                            let visual = text_box(control_sequence, &mut rest)?;
+                            let group = lex::text_argument(&mut rest)?;
+                            let count = self.push_text(&mut text) as usize + 1;
+                            if grouped_depth == 0 {
+                                element_count += count;
+                            }
+                            // The box is parsed as a group of the text, so that nested boxes do not
+                            // recurse.
+                            self.buffer.extend([
+                                I::Event(E::Visual(visual)),
+                                I::Event(E::Begin(G::Normal)),
+                            ]);
+                            stack.push(Frame {
+                                rest,
+                                font,
+                                grouped: true,
+                            });
+                            grouped_depth += 1;
+                            content = group;
+                            continue;
+                        }
+                        cs => {
+                            if let Some((combining, spacing)) = text_accent(cs) {
+                                let argument = lex::text_argument(&mut rest)?;
+                                push_accented(&mut text, argument, combining, spacing)?;
+                            } else if let Some(symbol) = text_symbol(cs) {
+                                push_str(&mut text, symbol);
+                            } else if matches!(cs, "hspace" | "rule")
+                                || self.macro_context.primitive(cs).is_some()
+                            {
+                                let count = self.push_text(&mut text) as usize;
+                                let start = self.buffer.len();
+                                self.text_primitive(cs, &mut rest)?;
+                                let pushed = &self.buffer[start..];
+                                if grouped_depth == 0 {
+                                    element_count += count + !pushed.is_empty() as usize;
+                                }
+                                // State changes apply to the rest of the text group, which is
+                                // grouped so that they do not leak out of the text.
+                                if !pushed.is_empty()
+                                    && pushed
+                                        .iter()
+                                        .all(|i| matches!(i, I::Event(E::StateChange(_))))
+                                {
+                                    self.buffer.insert(start, I::Event(E::Begin(G::Normal)));
+                                    stack.push(Frame {
+                                        rest: "",
+                                        font,
+                                        grouped: true,
+                                    });
+                                    grouped_depth += 1;
+                                }
+                            } else {
+                                // Unknown control sequences are displayed as is.
+                                push_str(&mut text, &call_site[..call_site.len() - rest.len()]);
+                            }
+                            content = rest;
+                            continue;
+                        }
+                    };
+
+                    let group = lex::text_argument(&mut rest)?;
+                    stack.push(Frame {
+                        rest,
+                        font,
+                        grouped: state_change.is_some(),
+                    });
+                    if let Some(state_change) = state_change {
+                        let count = self.push_text(&mut text) as usize + 1;
+                        if grouped_depth == 0 {
+                            element_count += count;
+                        }
+                        self.buffer.extend([
+                            I::Event(E::Begin(G::Normal)),
+                            I::Event(E::StateChange(state_change)),
+                        ]);
+                        grouped_depth += 1;
+                        if let SC::Font(group_font) = state_change {
+                            font = group_font;
+                        }
+                    }
+                    group
+                }
+            };
+        }
+
+        if self.push_text(&mut text) {
+            element_count += 1;
+        }
+        Ok(element_count)
+    }
+
+    /// Push the accumulated text to the buffer if it is not empty, and return whether it was
+    /// pushed.
+    fn push_text(&mut self, text: &mut Cow<'store, str>) -> bool {
+        if text.is_empty() {
+            return false;
+        }
+        let text = std::mem::replace(text, Cow::Borrowed(""));
+        self.buffer.push(I::Event(E::Content(C::Text(text))));
+        true
+    }
+
+    /// Push the events of a primitive that is valid in text mode, e.g., `\hspace` or one defined
+    /// through [`MacroSet::define_primitive`], whose arguments follow in `rest`.
+    ///
+    /// [`MacroSet::define_primitive`]: crate::MacroSet::define_primitive
+    fn text_primitive(&mut self, name: &'store str, rest: &mut &'store str) -> InnerResult<()> {
+        let content = std::mem::replace(&mut self.content, *rest);
+        // Scripts do not apply in text mode, so the state of the enclosing math is kept.
+        let skip_scripts = self.state.skip_scripts;
+        let result = self.handle_primitive(name);
+        self.state.skip_scripts = skip_scripts;
+        *rest = std::mem::replace(&mut self.content, content);
+        result
+    }
+
+    /// Push the accumulated text, followed by math nested in text mode as a group in the default
+    /// math font, and return the number of elements pushed.
+    fn push_math(&mut self, text: &mut Cow<'store, str>, math: &'store str) -> usize {
+        let count = self.push_text(text) as usize + 1;
+        self.buffer.extend([
+            I::Event(E::Begin(G::Normal)),
+            I::Event(E::StateChange(SC::Font(None))),
+            I::SubGroup {
+                content: math,
+                allowed_alignment_count: None,
+            },
+            I::Event(E::End),
+        ]);
+        count
+    }
+}
+
+/// Parse math nested in text mode up to the closing delimiter.
+fn math_content<'a>(input: &mut &'a str, grouping_kind: GroupingKind) -> InnerResult<&'a str> {
+    lex::content_with_suffix(input, &grouping_kind.closing_str()).map_err(|e| match e {
+        ErrorKind::MacroSuffixNotFound => ErrorKind::UnbalancedGroup(Some(grouping_kind)),
+        e => e,
+    })
+}
+
+/// Parse the arguments preceding the content of a box in text mode, e.g., the shift of
+/// `\raisebox`, and return the visual event that the box applies to its content.
+pub(super) fn text_box(control_sequence: &str, input: &mut &str) -> InnerResult<V> {
+    Ok(match control_sequence {
+        "llap" => V::Overlap(ColumnAlignment::Left),
+        "rlap" => V::Overlap(ColumnAlignment::Right),
+        "clap" => V::Overlap(ColumnAlignment::Center),
+        "raisebox" => {
+            let shift = lex::dimension_or_braced(input)?;
+            // The optional height and depth of the box are not supported.
+            if input.trim_start().starts_with('[') {
+                return Err(ErrorKind::Argument);
+            }
+            V::Raise(shift)
+        }
+        "hbox" | "mbox" => {
+            // Boxes of a given width (`\hbox to`) or stretched (`\hbox spread`) are not
+            // supported, and `\hbox` requires braces around its content.
+            if control_sequence == "hbox" && !input.trim_start().starts_with('{') {
+                return Err(ErrorKind::GroupArgument);
+            }
+            V::HorizontalBox
+        }
+        "fbox" => V::Framed(Framing::default()),
+        "framebox" => {
+            let width = lex::optional_argument(input)
+                .map(|mut width| lex::dimension(&mut width))
+                .transpose()?;
+            let alignment = match lex::optional_argument(input) {
+                None | Some("c") => ColumnAlignment::Center,
+                Some("l") => ColumnAlignment::Left,
+                Some("r") => ColumnAlignment::Right,
+                // Stretching the interword spaces of the content is not supported, so it is
+                // centered instead.
+                Some("s") => ColumnAlignment::Center,
+                Some(_) => return Err(ErrorKind::Argument),
+            };
+            V::Framed(Framing { width, alignment })
+        }
+        _ => unreachable!("the control sequence is a text box"),
+    })
+}
+
+/// Append `s` to the text, borrowing from the input whenever possible.
+fn push_str<'a>(text: &mut Cow<'a, str>, s: &'a str) {
+    if text.is_empty() {
+        *text = Cow::Borrowed(s);
+    } else if !s.is_empty() {
+        text.to_mut().push_str(s);
+    }
+}
+
+/// Append the `argument` of an accent command to the text, with the accent applied to its first
+/// character.
+fn push_accented(
+    text: &mut Cow<'_, str>,
+    argument: &str,
+    combining: char,
+    spacing: char,
+) -> InnerResult<()> {
+    let text = text.to_mut();
+    let argument = argument.trim();
+    if let Some(mut control_sequence) = argument.strip_prefix('\\') {
+        // Accents can be applied to symbols, e.g., `\'{\i}`.
+        let symbol = lex::text_control_sequence(&mut control_sequence)
+            .ok()
+            .and_then(text_symbol)
+            .ok_or(ErrorKind::UnknownPrimitive)?;
+        text.push_str(symbol);
+        text.push(combining);
+        return Ok(());
+    }
+    let mut chars = argument.chars();
+    match chars.next() {
+        Some(base) => {
+            text.push(base);
+            text.push(combining);
+            text.push_str(chars.as_str());
+        }
+        None => text.push(spacing),
+    }
+    Ok(())
+}
+
+pub(crate) fn bold(font: Option<Font>) -> Font {
+    match font {
+        Some(Font::Italic | Font::BoldItalic) => Font::BoldItalic,
+        Some(Font::SansSerif | Font::BoldSansSerif) => Font::BoldSansSerif,
+        Some(Font::SansSerifItalic | Font::SansSerifBoldItalic) => Font::SansSerifBoldItalic,
+        _ => Font::Bold,
+    }
+}
+
+pub(crate) fn italic(font: Option<Font>) -> Font {
+    match font {
+        Some(Font::Bold | Font::BoldItalic) => Font::BoldItalic,
+        Some(Font::SansSerif | Font::SansSerifItalic) => Font::SansSerifItalic,
+        Some(Font::BoldSansSerif | Font::SansSerifBoldItalic) => Font::SansSerifBoldItalic,
+        _ => Font::Italic,
+    }
+}
+
+pub(crate) fn upright(font: Option<Font>) -> Font {
+    match font {
+        Some(Font::BoldItalic) => Font::Bold,
+        Some(Font::SansSerifItalic) => Font::SansSerif,
+        Some(Font::SansSerifBoldItalic) => Font::BoldSansSerif,
+        None | Some(Font::Italic) => Font::UpRight,
+        Some(font) => font,
+    }
+}
+
+fn is_italic(font: Option<Font>) -> bool {
+    matches!(
+        font,
+        Some(Font::Italic | Font::BoldItalic | Font::SansSerifItalic | Font::SansSerifBoldItalic)
+    )
+}
+
+/// Returns the combining and spacing characters of a text accent command.
+fn text_accent(control_sequence: &str) -> Option<(char, char)> {
+    Some(match control_sequence {
+        "`" => ('\u{0300}', '`'),
+        "'" => ('\u{0301}', '´'),
+        "^" => ('\u{0302}', '^'),
+        "~" => ('\u{0303}', '~'),
+        "=" => ('\u{0304}', '¯'),
+        "u" => ('\u{0306}', '˘'),
+        "." => ('\u{0307}', '˙'),
+        "\"" => ('\u{0308}', '¨'),
+        "r" => ('\u{030A}', '˚'),
+        "H" => ('\u{030B}', '˝'),
+        "v" => ('\u{030C}', 'ˇ'),
+        "d" => ('\u{0323}', '.'),
+        "c" => ('\u{0327}', '¸'),
+        "k" => ('\u{0328}', '˛'),
+        "b" => ('\u{0331}', '_'),
+        _ => return None,
+    })
+}
+
+/// Returns the text produced by a control sequence that expands to a symbol or a space in text
+/// mode.
+fn text_symbol(control_sequence: &str) -> Option<&'static str> {
+    Some(match control_sequence {
+        // Control spaces
+        " " | "\n" | "\t" | "\r" => " ",
+        "," | "thinspace" => "\u{2009}",
+        ":" | ">" | "medspace" => "\u{205F}",
+        ";" | "thickspace" => "\u{2004}",
+        "enspace" => "\u{2002}",
+        "quad" => "\u{2003}",
+        "qquad" => "\u{2003}\u{2003}",
+        "nobreakspace" => "\u{A0}",
+        "!" | "negthinspace" | "/" => "",
+
+        // Escaped characters
+        "$" => "$",
+        "%" => "%",
+        "&" => "&",
+        "#" => "#",
+        "_" | "textunderscore" => "_",
+        "{" | "textbraceleft" => "{",
+        "}" | "textbraceright" => "}",
+        "textbackslash" => "\\",
+        "textasciitilde" => "~",
+        "textasciicircum" => "^",
+        "textbar" => "|",
+        "textless" => "<",
+        "textgreater" => ">",
+
+        // Symbols
+        "i" => "ı",
+        "j" => "ȷ",
+        "ss" => "ß",
+        "ae" => "æ",
+        "AE" => "Æ",
+        "oe" => "œ",
+        "OE" => "Œ",
+        "o" => "ø",
+        "O" => "Ø",
+        "aa" => "å",
+        "AA" => "Å",
+        "l" => "ł",
+        "L" => "Ł",
+        "S" => "§",
+        "P" => "¶",
+        "dag" => "†",
+        "ddag" => "‡",
+        "ldots" | "dots" | "textellipsis" => "…",
+        "textendash" => "–",
+        "textemdash" => "—",
+        "textquoteleft" => "‘",
+        "textquoteright" => "’",
+        "textquotedblleft" => "“",
+        "textquotedblright" => "”",
+        "copyright" | "textcopyright" => "©",
+        "textregistered" => "®",
+        "pounds" | "textsterling" => "£",
+        "textdegree" => "°",
+        _ => return None,
+    })
+}
@@ -0,0 +1,1260 @@
+//! A tree representation of the event stream, for structural analysis of formulas.
+//!
+//! The event stream produced by the [`Parser`] is flat: groups are delimited by [`Event::Begin`]
+//! and [`Event::End`], and the arguments of [`Event::Script`]s and [`Event::Visual`]s are the
+//! elements following them. The [`build`] function turns such a stream into a [`Tree`] of
+//! [`Node`]s, where every node owns its children, and the [`Visitor`] trait can be used to walk
+//! the tree.
+//!
+//! [`Parser`]: crate::Parser
+//!
+//! ## Example
+//!
+//! ```rust
+//! use pulldown_latex::{
+//!     event::Content,
+//!     tree::{self, Node, Visitor},
+//!     Parser, Storage,
+//! };
+//!
+//! /// Count the number of fractions in a formula.
+//! #[derive(Default)]
+//! struct FractionCounter(usize);
+//!
+//! impl<'a> Visitor<'a> for FractionCounter {
+//!     fn visit_node(&mut self, node: &Node<'a>) {
+//!         if let Node::Fraction { .. } = node {
+//!             self.0 += 1;
+//!         }
+//!         tree::walk_node(self, node);
+//!     }
+//! }
+//!
+//! let storage = Storage::new();
+//! let tree = tree::build(Parser::new(r"\frac{1}{\frac{2}{3}} + \sqrt{\frac{4}{5}}", &storage))
+//!     .unwrap();
+//!
+//! let mut counter = FractionCounter::default();
+//! counter.visit_nodes(&tree);
+//! assert_eq!(counter.0, 3);
+//! ```
+
+use std::{borrow::Cow, fmt::Display};
+
+use crate::event::{
+    ColumnAlignment, Content, Dimension, EnvironmentFlow, Event, Framing, Grouping, Line,
+    ScriptPosition, ScriptType, StateChange, Visual,
+};
+
+/// A node of the tree, which corresponds to an _element_ of the event stream.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Node<'a> {
+    /// See [`Event::Content`].
+    Content(Content<'a>),
+    /// A group that is not a mathematical environment, i.e., [`Grouping::Normal`] or
+    /// [`Grouping::LeftRight`].
+    Group {
+        /// The kind of the group.
+        grouping: Grouping,
+        /// The elements of the group.
+        children: Vec<Node<'a>>,
+    },
+    /// A mathematical environment, e.g., `array`, `align`, `matrix`, etc.
+    Environment(Environment<'a>),
+    /// A base and its script(s).
+    ///
+    /// At least one of `subscript` and `superscript` is `Some`.
+    Script {
+        /// The position of the scripts.
+        position: ScriptPosition,
+        /// The base of the scripts.
+        base: Box<Node<'a>>,
+        /// The subscript, if any.
+        subscript: Option<Box<Node<'a>>>,
+        /// The superscript, if any.
+        superscript: Option<Box<Node<'a>>>,
+    },
+    /// A fraction.
+    Fraction {
+        /// The size of the fraction bar, or `None` for the default size.
+        bar_size: Option<Dimension>,
+        /// The numerator.
+        numerator: Box<Node<'a>>,
+        /// The denominator.
+        denominator: Box<Node<'a>>,
+    },
+    /// A square root, or a root with an index.
+    Root {
+        /// The radicand.
+        radicand: Box<Node<'a>>,
+        /// The index of the root, or `None` for a square root.
+        index: Option<Box<Node<'a>>>,
+    },
+    /// The negation of the node, as in `\not=`.
+    Negation(Box<Node<'a>>),
+    /// A node surrounded by a frame, as in `\boxed{x}`.
+    Framed {
+        /// The appearance of the frame.
+        framing: Framing,
+        /// The framed node.
+        content: Box<Node<'a>>,
+    },
+    /// An invisible node which takes up space, as in `\phantom{x}`.
+    ///
+    /// See [`Visual::Phantom`].
+    Phantom {
+        /// Whether the width of the node is kept.
+        horizontal: bool,
+        /// Whether the height and depth of the node are kept.
+        vertical: bool,
+        /// The invisible node.
+        content: Box<Node<'a>>,
+    },
+    /// A node whose height, depth, or both are treated as zero, as in `\smash{x}`.
+    ///
+    /// See [`Visual::Smash`].
+    Smash {
+        /// Whether the height of the node is treated as zero.
+        top: bool,
+        /// Whether the depth of the node is treated as zero.
+        bottom: bool,
+        /// The smashed node.
+        content: Box<Node<'a>>,
+    },
+    /// A node with a width of zero, as in `\llap{x}`.
+    ///
+    /// See [`Visual::Overlap`].
+    Overlap {
+        /// The side(s) on which the node overlaps its surroundings.
+        alignment: ColumnAlignment,
+        /// The overlapping node.
+        content: Box<Node<'a>>,
+    },
+    /// A node shifted vertically, as in `\raisebox{1ex}{x}`.
+    ///
+    /// See [`Visual::Raise`].
+    Raise {
+        /// The distance by which the node is shifted up.
+        shift: Dimension,
+        /// The shifted node.
+        content: Box<Node<'a>>,
+    },
+    /// A choice between nodes depending on the style, as in `\mathchoice{a}{b}{c}{d}`.
+    ///
+    /// See [`Visual::MathChoice`].
+    MathChoice {
+        /// The node rendered in display style.
+        display: Box<Node<'a>>,
+        /// The node rendered in text style.
+        text: Box<Node<'a>>,
+        /// The node rendered in script style.
+        script: Box<Node<'a>>,
+        /// The node rendered in scriptscript style.
+        script_script: Box<Node<'a>>,
+    },
+    /// A node set in a horizontal box, as in `\mbox{x}`.
+    ///
+    /// See [`Visual::HorizontalBox`].
+    HorizontalBox(Box<Node<'a>>),
+    /// A node vertically centered on the math axis, as in `\vcenter{x}`.
+    ///
+    /// See [`Visual::VerticalCenter`].
+    VerticalCenter(Box<Node<'a>>),
+    /// See [`Event::Space`].
+    Space {
+        /// The amount of space to add before the element.
+        width: Option<Dimension>,
+        /// The amount of space to add after the element.
+        height: Option<Dimension>,
+        /// The amount of depth (space below the baseline) to add.
+        depth: Option<Dimension>,
+    },
+    /// See [`Event::Rule`].
+    Rule {
+        /// The width of the rectangle.
+        width: Dimension,
+        /// The height of the rectangle above the baseline.
+        height: Dimension,
+        /// The depth of the rectangle below the baseline.
+        depth: Dimension,
+    },
+    /// See [`Event::StateChange`].
+    ///
+    /// The state change applies to the following nodes of the same group and their children.
+    StateChange(StateChange),
+}
+
+impl<'a> Node<'a> {
+    /// Move the children of the node to the given list, leaving the node without children.
+    fn move_children(&mut self, children: &mut Vec<Node<'a>>) {
+        let mut take = |node: &mut Box<Node<'a>>| {
+            let empty = Node::Group {
+                grouping: Grouping::Normal,
+                children: Vec::new(),
+            };
+            children.push(std::mem::replace(&mut **node, empty));
+        };
+        match self {
+            Node::Group {
+                children: group, ..
+            } => children.append(group),
+            Node::Environment(environment) => {
+                for row in &mut environment.rows {
+                    for cell in &mut row.cells {
+                        children.append(cell);
+                    }
+                }
+            }
+            Node::Script {
+                base,
+                subscript,
+                superscript,
+                ..
+            } => {
+                take(base);
+                subscript.iter_mut().chain(superscript).for_each(take);
+            }
+            Node::Fraction {
+                numerator,
+                denominator,
+                ..
+            } => {
+                take(numerator);
+                take(denominator);
+            }
+            Node::Root { radicand, index } => {
+                take(radicand);
+                index.iter_mut().for_each(take);
+            }
+            Node::MathChoice {
+                display,
+                text,
+                script,
+                script_script,
+            } => [display, text, script, script_script]
+                .into_iter()
+                .for_each(take),
+            Node::Negation(content)
+            | Node::Framed { content, .. }
+            | Node::Phantom { content, .. }
+            | Node::Smash { content, .. }
+            | Node::Overlap { content, .. }
+            | Node::Raise { content, .. }
+            | Node::HorizontalBox(content)
+            | Node::VerticalCenter(content) => take(content),
+            Node::Content(_) | Node::Space { .. } | Node::Rule { .. } | Node::StateChange(_) => {}
+        }
+    }
+}
+
+impl<'a> Node<'a> {
+    /// The children of the node, in the order of the event stream.
+    fn children(&self) -> Vec<&Node<'a>> {
+        match self {
+            Node::Group { children, .. } => children.iter().collect(),
+            Node::Environment(environment) => environment
+                .rows
+                .iter()
+                .flat_map(|row| row.cells.iter().flatten())
+                .collect(),
+            Node::Script {
+                base,
+                subscript,
+                superscript,
+                ..
+            } => std::iter::once(&**base)
+                .chain(subscript.as_deref())
+                .chain(superscript.as_deref())
+                .collect(),
+            Node::Fraction {
+                numerator,
+                denominator,
+                ..
+            } => vec![numerator, denominator],
+            Node::Root { radicand, index } => std::iter::once(&**radicand)
+                .chain(index.as_deref())
+                .collect(),
+            Node::MathChoice {
+                display,
+                text,
+                script,
+                script_script,
+            } => vec![display, text, script, script_script],
+            Node::Negation(content)
+            | Node::Framed { content, .. }
+            | Node::Phantom { content, .. }
+            | Node::Smash { content, .. }
+            | Node::Overlap { content, .. }
+            | Node::Raise { content, .. }
+            | Node::HorizontalBox(content)
+            | Node::VerticalCenter(content) => vec![content],
+            Node::Content(_) | Node::Space { .. } | Node::Rule { .. } | Node::StateChange(_) => {
+                Vec::new()
+            }
+        }
+    }
+
+    /// Clone the node, using the given clones of its children in the order of
+    /// [`Node::children`].
+    fn clone_with_children(&self, children: Vec<Node<'a>>) -> Node<'a> {
+        let mut children = children.into_iter();
+        match self {
+            Node::Group { grouping, .. } => {
+                return Node::Group {
+                    grouping: grouping.clone(),
+                    children: children.collect(),
+                }
+            }
+            Node::Environment(environment) => {
+                let rows = environment
+                    .rows
+                    .iter()
+                    .map(|row| Row {
+                        cells: row
+                            .cells
+                            .iter()
+                            .map(|cell| children.by_ref().take(cell.len()).collect())
+                            .collect(),
+                        spacing: row.spacing,
+                        horizontal_lines: row.horizontal_lines.clone(),
+                        tag: row.tag.clone(),
+                        no_number: row.no_number,
+                        labels: row.labels.clone(),
+                    })
+                    .collect();
+                return Node::Environment(Environment {
+                    grouping: environment.grouping.clone(),
+                    start_lines: environment.start_lines.clone(),
+                    rows,
+                });
+            }
+            _ => {}
+        }
+
+        let mut next = || Box::new(children.next().expect("every child was cloned"));
+        match self {
+            Node::Script {
+                position,
+                subscript,
+                superscript,
+                ..
+            } => Node::Script {
+                position: *position,
+                base: next(),
+                subscript: subscript.as_ref().map(|_| next()),
+                superscript: superscript.as_ref().map(|_| next()),
+            },
+            Node::Fraction { bar_size, .. } => Node::Fraction {
+                bar_size: *bar_size,
+                numerator: next(),
+                denominator: next(),
+            },
+            Node::Root { index, .. } => Node::Root {
+                radicand: next(),
+                index: index.as_ref().map(|_| next()),
+            },
+            Node::MathChoice { .. } => Node::MathChoice {
+                display: next(),
+                text: next(),
+                script: next(),
+                script_script: next(),
+            },
+            Node::Negation(_) => Node::Negation(next()),
+            Node::Framed { framing, .. } => Node::Framed {
+                framing: *framing,
+                content: next(),
+            },
+            Node::Phantom {
+                horizontal,
+                vertical,
+                ..
+            } => Node::Phantom {
+                horizontal: *horizontal,
+                vertical: *vertical,
+                content: next(),
+            },
+            Node::Smash { top, bottom, .. } => Node::Smash {
+                top: *top,
+                bottom: *bottom,
+                content: next(),
+            },
+            Node::Overlap { alignment, .. } => Node::Overlap {
+                alignment: *alignment,
+                content: next(),
+            },
+            Node::Raise { shift, .. } => Node::Raise {
+                shift: *shift,
+                content: next(),
+            },
+            Node::HorizontalBox(_) => Node::HorizontalBox(next()),
+            Node::VerticalCenter(_) => Node::VerticalCenter(next()),
+            Node::Content(_) | Node::Space { .. } | Node::Rule { .. } | Node::StateChange(_) => {
+                self.clone()
+            }
+            Node::Group { .. } | Node::Environment(_) => unreachable!("handled above"),
+        }
+    }
+}
+
+/// The nodes of a formula, as built by [`build`].
+///
+/// The top-level nodes are accessed through [`Deref`](std::ops::Deref) to a slice.
+///
+/// The tree owns its nodes, and is dropped and cloned without recursion, so that a deeply nested
+/// tree does not overflow the stack. This is not the case of its individual nodes, which can be
+/// moved out of it with [`Tree::into_nodes`] and destructured freely.
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Tree<'a> {
+    nodes: Vec<Node<'a>>,
+}
+
+impl<'a> Tree<'a> {
+    /// Take ownership of the top-level nodes of the tree.
+    ///
+    /// Unlike the tree, the nodes are dropped recursively.
+    pub fn into_nodes(mut self) -> Vec<Node<'a>> {
+        std::mem::take(&mut self.nodes)
+    }
+}
+
+impl<'a> From<Vec<Node<'a>>> for Tree<'a> {
+    fn from(nodes: Vec<Node<'a>>) -> Self {
+        Self { nodes }
+    }
+}
+
+impl<'a> std::ops::Deref for Tree<'a> {
+    type Target = [Node<'a>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.nodes
+    }
+}
+
+/// A node whose children are being cloned.
+struct CloneFrame<'n, 'a> {
+    node: &'n Node<'a>,
+    children: Vec<&'n Node<'a>>,
+    clones: Vec<Node<'a>>,
+}
+
+impl<'n, 'a> CloneFrame<'n, 'a> {
+    fn new(node: &'n Node<'a>) -> Self {
+        Self {
+            node,
+            children: node.children(),
+            clones: Vec::new(),
+        }
+    }
+}
+
+impl Clone for Tree<'_> {
+    /// Clone the nodes bottom-up, one at a time.
+    fn clone(&self) -> Self {
+        let mut nodes = Vec::with_capacity(self.nodes.len());
+        let mut stack = Vec::new();
+        for node in &self.nodes {
+            stack.push(CloneFrame::new(node));
+            while let Some(top) = stack.last_mut() {
+                if let Some(&child) = top.children.get(top.clones.len()) {
+                    stack.push(CloneFrame::new(child));
+                    continue;
+                }
+                let top = stack.pop().expect("the stack is not empty");
+                let clone = top.node.clone_with_children(top.clones);
+                match stack.last_mut() {
+                    Some(parent) => parent.clones.push(clone),
+                    None => nodes.push(clone),
+                }
+            }
+        }
+        Self { nodes }
+    }
+}
+
+impl Drop for Tree<'_> {
+    /// Drop the nodes one at a time, after moving out their children.
+    fn drop(&mut self) {
+        let mut descendants = std::mem::take(&mut self.nodes);
+        while let Some(mut node) = descendants.pop() {
+            node.move_children(&mut descendants);
+        }
+    }
+}
+
+/// A mathematical environment, made of rows of cells.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Environment<'a> {
+    /// The kind of the environment.
+    pub grouping: Grouping,
+    /// The horizontal lines drawn before the first row.
+    pub start_lines: Box<[Line]>,
+    /// The rows of the environment.
+    pub rows: Vec<Row<'a>>,
+}
+
+/// A row of a mathematical [`Environment`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Row<'a> {
+    /// The cells of the row, separated by alignment marks (`&`) in `LaTeX`.
+    pub cells: Vec<Vec<Node<'a>>>,
+    /// The amount of space to add after the row.
+    pub spacing: Option<Dimension>,
+    /// The horizontal lines drawn after the row.
+    pub horizontal_lines: Box<[Line]>,
+    /// The custom tag of the row along with whether it is parenthesized, as given by `\tag`.
+    pub tag: Option<(Cow<'a, str>, bool)>,
+    /// Whether the row is not numbered, as specified by `\notag`.
+    pub no_number: bool,
+    /// The labels attached to the row.
+    pub labels: Vec<Cow<'a, str>>,
+}
+
+impl Row<'_> {
+    fn new() -> Self {
+        Self {
+            cells: vec![Vec::new()],
+            spacing: None,
+            horizontal_lines: Box::new([]),
+            tag: None,
+            no_number: false,
+            labels: Vec::new(),
+        }
+    }
+
+    /// Whether the row has no content, i.e., it comes from a trailing line break.
+    fn is_empty(&self) -> bool {
+        self.cells.len() == 1
+            && self.cells[0].is_empty()
+            && self.tag.is_none()
+            && !self.no_number
+            && self.labels.is_empty()
+    }
+}
+
+/// An error that occurred while building a tree with [`build`].
+#[derive(Debug)]
+pub enum TreeError<E> {
+    /// The event stream contained an error.
+    Event(E),
+    /// An [`Event::End`] was found while no group was open, or while an element was expected.
+    UnexpectedEnd,
+    /// An [`Event::EnvironmentFlow`] was found outside of a mathematical environment, or while an
+    /// element was expected.
+    UnexpectedEnvironmentFlow,
+    /// The event stream ended while a group was open, or while an element was expected.
+    UnexpectedEndOfInput,
+}
+
+impl<E: Display> Display for TreeError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TreeError::Event(e) => e.fmt(f),
+            TreeError::UnexpectedEnd => f.write_str("unexpected end of group"),
+            TreeError::UnexpectedEnvironmentFlow => {
+                f.write_str("unexpected alignment or line break")
+            }
+            TreeError::UnexpectedEndOfInput => f.write_str("unexpected end of input"),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for TreeError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TreeError::Event(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A node whose children are being collected.
+enum Frame<'a> {
+    Group {
+        grouping: Grouping,
+        children: Vec<Node<'a>>,
+    },
+    Environment {
+        environment: Environment<'a>,
+        /// The row currently being filled.
+        row: Row<'a>,
+    },
+    /// A node that takes a fixed number of elements following it.
+    Arguments {
+        event: Event<'a>,
+        arity: usize,
+        arguments: Vec<Node<'a>>,
+    },
+}
+
+/// Build a tree from a stream of events, such as a [`Parser`].
+///
+/// The tree is built, cloned and dropped without recursion, so deeply nested input does not
+/// overflow the stack. Other operations recurse through its depth, i.e., comparing, formatting or
+/// (de)serializing it, walking it with a [`Visitor`], and cloning or dropping nodes outside of a
+/// [`Tree`]. Their use on untrusted input should be bounded by
+/// [`ParserConfig::max_group_depth`], along with the limits on macro expansions.
+///
+/// [`ParserConfig::max_group_depth`]: crate::config::ParserConfig::max_group_depth
+///
+/// [`Parser`]: crate::Parser
+pub fn build<'a, I, E>(events: I) -> Result<Tree<'a>, TreeError<E>>
+where
+    I: IntoIterator<Item = Result<Event<'a>, E>>,
+{
+    let mut tree = Tree::default();
+    let mut stack = Vec::new();
+    let result = build_nodes(events, &mut tree.nodes, &mut stack);
+
+    // The nodes of the frames left open by an error are dropped with the tree, without recursion.
+    for frame in stack {
+        match frame {
+            Frame::Group { children, .. } => tree.nodes.extend(children),
+            Frame::Environment { environment, row } => {
+                tree.nodes.push(Node::Environment(environment));
+                tree.nodes.extend(row.cells.into_iter().flatten());
+            }
+            Frame::Arguments { arguments, .. } => tree.nodes.extend(arguments),
+        }
+    }
+    result.map(|()| tree)
+}
+
+/// Build the nodes of a tree from a stream of events, using the given stack of open frames.
+fn build_nodes<'a, I, E>(
+    events: I,
+    nodes: &mut Vec<Node<'a>>,
+    stack: &mut Vec<Frame<'a>>,
+) -> Result<(), TreeError<E>>
+where
+    I: IntoIterator<Item = Result<Event<'a>, E>>,
+{
+    for event in events {
+        let mut node = match event.map_err(TreeError::Event)? {
+            Event::Content(content) => Node::Content(content),
+            Event::Space {
+                width,
+                height,
+                depth,
+            } => Node::Space {
+                width,
+                height,
+                depth,
+            },
+            Event::Rule {
+                width,
+                height,
+                depth,
+            } => Node::Rule {
+                width,
+                height,
+                depth,
+            },
+            Event::StateChange(state_change) => Node::StateChange(state_change),
+            Event::Begin(grouping) if grouping.is_math_env() => {
+                stack.push(Frame::Environment {
+                    environment: Environment {
+                        grouping,
+                        start_lines: Box::new([]),
+                        rows: Vec::new(),
+                    },
+                    row: Row::new(),
+                });
+                continue;
+            }
+            Event::Begin(grouping) => {
+                stack.push(Frame::Group {
+                    grouping,
+                    children: Vec::new(),
+                });
+                continue;
+            }
+            event @ (Event::Visual(_) | Event::Script { .. }) => {
+                let arity = match event {
+                    Event::Visual(Visual::MathChoice) => 4,
+                    Event::Visual(Visual::Root | Visual::Fraction(_)) => 2,
+                    Event::Visual(
+                        Visual::SquareRoot
+                        | Visual::Negation
+                        | Visual::Framed(_)
+                        | Visual::Phantom { .. }
+                        | Visual::Smash { .. }
+                        | Visual::Overlap(_)
+                        | Visual::Raise(_)
+                        | Visual::HorizontalBox
+                        | Visual::VerticalCenter,
+                    ) => 1,
+                    Event::Script {
+                        ty: ScriptType::SubSuperscript,
+                        ..
+                    } => 3,
+                    _ => 2,
+                };
+                stack.push(Frame::Arguments {
+                    event,
+                    arity,
+                    arguments: Vec::with_capacity(arity),
+                });
+                continue;
+            }
+            Event::End => match stack.pop() {
+                Some(Frame::Group { grouping, children }) => Node::Group { grouping, children },
+                Some(Frame::Environment {
+                    mut environment,
+                    row,
+                }) => {
+                    // A trailing line break does not start a new row.
+                    if !row.is_empty() || environment.rows.is_empty() {
+                        environment.rows.push(row);
+                    }
+                    Node::Environment(environment)
+                }
+                Some(Frame::Arguments { .. }) | None => return Err(TreeError::UnexpectedEnd),
+            },
+            Event::EnvironmentFlow(flow) => {
+                let Some(Frame::Environment { environment, row }) = stack.last_mut() else {
+                    return Err(TreeError::UnexpectedEnvironmentFlow);
+                };
+                match flow {
+                    EnvironmentFlow::Alignment => row.cells.push(Vec::new()),
+                    EnvironmentFlow::NewLine {
+                        spacing,
+                        horizontal_lines,
+                    } => {
+                        let mut row = std::mem::replace(row, Row::new());
+                        row.spacing = spacing;
+                        row.horizontal_lines = horizontal_lines;
+                        environment.rows.push(row);
+                    }
+                    EnvironmentFlow::StartLines { lines } => environment.start_lines = lines,
+                    EnvironmentFlow::Tag { tag, parenthesized } => {
+                        row.tag = Some((tag, parenthesized))
+                    }
+                    EnvironmentFlow::NoNumber => row.no_number = true,
+                    EnvironmentFlow::Label(label) => row.labels.push(label),
+                }
+                continue;
+            }
+        };
+
+        // Give the completed node to its parent, completing the parents that have all of their
+        // arguments.
+        loop {
+            match stack.last_mut() {
+                None => {
+                    nodes.push(node);
+                    break;
+                }
+                Some(Frame::Group { children, .. }) => {
+                    children.push(node);
+                    break;
+                }
+                Some(Frame::Environment { row, .. }) => {
+                    row.cells
+                        .last_mut()
+                        .expect("an environment always has a cell")
+                        .push(node);
+                    break;
+                }
+                Some(Frame::Arguments {
+                    arity, arguments, ..
+                }) => {
+                    arguments.push(node);
+                    if arguments.len() < *arity {
+                        break;
+                    }
+                    let Some(Frame::Arguments {
+                        event, arguments, ..
+                    }) = stack.pop()
+                    else {
+                        unreachable!("the frame was just matched");
+                    };
+                    node = arguments_node(event, arguments);
+                }
+            }
+        }
+    }
+
+    if stack.is_empty() {
+        Ok(())
+    } else {
+        Err(TreeError::UnexpectedEndOfInput)
+    }
+}
+
+/// Build the node of a [`Event::Visual`] or [`Event::Script`] from its arguments.
+fn arguments_node<'a>(event: Event<'a>, arguments: Vec<Node<'a>>) -> Node<'a> {
+    let mut arguments = arguments.into_iter().map(Box::new);
+    let mut next = || arguments.next().expect("all arguments were collected");
+    match event {
+        Event::Visual(Visual::SquareRoot) => Node::Root {
+            radicand: next(),
+            index: None,
+        },
+        Event::Visual(Visual::Root) => Node::Root {
+            radicand: next(),
+            index: Some(next()),
+        },
+        Event::Visual(Visual::Fraction(bar_size)) => Node::Fraction {
+            bar_size,
+            numerator: next(),
+            denominator: next(),
+        },
+        Event::Visual(Visual::Negation) => Node::Negation(next()),
+        Event::Visual(Visual::Framed(framing)) => Node::Framed {
+            framing,
+            content: next(),
+        },
+        Event::Visual(Visual::Phantom {
+            horizontal,
+            vertical,
+        }) => Node::Phantom {
+            horizontal,
+            vertical,
+            content: next(),
+        },
+        Event::Visual(Visual::Smash { top, bottom }) => Node::Smash {
+            top,
+            bottom,
+            content: next(),
+        },
+        Event::Visual(Visual::Overlap(alignment)) => Node::Overlap {
+            alignment,
+            content: next(),
+        },
+        Event::Visual(Visual::Raise(shift)) => Node::Raise {
+            shift,
+            content: next(),
+        },
+        Event::Visual(Visual::MathChoice) => Node::MathChoice {
+            display: next(),
+            text: next(),
+            script: next(),
+            script_script: next(),
+        },
+        Event::Visual(Visual::HorizontalBox) => Node::HorizontalBox(next()),
+        Event::Visual(Visual::VerticalCenter) => Node::VerticalCenter(next()),
+        Event::Script { ty, position } => {
+            let base = next();
+            let (subscript, superscript) = match ty {
+                ScriptType::Subscript => (Some(next()), None),
+                ScriptType::Superscript => (None, Some(next())),
+                ScriptType::SubSuperscript => (Some(next()), Some(next())),
+            };
+            Node::Script {
+                position,
+                base,
+                subscript,
+                superscript,
+            }
+        }
+        _ => unreachable!("only visuals and scripts take arguments"),
+    }
+}
+
+/// A visitor over the nodes of a tree.
+///
+/// Every method has a default implementation that walks the children of the node, using the
+/// `walk_*` functions of this module. Overriding a method and calling the corresponding `walk_*`
+/// function allows for doing something before and/or after visiting the children.
+pub trait Visitor<'a> {
+    /// Visit a list of nodes, e.g., the output of [`build`].
+    fn visit_nodes(&mut self, nodes: &[Node<'a>]) {
+        for node in nodes {
+            self.visit_node(node);
+        }
+    }
+
+    /// Visit any node.
+    fn visit_node(&mut self, node: &Node<'a>) {
+        walk_node(self, node);
+    }
+
+    /// Visit a [`Node::Content`].
+    fn visit_content(&mut self, _content: &Content<'a>) {}
+
+    /// Visit a [`Node::Group`].
+    fn visit_group(&mut self, _grouping: &Grouping, children: &[Node<'a>]) {
+        self.visit_nodes(children);
+    }
+
+    /// Visit a [`Node::Environment`].
+    fn visit_environment(&mut self, environment: &Environment<'a>) {
+        walk_environment(self, environment);
+    }
+
+    /// Visit a cell of a [`Node::Environment`].
+    fn visit_cell(&mut self, cell: &[Node<'a>]) {
+        self.visit_nodes(cell);
+    }
+
+    /// Visit a [`Node::Script`].
+    fn visit_script(
+        &mut self,
+        _position: ScriptPosition,
+        base: &Node<'a>,
+        subscript: Option<&Node<'a>>,
+        superscript: Option<&Node<'a>>,
+    ) {
+        self.visit_node(base);
+        if let Some(subscript) = subscript {
+            self.visit_node(subscript);
+        }
+        if let Some(superscript) = superscript {
+            self.visit_node(superscript);
+        }
+    }
+
+    /// Visit a [`Node::Fraction`].
+    fn visit_fraction(
+        &mut self,
+        _bar_size: Option<Dimension>,
+        numerator: &Node<'a>,
+        denominator: &Node<'a>,
+    ) {
+        self.visit_node(numerator);
+        self.visit_node(denominator);
+    }
+
+    /// Visit a [`Node::Root`].
+    fn visit_root(&mut self, radicand: &Node<'a>, index: Option<&Node<'a>>) {
+        self.visit_node(radicand);
+        if let Some(index) = index {
+            self.visit_node(index);
+        }
+    }
+
+    /// Visit a [`Node::Negation`].
+    fn visit_negation(&mut self, negated: &Node<'a>) {
+        self.visit_node(negated);
+    }
+
+    /// Visit a [`Node::Framed`].
+    fn visit_framed(&mut self, _framing: Framing, content: &Node<'a>) {
+        self.visit_node(content);
+    }
+
+    /// Visit a [`Node::Phantom`].
+    fn visit_phantom(&mut self, _horizontal: bool, _vertical: bool, content: &Node<'a>) {
+        self.visit_node(content);
+    }
+
+    /// Visit a [`Node::Smash`].
+    fn visit_smash(&mut self, _top: bool, _bottom: bool, content: &Node<'a>) {
+        self.visit_node(content);
+    }
+
+    /// Visit a [`Node::Overlap`].
+    fn visit_overlap(&mut self, _alignment: ColumnAlignment, content: &Node<'a>) {
+        self.visit_node(content);
+    }
+
+    /// Visit a [`Node::Raise`].
+    fn visit_raise(&mut self, _shift: Dimension, content: &Node<'a>) {
+        self.visit_node(content);
+    }
+
+    /// Visit a [`Node::MathChoice`].
+    ///
+    /// The branches are given in the order display, text, script, and scriptscript.
+    fn visit_math_choice(&mut self, branches: [&Node<'a>; 4]) {
+        for branch in branches {
+            self.visit_node(branch);
+        }
+    }
+
+    /// Visit a [`Node::HorizontalBox`].
+    fn visit_horizontal_box(&mut self, content: &Node<'a>) {
+        self.visit_node(content);
+    }
+
+    /// Visit a [`Node::VerticalCenter`].
+    fn visit_vertical_center(&mut self, content: &Node<'a>) {
+        self.visit_node(content);
+    }
+
+    /// Visit a [`Node::Rule`].
+    fn visit_rule(&mut self, _width: Dimension, _height: Dimension, _depth: Dimension) {}
+
+    /// Visit a [`Node::Space`].
+    fn visit_space(
+        &mut self,
+        _width: Option<Dimension>,
+        _height: Option<Dimension>,
+        _depth: Option<Dimension>,
+    ) {
+    }
+
+    /// Visit a [`Node::StateChange`].
+    fn visit_state_change(&mut self, _state_change: &StateChange) {}
+}
+
+/// Dispatch the node to the corresponding method of the visitor.
+pub fn walk_node<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, node: &Node<'a>) {
+    match node {
+        Node::Content(content) => visitor.visit_content(content),
+        Node::Group { grouping, children } => visitor.visit_group(grouping, children),
+        Node::Environment(environment) => visitor.visit_environment(environment),
+        Node::Script {
+            position,
+            base,
+            subscript,
+            superscript,
+        } => visitor.visit_script(
+            *position,
+            base,
+            subscript.as_deref(),
+            superscript.as_deref(),
+        ),
+        Node::Fraction {
+            bar_size,
+            numerator,
+            denominator,
+        } => visitor.visit_fraction(*bar_size, numerator, denominator),
+        Node::Root { radicand, index } => visitor.visit_root(radicand, index.as_deref()),
+        Node::Negation(negated) => visitor.visit_negation(negated),
+        Node::Framed { framing, content } => visitor.visit_framed(*framing, content),
+        Node::Phantom {
+            horizontal,
+            vertical,
+            content,
+        } => visitor.visit_phantom(*horizontal, *vertical, content),
+        Node::Smash {
+            top,
+            bottom,
+            content,
+        } => visitor.visit_smash(*top, *bottom, content),
+        Node::Overlap { alignment, content } => visitor.visit_overlap(*alignment, content),
+        Node::Raise { shift, content } => visitor.visit_raise(*shift, content),
+        Node::MathChoice {
+            display,
+            text,
+            script,
+            script_script,
+        } => visitor.visit_math_choice([display, text, script, script_script]),
+        Node::HorizontalBox(content) => visitor.visit_horizontal_box(content),
+        Node::VerticalCenter(content) => visitor.visit_vertical_center(content),
+        Node::Rule {
+            width,
+            height,
+            depth,
+        } => visitor.visit_rule(*width, *height, *depth),
+        Node::Space {
+            width,
+            height,
+            depth,
+        } => visitor.visit_space(*width, *height, *depth),
+        Node::StateChange(state_change) => visitor.visit_state_change(state_change),
+    }
+}
+
+/// Visit every cell of the environment, row by row.
+pub fn walk_environment<'a, V: Visitor<'a> + ?Sized>(
+    visitor: &mut V,
+    environment: &Environment<'a>,
+) {
+    for row in &environment.rows {
+        for cell in &row.cells {
+            visitor.visit_cell(cell);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{ArrayColumn, ColumnAlignment},
+        Parser, Storage,
+    };
+
+    use super::*;
+
+    fn build_str(input: &str) -> Vec<Node<'_>> {
+        let storage = Storage::new();
+        let events = Parser::new(input, &storage)
+            .map(|event| event.map(Event::into_owned))
+            .collect::<Vec<_>>();
+        build(events).unwrap().into_nodes()
+    }
+
+    fn ordinary(content: char) -> Node<'static> {
+        Node::Content(Content::Ordinary {
+            content,
+            stretchy: false,
+        })
+    }
+
+    #[test]
+    fn scripts_and_visuals() {
+        let nodes = build_str(r"a_1^{b} \frac{c}{\sqrt[n]{d}}");
+        assert_eq!(
+            nodes,
+            [
+                Node::Script {
+                    position: ScriptPosition::Right,
+                    base: Box::new(ordinary('a')),
+                    subscript: Some(Box::new(Node::Content(Content::Number("1".into())))),
+                    superscript: Some(Box::new(Node::Group {
+                        grouping: Grouping::Normal,
+                        children: vec![ordinary('b')],
+                    })),
+                },
+                Node::Fraction {
+                    bar_size: None,
+                    numerator: Box::new(Node::Group {
+                        grouping: Grouping::Normal,
+                        children: vec![ordinary('c')],
+                    }),
+                    denominator: Box::new(Node::Group {
+                        grouping: Grouping::Normal,
+                        children: vec![Node::Root {
+                            radicand: Box::new(Node::Group {
+                                grouping: Grouping::Normal,
+                                children: vec![ordinary('d')],
+                            }),
+                            index: Some(Box::new(Node::Group {
+                                grouping: Grouping::Normal,
+                                children: vec![ordinary('n')],
+                            })),
+                        }],
+                    }),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn environment_rows_and_cells() {
+        let nodes = build_str(r"\begin{array}{cc} \hline a & b \\[2pt] \hline c & \end{array}");
+        let [Node::Environment(environment)] = nodes.as_slice() else {
+            panic!("expected a single environment, got {nodes:?}");
+        };
+        assert_eq!(
+            environment.grouping,
+            Grouping::Array(Box::new([
+                ArrayColumn::Column(ColumnAlignment::Center),
+                ArrayColumn::Column(ColumnAlignment::Center),
+            ]))
+        );
+        assert_eq!(*environment.start_lines, [Line::Solid]);
+        assert_eq!(environment.rows.len(), 2);
+        assert_eq!(
+            environment.rows[0].cells,
+            [vec![ordinary('a')], vec![ordinary('b')]]
+        );
+        assert!(environment.rows[0].spacing.is_some());
+        assert_eq!(*environment.rows[0].horizontal_lines, [Line::Solid]);
+        assert_eq!(environment.rows[1].cells, [vec![ordinary('c')], vec![]]);
+    }
+
+    #[test]
+    fn trailing_new_line() {
+        let nodes = build_str(r"\begin{matrix} a \\ b \\ \end{matrix}");
+        let [Node::Environment(environment)] = nodes.as_slice() else {
+            panic!("expected a single environment, got {nodes:?}");
+        };
+        assert_eq!(environment.rows.len(), 2);
+    }
+
+    #[test]
+    fn structural_errors() {
+        let build_events = |events: Vec<Event<'static>>| {
+            build(events.into_iter().map(Ok::<_, std::convert::Infallible>))
+        };
+        assert!(matches!(
+            build_events(vec![Event::End]),
+            Err(TreeError::UnexpectedEnd)
+        ));
+        assert!(matches!(
+            build_events(vec![Event::Begin(Grouping::Normal)]),
+            Err(TreeError::UnexpectedEndOfInput)
+        ));
+        assert!(matches!(
+            build_events(vec![
+                Event::Visual(Visual::Fraction(None)),
+                ordinary_event()
+            ]),
+            Err(TreeError::UnexpectedEndOfInput)
+        ));
+        assert!(matches!(
+            build_events(vec![Event::EnvironmentFlow(EnvironmentFlow::Alignment)]),
+            Err(TreeError::UnexpectedEnvironmentFlow)
+        ));
+    }
+
+    fn ordinary_event() -> Event<'static> {
+        Event::Content(Content::Ordinary {
+            content: 'x',
+            stretchy: false,
+        })
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let depth = 100_000;
+        let mut events = vec![Event::Begin(Grouping::Normal); depth];
+        events.extend(std::iter::repeat(Event::End).take(depth));
+        let tree = build(
+            events
+                .iter()
+                .cloned()
+                .map(Ok::<_, std::convert::Infallible>),
+        )
+        .unwrap();
+        assert_eq!(tree.len(), 1);
+
+        // The nodes of the groups left open are dropped without recursion as well.
+        events.pop();
+        assert!(matches!(
+            build(events.into_iter().map(Ok::<_, std::convert::Infallible>)),
+            Err(TreeError::UnexpectedEndOfInput)
+        ));
+    }
+
+    /// The depth of the deepest node of the list, computed without recursion.
+    fn tree_depth(nodes: &[Node<'_>]) -> usize {
+        let mut stack: Vec<_> = nodes.iter().map(|node| (node, 1)).collect();
+        let mut max_depth = 0;
+        while let Some((node, depth)) = stack.pop() {
+            max_depth = max_depth.max(depth);
+            stack.extend(node.children().into_iter().map(|child| (child, depth + 1)));
+        }
+        max_depth
+    }
+
+    #[test]
+    fn deep_clone_and_drop() {
+        // Nested fractions, whose numerators are groups.
+        let depth = 50_000;
+        let mut events = Vec::new();
+        for _ in 0..depth {
+            events.extend([
+                Event::Visual(Visual::Fraction(None)),
+                Event::Begin(Grouping::Normal),
+            ]);
+        }
+        events.push(ordinary_event());
+        for _ in 0..depth {
+            events.extend([Event::End, ordinary_event()]);
+        }
+        let tree = build(events.into_iter().map(Ok::<_, std::convert::Infallible>)).unwrap();
+
+        let clone = tree.clone();
+        drop(tree);
+        assert_eq!(tree_depth(&clone), 2 * depth + 1);
+    }
+
+    #[test]
+    fn clone() {
+        let nodes = build_str(
+            r"\begin{align} a_1^{\sqrt[n]{b}} & \boxed{c} \tag{1} \\ \raisebox{1pt}{d} \end{align}",
+        );
+        let tree = Tree::from(nodes.clone());
+        assert_eq!(tree.clone().into_nodes(), nodes);
+    }
+}
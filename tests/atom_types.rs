@@ -67,6 +67,9 @@ fn mathop_multi_char_renders_as_function() {
     let out = render(r"\mathop{foo}(x)");
     // Multi-letter \mathop should render like \operatorname{foo} → <mi>foo</mi>.
     assert!(out.contains("foo"), "expected 'foo' in {out}");
+    // Unlike `\operatorname`, the argument is not parsed, so it may contain anything.
+    let out = render(r"\mathop{x^2}");
+    assert!(out.contains("x^2"), "expected 'x^2' in {out}");
 }
 
 #[test]
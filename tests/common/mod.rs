@@ -63,6 +63,7 @@ pub fn show_errors(parser: Parser) -> Result<(), usize> {
     }
 }
 
+#[allow(dead_code)]
 pub fn tabled(file: &mut std::fs::File) -> anyhow::Result<()> {
     file.write_all(br#"<table style="max-width: 60vw; margin: auto;">"#)?;
     let mut rendered = RENDERED.lock().unwrap();
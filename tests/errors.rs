@@ -1,4 +1,7 @@
-use pulldown_latex::{push_mathml, Parser, Storage};
+use pulldown_latex::{
+    event::GroupingKind, parser::error::ErrorKind, push_mathml, MacroSet, Parser, ParserConfig,
+    Storage,
+};
 
 macro_rules! should_error {
     ($name:ident, $($input:literal),+ $(,)?) => {
@@ -148,3 +151,147 @@ fn escapes_html_special_chars() {
         );
     }
 }
+
+#[test]
+fn error_kind_and_span() {
+    let storage = Storage::new();
+    let input = r"a + \unknown b";
+    let error = Parser::new(input, &storage).find_map(Result::err).unwrap();
+
+    assert_eq!(error.kind(), &ErrorKind::UnknownPrimitive);
+    assert_eq!(&input[error.span()], r"\unknown");
+    assert!(error.expansions().is_empty());
+}
+
+#[test]
+fn error_expansion_chain() {
+    let storage = Storage::new();
+    let input = r"\def\inner{\bad} \def\outer#1{#1 \inner} x \outer{y}";
+    let error = Parser::new(input, &storage).find_map(Result::err).unwrap();
+
+    assert_eq!(error.kind(), &ErrorKind::UnknownPrimitive);
+    assert_eq!(&input[error.span()], r"\outer{y}");
+
+    let expansions = error.expansions();
+    assert_eq!(expansions.len(), 2);
+    assert_eq!(expansions[0].call_site(), r"\outer{y}");
+    assert_eq!(&input[expansions[0].span()], r"\outer{y}");
+    assert_eq!(expansions[1].call_site(), r"\inner");
+    assert_eq!(expansions[1].expanded(), r"\bad");
+    assert_eq!(&input[expansions[1].span()], r"\outer{y}");
+}
+
+#[test]
+fn unbalanced_group_kind() {
+    let storage = Storage::new();
+    let error = Parser::new(r"\frac{1", &storage)
+        .find_map(Result::err)
+        .unwrap();
+
+    assert_eq!(
+        error.kind(),
+        &ErrorKind::UnbalancedGroup(Some(GroupingKind::Normal))
+    );
+}
+
+fn first_error(input: &str, config: ParserConfig) -> Option<ErrorKind> {
+    let storage = Storage::new();
+    let mut parser = Parser::with_config(input, &storage, &MacroSet::new(), config);
+    let error = parser.find_map(Result::err)?;
+    Some(error.kind().clone())
+}
+
+#[test]
+fn parser_limits() {
+    let default = ParserConfig::default();
+    assert_eq!(
+        first_error(
+            r"\def\a{\a} \a",
+            ParserConfig {
+                max_expansion_depth: 8,
+                ..default
+            }
+        ),
+        Some(ErrorKind::MacroRecursionLimit)
+    );
+    assert_eq!(
+        first_error(
+            r"\def\a{aaaaaaaaaa} \a\a\a\a",
+            ParserConfig {
+                max_expansion_bytes: 32,
+                ..default
+            }
+        ),
+        Some(ErrorKind::ExpansionSizeLimit)
+    );
+    assert_eq!(
+        first_error(
+            "{{{a}}}",
+            ParserConfig {
+                max_group_depth: Some(2),
+                ..default
+            }
+        ),
+        Some(ErrorKind::GroupNestingLimit)
+    );
+    assert_eq!(
+        first_error(
+            "abcd",
+            ParserConfig {
+                max_events: Some(3),
+                ..default
+            }
+        ),
+        Some(ErrorKind::EventLimit)
+    );
+    assert_eq!(
+        first_error(
+            "abcd",
+            ParserConfig {
+                max_input_length: Some(3),
+                ..default
+            }
+        ),
+        Some(ErrorKind::InputLengthLimit)
+    );
+    assert_eq!(
+        first_error(
+            "abcd",
+            ParserConfig {
+                step_budget: Some(3),
+                ..default
+            }
+        ),
+        Some(ErrorKind::StepLimit)
+    );
+    assert_eq!(
+        first_error(
+            "{{{abcd}}}",
+            ParserConfig {
+                max_group_depth: Some(3),
+                max_events: Some(10),
+                max_input_length: Some(10),
+                step_budget: Some(10),
+                ..default
+            }
+        ),
+        None
+    );
+}
+
+#[test]
+fn limits_stop_error_recovery() {
+    let storage = Storage::new();
+    let config = ParserConfig {
+        error_recovery: true,
+        max_events: Some(2),
+        ..Default::default()
+    };
+    let parser = Parser::with_config(r"a \foo b c", &storage, &MacroSet::new(), config);
+    let events = parser.collect::<Vec<_>>();
+    assert_eq!(events.len(), 3);
+    assert_eq!(
+        events[2].as_ref().unwrap_err().kind(),
+        &ErrorKind::EventLimit
+    );
+}
@@ -1,6 +1,7 @@
 use pulldown_latex::{
-    config::{MathStyle, RenderConfig},
-    push_mathml, Parser, Storage,
+    config::{DisplayMode, MathStyle, RenderConfig},
+    event::{Content, Event},
+    push_mathml, Parser, ParserError, Storage,
 };
 
 fn render(input: &str) -> String {
@@ -121,3 +122,264 @@ fn boldsymbol_with_upright_style_forces_bold_upright() {
         "bold upright lowercase",
     );
 }
+
+#[test]
+fn owned_events_replay_after_storage_reset() {
+    let input = r"\def\f#1{\operatorname{f}(#1)} \f{x} + \text{text} = 12";
+    let expected = render(input);
+
+    let mut storage = Storage::new();
+    let events: Vec<pulldown_latex::Event<'static>> = Parser::new(input, &storage)
+        .map(|event| event.map(pulldown_latex::Event::into_owned))
+        .collect::<Result<_, _>>()
+        .unwrap();
+    storage.reset();
+
+    let events = std::thread::spawn(move || events).join().unwrap();
+    let mut out = String::new();
+    push_mathml(
+        &mut out,
+        events.into_iter().map(Ok::<_, std::convert::Infallible>),
+        RenderConfig::default(),
+    )
+    .unwrap();
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn error_recovery_renders_placeholder() {
+    let storage = Storage::new();
+    let config = pulldown_latex::ParserConfig {
+        error_recovery: true,
+        ..Default::default()
+    };
+    let parser = Parser::with_config(
+        r"a < \foo",
+        &storage,
+        &pulldown_latex::MacroSet::new(),
+        config,
+    );
+    let mut out = String::new();
+    push_mathml(&mut out, parser, RenderConfig::default()).unwrap();
+    assert!(
+        out.contains(r#"title="unknown primitive command found"><mtext>\foo</mtext></merror>"#),
+        "expected an error placeholder in output: {out}"
+    );
+}
+
+#[test]
+fn error_colors_keep_leading_zeros() {
+    let config = RenderConfig {
+        error_color: (255, 0, 8),
+        ..RenderConfig::default()
+    };
+    let out = render_with(r"\foo", config);
+    assert!(
+        out.contains(r#"<merror style="border-color: #ff0008">"#),
+        "expected a six digit error color: {out}"
+    );
+}
+
+#[test]
+fn references_link_through_an_html_anchor() {
+    let events = [
+        Event::Content(Content::Reference {
+            label: r#"eq:"a""#.into(),
+            number: Some("1".into()),
+            parenthesized: true,
+        }),
+        Event::Content(Content::Reference {
+            label: "eq:b".into(),
+            number: None,
+            parenthesized: false,
+        }),
+    ];
+    let mut out = String::new();
+    push_mathml(
+        &mut out,
+        events.into_iter().map(Ok::<_, ParserError>),
+        RenderConfig::default(),
+    )
+    .unwrap();
+    assert!(
+        out.contains(r##"<mtext><a href="#eq:&quot;a&quot;">(1)</a></mtext><mtext>??</mtext>"##),
+        "expected the resolved reference to be an anchor: {out}"
+    );
+}
+
+#[test]
+fn text_with_nested_math() {
+    let out = render(r"\text{if $x > 0$}");
+    assert!(
+        out.contains("<mtext>if&nbsp;</mtext><mrow><mi>x</mi>"),
+        "expected the nested math to be rendered as math: {out}"
+    );
+    assert!(
+        !out.contains('$'),
+        "unexpected dollar sign in output: {out}"
+    );
+}
+
+#[test]
+fn tags_are_written_in_a_label_cell() {
+    let out = render(
+        r#"\begin{align} a &= b \tag{1<2} \label{eq:"a"} \label{b} \\ c &= d \notag \\ e &= f \end{align}"#,
+    );
+    assert!(
+        out.contains(
+            r#"</mtd><mtd class="menv-tag menv-nonumber"><mrow id="eq:&quot;a&quot;"></mrow><mrow id="b"></mrow><mtext>(1&lt;2)</mtext></mtd></mtr>"#
+        ),
+        "expected a tag cell at the end of the first row: {out}"
+    );
+    assert!(
+        out.contains(r#"</mtd><mtd class="menv-tag menv-nonumber"></mtd></mtr>"#),
+        "expected an unnumbered second row: {out}"
+    );
+    assert_eq!(
+        out.matches("menv-tag").count(),
+        2,
+        "unexpected tag cells in output: {out}"
+    );
+
+    let out = render(r"\begin{equation*} x \tag*{A} \end{equation*}");
+    assert!(
+        out.contains(
+            r#"<mtd class="menv-tag menv-nonumber"><mtext>A</mtext></mtd></mtr></mtable>"#
+        ),
+        "expected an unparenthesized tag: {out}"
+    );
+}
+
+#[test]
+fn frames_are_single_cell_tables() {
+    let out = render(r"\boxed{x} \framebox[2cm][r]{y}");
+    assert!(
+        out.contains(
+            r#"<mtable class="mframed"><mtr><mtd><mrow displaystyle="true" scriptlevel="0"><mi>x</mi></mrow></mtd></mtr></mtable>"#
+        ),
+        "expected a framed display style group: {out}"
+    );
+    assert!(
+        out.contains(r#"<mtable class="mframed"><mtr><mtd class="cell-right" style="width: "#),
+        "expected a right aligned frame with a width: {out}"
+    );
+    assert!(
+        out.contains(r#"<mtext>y</mtext></mtd></mtr></mtable>"#),
+        "expected the frame to be closed: {out}"
+    );
+}
+
+#[test]
+fn phantoms_and_smashes_are_padded() {
+    let out = render(r"\phantom{x} \hphantom{y} \vphantom{z} \smash[t]{w}");
+    assert!(
+        out.contains(r#"<mphantom><mrow><mi>x</mi></mrow></mphantom>"#),
+        "expected a phantom: {out}"
+    );
+    assert!(
+        out.contains(
+            r#"<mpadded height="0" depth="0"><mphantom><mrow><mi>y</mi></mrow></mphantom></mpadded>"#
+        ),
+        "expected a horizontal phantom: {out}"
+    );
+    assert!(
+        out.contains(
+            r#"<mpadded width="0"><mphantom><mrow><mi>z</mi></mrow></mphantom></mpadded>"#
+        ),
+        "expected a vertical phantom: {out}"
+    );
+    assert!(
+        out.contains(r#"<mpadded height="0"><mrow><mi>w</mi></mrow></mpadded>"#),
+        "expected a smashed element: {out}"
+    );
+}
+
+#[test]
+fn overlaps_have_no_width() {
+    let out = render(r"\sum_{\mathclap{i < j}} \llap{a} \rlap{b}");
+    assert!(
+        out.contains(r#"<mpadded class="moverlap-center" width="0"><mrow><mrow><mi>i</mi>"#),
+        "expected a centered overlap: {out}"
+    );
+    assert!(
+        out.contains(
+            r#"<mpadded class="moverlap-left" width="0"><mrow><mtext>a</mtext></mrow></mpadded>"#
+        ),
+        "expected a left overlap: {out}"
+    );
+    assert!(
+        out.contains(r#"<mpadded width="0"><mtext>b</mtext></mpadded>"#),
+        "expected a right overlap: {out}"
+    );
+}
+
+#[test]
+fn rules_and_raised_elements() {
+    let out = render(r"\rule{1em}{2ex} \raisebox{0.5ex}{a}");
+    assert!(
+        out.contains(
+            r#"<mspace width="1em" height="2ex" depth="0em" mathbackground="currentColor" />"#
+        ),
+        "expected a filled space: {out}"
+    );
+    assert!(
+        out.contains(r#"<mpadded voffset="0.5ex"><mtext>a</mtext></mpadded>"#),
+        "expected a raised element: {out}"
+    );
+}
+
+#[test]
+fn boxes() {
+    let out = render(r"\mbox{ if }\vcenter{x}");
+    assert!(
+        out.contains(r#"<mrow><mtext>&nbsp;if&nbsp;</mtext></mrow>"#),
+        "expected a text box: {out}"
+    );
+    assert!(
+        out.contains(
+            r#"<mtable class="mvcenter"><mtr><mtd><mrow><mi>x</mi></mrow></mtd></mtr></mtable>"#
+        ),
+        "expected a vertically centered box: {out}"
+    );
+
+    let display = render_with(
+        r"\vcenter{x}",
+        RenderConfig {
+            display_mode: DisplayMode::Block,
+            ..RenderConfig::default()
+        },
+    );
+    assert!(
+        display.contains(r#"<mtd displaystyle="true"><mrow><mi>x</mi></mrow></mtd>"#),
+        "expected the cell to be in display style: {display}"
+    );
+}
+
+#[test]
+fn math_choice_renders_the_branch_of_the_current_style() {
+    let choice = r"\mathchoice{D}{T}{S}{SS}";
+    let out = render(&format!(
+        r"{choice} x^{choice} \frac{{{choice}}}{{2}} \sqrt[{choice}]{{2}}"
+    ));
+    assert_eq!(
+        out,
+        "<math display=\"inline\"><mrow><mrow><mi>T</mi></mrow></mrow>\
+         <msup><mi>x</mi><mrow><mrow><mi>S</mi></mrow></mrow></msup>\
+         <mfrac><mrow><mrow><mrow><mi>S</mi></mrow></mrow></mrow><mrow><mn>2</mn></mrow></mfrac>\
+         <mroot><mrow><mn>2</mn></mrow><mrow><mrow><mrow><mi>S</mi><mi>S</mi></mrow></mrow></mrow>\
+         </mroot></math>",
+    );
+
+    let out = render(&format!(
+        r"\displaystyle {choice} {{\scriptstyle {choice}}}"
+    ));
+    assert!(
+        out.contains("<mi>D</mi>"),
+        "expected the display branch: {out}"
+    );
+    assert!(
+        out.contains("<mi>S</mi></mrow></mrow></mrow>"),
+        "expected the script branch: {out}"
+    );
+    assert!(!out.contains("<mi>T</mi>"), "unexpected text branch: {out}");
+}
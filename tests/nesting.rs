@@ -0,0 +1,112 @@
+//! Regression tests for pathologically nested inputs.
+//!
+//! The parser and the renderer must not overflow the stack, no matter how deeply nested the input
+//! is. Every test is run on a thread with a small stack to make sure that stack usage is bounded.
+use pulldown_latex::{push_mathml, Parser, Storage};
+
+const STACK_SIZE: usize = 2 * 1024 * 1024;
+
+/// Parse and render the input on a thread with a small stack, and return whether parsing
+/// succeeded.
+fn parse_and_render(input: String) -> bool {
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || {
+            let storage = Storage::new();
+            let ok = Parser::new(&input, &storage).all(|event| event.is_ok());
+            let mut out = String::new();
+            push_mathml(&mut out, Parser::new(&input, &storage), Default::default()).unwrap();
+            ok
+        })
+        .unwrap()
+        .join()
+        .unwrap()
+}
+
+#[test]
+fn deeply_nested_groups() {
+    let depth = 1_000;
+    assert!(parse_and_render(format!(
+        "{}x{}",
+        "{".repeat(depth),
+        "}".repeat(depth)
+    )));
+}
+
+#[test]
+fn deeply_nested_scripts() {
+    let depth = 1_000;
+    assert!(parse_and_render(format!(
+        "{}x{}",
+        "x^{".repeat(depth),
+        "}".repeat(depth)
+    )));
+}
+
+#[test]
+fn deeply_nested_fractions() {
+    let depth = 1_000;
+    assert!(parse_and_render(format!(
+        "{}x{}",
+        r"\frac{1}{".repeat(depth),
+        "}".repeat(depth)
+    )));
+}
+
+#[test]
+fn deeply_nested_boxes() {
+    // Boxes in text mode are parsed as text groups, so they do not recurse.
+    let depth = 250;
+    assert!(parse_and_render(format!(
+        "{}x{}",
+        r"\raisebox{1pt}{\fbox{\mbox{\llap{".repeat(depth),
+        "}}}}".repeat(depth)
+    )));
+}
+
+#[test]
+fn long_chain_of_empty_groups() {
+    assert!(parse_and_render("{}".repeat(100_000)));
+}
+
+#[test]
+fn long_chain_of_definitions() {
+    // Definitions do not produce any event.
+    assert!(parse_and_render(r"\def\a{x}".repeat(100_000)));
+}
+
+#[test]
+fn long_chain_of_control_sequence_arguments() {
+    // Control sequences given as arguments are nested within one another.
+    assert!(!parse_and_render(format!("{} x", r"\sqrt".repeat(100_000))));
+    assert!(!parse_and_render(format!("{} x", r"\hat".repeat(100_000))));
+    assert!(parse_and_render(format!("{} x", r"\sqrt".repeat(48))));
+}
+
+#[test]
+fn nested_operator_names() {
+    // The name of an operator is flattened into text as soon as it is found.
+    let depth = 63;
+    assert!(parse_and_render(format!(
+        "{}x{}",
+        r"\operatorname{".repeat(depth),
+        "}".repeat(depth)
+    )));
+    assert!(!parse_and_render(format!(
+        "{}x{}",
+        r"\operatorname{".repeat(100),
+        "}".repeat(100)
+    )));
+    // Arguments and nested parsers share a single nesting limit.
+    assert!(!parse_and_render(format!(
+        "{}x{}",
+        format!(r"{}\operatorname{{", r"\sqrt".repeat(60)).repeat(depth),
+        "}".repeat(depth)
+    )));
+}
+
+#[test]
+fn recursive_macros() {
+    assert!(!parse_and_render(r"\def\a{\a\a} \a".to_string()));
+    assert!(!parse_and_render(r"\def\a#1{\a{#1#1}} \a x".to_string()));
+}
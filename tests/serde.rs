@@ -0,0 +1,105 @@
+#![cfg(feature = "serde")]
+
+use std::convert::Infallible;
+
+use pulldown_latex::{
+    config::{DisplayMode, MathStyle},
+    push_mathml, Event, MacroSet, Parser, ParserConfig, RenderConfig, Storage,
+};
+
+const INPUTS: &[&str] = &[
+    r"x^2 + \frac{1}{\sqrt[3]{y}} \leq \sum_{n=0}^\infty a_n",
+    r"\left( \mathbb{R} \middle| \boldsymbol{\alpha} \right] \not\in \Bigl\{ \sin \theta \Bigr\}",
+    r"\begin{array}{|l:c|r} 1 & 2 & 3 \\ \hline 4 & 5 & 6 \end{array}",
+    r"\begin{pmatrix*}[r] a & -b \\ c & d \end{pmatrix*} \begin{cases} 0 & x < 0 \\ 1 \end{cases}",
+    r"\begin{align} a &= b \\ &\neq c \end{align}",
+    r"\color{red} a \textcolor{#00ff00}{b} \colorbox{blue}{c} \fcolorbox{red}{blue}{d}",
+    r"\displaystyle a \scriptstyle b \kern1em c \hspace{2pt} d \, e \quad f",
+    r"\overbrace{a + b}^{n} \underset{x}{\lim} \operatorname{arg\,max} \text{if $x > 0$}",
+    r"\binom{n}{k} \cancel{x} \hat{y} \widetilde{abc} 3.14 \% \#",
+];
+
+fn render<'a>(events: impl IntoIterator<Item = Event<'a>>, config: RenderConfig<'a>) -> String {
+    let mut out = String::new();
+    push_mathml(
+        &mut out,
+        events.into_iter().map(Ok::<_, Infallible>),
+        config,
+    )
+    .unwrap();
+    out
+}
+
+#[test]
+fn event_round_trip() {
+    let storage = Storage::new();
+    for input in INPUTS {
+        let events = Parser::new(input, &storage)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let json = serde_json::to_string(&events).unwrap();
+        let deserialized: Vec<Event> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(deserialized, events, "input: {input}");
+        assert_eq!(
+            render(deserialized, RenderConfig::default()),
+            render(events, RenderConfig::default()),
+            "input: {input}"
+        );
+    }
+}
+
+#[test]
+fn error_event_round_trip() {
+    let storage = Storage::new();
+    let config = ParserConfig {
+        error_recovery: true,
+        ..Default::default()
+    };
+    let events = Parser::with_config(r"a \undefined {b", &storage, &MacroSet::new(), config)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let json = serde_json::to_string(&events).unwrap();
+    let deserialized: Vec<Event> = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(deserialized, events);
+    assert_eq!(
+        render(deserialized, RenderConfig::default()),
+        render(events, RenderConfig::default())
+    );
+}
+
+#[test]
+fn events_deserialize_without_borrowing() {
+    let storage = Storage::new();
+    let events = Parser::new(r"\sin x + \text{hello}", &storage)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let json = serde_json::to_vec(&events).unwrap();
+    let deserialized: Vec<Event<'static>> = serde_json::from_reader(json.as_slice()).unwrap();
+
+    assert_eq!(deserialized, events);
+}
+
+#[test]
+fn config_round_trip() {
+    let config = RenderConfig {
+        display_mode: DisplayMode::Block,
+        annotation: Some("x^2"),
+        error_color: (1, 2, 3),
+        xml: true,
+        math_style: MathStyle::French,
+    };
+    let json = serde_json::to_string(&config).unwrap();
+    let deserialized: RenderConfig = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, config);
+
+    let parser_config = ParserConfig {
+        error_recovery: true,
+        max_events: Some(10),
+        ..Default::default()
+    };
+    let json = serde_json::to_string(&parser_config).unwrap();
+    let deserialized: ParserConfig = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, parser_config);
+}